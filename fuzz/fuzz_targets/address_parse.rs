@@ -0,0 +1,12 @@
+#![no_main]
+
+use {anoma_primitives::Address, libfuzzer_sys::fuzz_target};
+
+fuzz_target!(|path: &str| {
+  if let Ok(address) = Address::new(path) {
+    assert_eq!(address.to_string(), path);
+    for ancestor in address.ancestors() {
+      assert!(ancestor.is_parent_of(&address));
+    }
+  }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+use {
+  anoma_network::{Codec, Config},
+  asynchronous_codec::{BytesMut, Decoder, Encoder},
+  libfuzzer_sys::fuzz_target,
+};
+
+fuzz_target!(|data: &[u8]| {
+  let mut codec = Codec::new(Config::default().max_transmit_size);
+  let mut reencoder = Codec::new(usize::MAX);
+  let mut src = BytesMut::from(data);
+
+  // a stream may carry any number of frames, malformed
+  // frames are rejected without panicking.
+  while let Ok(Some(message)) = codec.decode(&mut src) {
+    let mut dst = BytesMut::new();
+    reencoder.encode(message, &mut dst).unwrap();
+    assert!(reencoder.decode(&mut dst).unwrap().is_some());
+  }
+});
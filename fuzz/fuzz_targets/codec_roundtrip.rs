@@ -0,0 +1,62 @@
+#![no_main]
+
+use {
+  anoma_network::{Action, AddressablePeer, Codec, Message},
+  asynchronous_codec::{BytesMut, Decoder, Encoder},
+  libfuzzer_sys::fuzz_target,
+  std::collections::HashSet,
+};
+
+fuzz_target!(|message: Message| {
+  let mut codec = Codec::new(usize::MAX);
+  let mut encoded = BytesMut::new();
+  codec.encode(message.clone(), &mut encoded).unwrap();
+
+  let decoded = codec.decode(&mut encoded).unwrap().unwrap();
+  assert!(encoded.is_empty());
+
+  // peer address sets may be encoded in a different order,
+  // so decoded messages are compared instead of their bytes.
+  assert_eq!(decoded.topic, message.topic);
+  assert!(
+    same_action(&decoded.action, &message.action),
+    "{decoded:?} decoded from {message:?}"
+  );
+});
+
+fn same_action(a: &Action, b: &Action) -> bool {
+  match (a, b) {
+    (Action::Join(a), Action::Join(b)) => same_peer(&a.node, &b.node),
+    (Action::ForwardJoin(a), Action::ForwardJoin(b)) => {
+      a.hop == b.hop && same_peer(&a.node, &b.node)
+    }
+    (Action::Neighbour(a), Action::Neighbour(b)) => {
+      a.high_priority == b.high_priority && same_peer(&a.peer, &b.peer)
+    }
+    (Action::Shuffle(a), Action::Shuffle(b)) => {
+      a.hop == b.hop
+        && same_peer(&a.origin, &b.origin)
+        && same_peers(&a.peers, &b.peers)
+    }
+    (Action::ShuffleReply(a), Action::ShuffleReply(b)) => {
+      same_peers(&a.peers, &b.peers)
+    }
+    (Action::Gossip(a), Action::Gossip(b)) => a == b,
+    _ => false,
+  }
+}
+
+/// Equality of peers compares their ids only, this also compares addresses.
+fn same_peer(a: &AddressablePeer, b: &AddressablePeer) -> bool {
+  a.peer_id == b.peer_id && a.addresses == b.addresses
+}
+
+fn same_peers(
+  a: &HashSet<AddressablePeer>,
+  b: &HashSet<AddressablePeer>,
+) -> bool {
+  a.len() == b.len()
+    && a
+      .iter()
+      .all(|p| b.get(p).map_or(false, |q| same_peer(p, q)))
+}
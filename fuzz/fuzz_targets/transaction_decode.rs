@@ -0,0 +1,14 @@
+#![no_main]
+
+use {
+  anoma_primitives::{encoding, Limits, Transaction},
+  libfuzzer_sys::fuzz_target,
+};
+
+fuzz_target!(|data: &[u8]| {
+  // transactions received from peers are decoded and
+  // validated before anything else looks at them.
+  if let Ok(tx) = encoding::decode::<Transaction>(data) {
+    let _ = tx.validate(&Limits::default());
+  }
+});
@@ -0,0 +1,43 @@
+#![no_main]
+
+use {
+  anoma_primitives::{Account, AccountChange, Address, Limits, Transaction},
+  anoma_vm::{InMemoryStateStore, Rules, State, StateDiff},
+  libfuzzer_sys::fuzz_target,
+  std::collections::BTreeMap,
+};
+
+fuzz_target!(|input: (BTreeMap<Address, Account>, Transaction)| {
+  let (accounts, tx) = input;
+  let limits = Limits::default();
+
+  // accounts in the state were created by valid transactions
+  let genesis = Transaction::new(
+    vec![],
+    accounts
+      .iter()
+      .map(|(addr, acc)| {
+        (addr.clone(), AccountChange::CreateAccount(acc.clone()))
+      })
+      .collect(),
+  );
+  if genesis.validate(&limits).is_err() || tx.validate(&limits).is_err() {
+    return;
+  }
+
+  let mut diff = StateDiff::default();
+  for (address, account) in accounts {
+    diff.set(address, account);
+  }
+  let mut state = InMemoryStateStore::default();
+  state.apply(diff);
+
+  // the transaction may be rejected, but without panicking
+  for hierarchy in [false, true] {
+    let rules = Rules {
+      hierarchy,
+      ..Rules::new("fuzznet")
+    };
+    let _ = anoma_vm::expand(tx.clone(), &rules, &state);
+  }
+});
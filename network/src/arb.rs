@@ -0,0 +1,58 @@
+//! Generators of random wire messages for fuzzing.
+
+use {
+  crate::wire::{AddressablePeer, Message},
+  arbitrary::{Arbitrary, Result, Unstructured},
+  bytes::Bytes,
+  libp2p::{
+    core::identity::{ed25519, PublicKey},
+    multiaddr::Protocol,
+    Multiaddr,
+  },
+  std::{
+    collections::HashSet,
+    net::{Ipv4Addr, Ipv6Addr},
+  },
+};
+
+impl<'a> Arbitrary<'a> for AddressablePeer {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    let secret = ed25519::SecretKey::from_bytes(u.arbitrary::<[u8; 32]>()?)
+      .expect("any 32 bytes are a valid ed25519 secret key");
+    let keypair = ed25519::Keypair::from(secret);
+
+    let mut addresses = HashSet::new();
+    for _ in 0..u.int_in_range(0..=4)? {
+      addresses.insert(multiaddr(u)?);
+    }
+
+    Ok(AddressablePeer {
+      peer_id: PublicKey::Ed25519(keypair.public()).to_peer_id(),
+      addresses,
+    })
+  }
+}
+
+/// A tcp address of a peer over ipv4 or ipv6.
+fn multiaddr(u: &mut Unstructured) -> Result<Multiaddr> {
+  let ip = match u.arbitrary()? {
+    true => Protocol::Ip4(Ipv4Addr::from(u.arbitrary::<u32>()?)),
+    false => Protocol::Ip6(Ipv6Addr::from(u.arbitrary::<u128>()?)),
+  };
+  Ok(
+    Multiaddr::empty()
+      .with(ip)
+      .with(Protocol::Tcp(u.arbitrary()?)),
+  )
+}
+
+impl<'a> Arbitrary<'a> for Message {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    Ok(Message::new(u.arbitrary()?, u.arbitrary()?))
+  }
+}
+
+/// Opaque gossip payloads.
+pub(crate) fn bytes(u: &mut Unstructured) -> Result<Bytes> {
+  Ok(Bytes::copy_from_slice(u.arbitrary()?))
+}
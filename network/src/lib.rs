@@ -1,3 +1,5 @@
+#[cfg(feature = "arbitrary")]
+mod arb;
 mod behaviour;
 mod cache;
 mod channel;
@@ -12,6 +14,12 @@ mod wire;
 
 pub mod topic;
 
+/// Wire protocol internals, exposed for fuzzing.
+#[cfg(feature = "arbitrary")]
+pub use {
+  codec::Codec,
+  wire::{Action, AddressablePeer, Message},
+};
 pub use {
   config::Config,
   libp2p::{core::identity::Keypair, multiaddr},
@@ -51,6 +51,7 @@ impl std::hash::Hash for AddressablePeer {
 
 /// Message sent to a bootstrap node to initiate network join
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Join {
   /// Identity and address of the local node that is trying
   /// to join the p2p network.
@@ -64,6 +65,7 @@ pub struct Join {
 /// procedure. They will send a [`Neighbor`] message to
 /// the joining node.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ForwardJoin {
   /// Hop counter. Incremented with every network hop.
   pub hop: u16,
@@ -76,6 +78,7 @@ pub struct ForwardJoin {
 /// Sent as a response to JOIN, FORWARDJOIN to the initating node,
 /// or if a node is being moved from passive to active view.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Neighbour {
   /// Identity and address of the peer that is attempting
   /// to add this local node to its active view.
@@ -93,6 +96,7 @@ pub struct Neighbour {
 /// This message is forwarded for up to N hops.
 /// N is configurable in [`network::Config`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Shuffle {
   /// Hop counter. Incremented with every network hop.
   pub hop: u16,
@@ -110,6 +114,7 @@ pub struct Shuffle {
 /// local node. This reply is sent by every node that receives
 /// the shuffle message.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ShuffleReply {
   /// A sample of known peers to the local node minus all
   /// nodes listed in the SHUFFLE message.
@@ -117,13 +122,17 @@ pub struct ShuffleReply {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Action {
   Join(Join),
   ForwardJoin(ForwardJoin),
   Neighbour(Neighbour),
   Shuffle(Shuffle),
   ShuffleReply(ShuffleReply),
-  Gossip(Bytes),
+  Gossip(
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::arb::bytes))]
+    Bytes,
+  ),
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -17,6 +17,7 @@ use {
 /// modification to the latter will trigger validity predicates stored at
 /// addresses.
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Account {
   /// Arbitary data stored within an account, this could be some token
   /// balance for a user wallet, wasm bytecode that acts as a predicate,
@@ -33,6 +34,7 @@ pub struct Account {
   ///
   /// Any state write will invoke all predicates and occurs only if they all
   /// return true, otherwise state change is rejected.
+  #[serde(with = "crate::readable::b58")]
   pub state: Vec<u8>,
 
   /// A Boolean Expressions Tree of predicates for an account.
@@ -4,7 +4,7 @@ use {
   serde::{Deserialize, Serialize},
 };
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AddressError {
   EmptyPath,
   EmptyPathSegment,
@@ -56,6 +56,18 @@ impl Iterator for AncestorIterator {
   }
 }
 
+/// Address of the account that governs the account hierarchy.
+///
+/// Chains that enable hierarchy rules enforce them when accounts are
+/// created or deleted: every account other than top-level accounts must
+/// have an existing parent, and top-level accounts can only be created
+/// while this account exists and its predicates are satisfied.
+///
+/// This account is a top-level account itself. It is created without
+/// any guard, usually in genesis, and can't be deleted afterwards.
+/// Chains without hierarchy rules treat it as any other account.
+pub const ROOT_ACCOUNT: &str = "/root";
+
 /// Represents an address of an account.
 #[derive(
   Clone, PartialEq, Eq, Hash, Serialize, Deserialize, PartialOrd, Ord,
@@ -117,6 +129,11 @@ impl Address {
     AncestorIterator::new(self.clone())
   }
 
+  /// The closest ancestor, or `None` for top-level addresses.
+  pub fn parent(&self) -> Option<Self> {
+    self.ancestors().next()
+  }
+
   pub fn combine(
     &self,
     segment: impl AsRef<str>,
@@ -132,6 +149,19 @@ impl Address {
     prefix.push('/');
     other.0.starts_with(&prefix)
   }
+
+  /// Moves this address from one subtree to another, keeping its
+  /// path relative to the subtree root.
+  ///
+  /// Returns `None` if the address is not in the subtree rooted at `from`.
+  pub fn rebase(&self, from: &Self, to: &Self) -> Option<Self> {
+    match self == from {
+      true => Some(to.clone()),
+      false => from
+        .is_parent_of(self)
+        .then(|| Self(alloc::format!("{}{}", to.0, &self.0[from.0.len()..]))),
+    }
+  }
 }
 
 impl Display for Address {
@@ -162,7 +192,10 @@ impl FromStr for Address {
 
 #[cfg(test)]
 mod tests {
-  use crate::{address::AddressError, Address};
+  use {
+    crate::{address::AddressError, Address},
+    proptest::prelude::*,
+  };
 
   #[test]
   fn construction() {
@@ -210,6 +243,8 @@ mod tests {
     let token_usda_wallet = token_usda.combine("walletaddr1")?;
 
     assert_eq!(token.ancestors().count(), 0);
+    assert_eq!(token.parent(), None);
+    assert_eq!(token_usda_wallet.parent(), Some(token_usda.clone()));
     assert_eq!(token_usda.ancestors().count(), 1);
     assert_eq!(token_usda_wallet.ancestors().count(), 2);
 
@@ -222,4 +257,38 @@ mod tests {
 
     Ok(())
   }
+
+  #[test]
+  fn rebase() -> Result<(), AddressError> {
+    let from = Address::new("/pgqf/1")?;
+    let to = Address::new("/archive/pgqf/1")?;
+
+    assert_eq!(from.rebase(&from, &to), Some(to.clone()));
+    assert_eq!(
+      Address::new("/pgqf/1/project/2")?.rebase(&from, &to),
+      Some(Address::new("/archive/pgqf/1/project/2")?)
+    );
+    assert_eq!(Address::new("/pgqf/10")?.rebase(&from, &to), None);
+    assert_eq!(Address::new("/pgqf")?.rebase(&from, &to), None);
+
+    Ok(())
+  }
+
+  proptest! {
+    #[test]
+    fn parses_valid_paths(path in "(/[a-zA-Z0-9._-]{1,8}){1,4}") {
+      let address = Address::new(&path).unwrap();
+      prop_assert_eq!(address.to_string(), path);
+      for ancestor in address.ancestors() {
+        prop_assert!(ancestor.is_parent_of(&address));
+      }
+    }
+
+    #[test]
+    fn parsing_never_panics(path in "[/a-z.$ ]{0,16}|\\PC*") {
+      if let Ok(address) = Address::new(&path) {
+        prop_assert_eq!(address.to_string(), path);
+      }
+    }
+  }
 }
@@ -0,0 +1,67 @@
+//! Generators of random primitives for fuzzing and property tests.
+//!
+//! Generated values are well-formed, so they can be encoded and
+//! decoded, but not necessarily valid. Transactions may reference
+//! missing intents, nest predicates too deep or exceed other limits,
+//! see [`crate::Limits`].
+
+use {
+  crate::{Address, Intent, Predicate, Transaction},
+  alloc::string::String,
+  arbitrary::{Arbitrary, Result, Unstructured},
+};
+
+impl<'a> Arbitrary<'a> for Address {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    let mut path = String::new();
+    for _ in 0..u.int_in_range(1..=4)? {
+      path.push('/');
+      for _ in 0..u.int_in_range(1..=8)? {
+        path.push(segment_char(u)?);
+      }
+    }
+    Ok(Address::new(path).expect("generated paths are valid"))
+  }
+}
+
+/// Mostly lowercase ascii, sometimes symbols or any
+/// unicode alphanumeric character.
+fn segment_char(u: &mut Unstructured) -> Result<char> {
+  Ok(match u.int_in_range(0..=9)? {
+    0 => *u.choose(&['.', '-', '_'])?,
+    1 => Some(u.arbitrary::<char>()?)
+      .filter(|c| c.is_alphanumeric())
+      .unwrap_or('x'),
+    _ => char::from(*u.choose(b"abcdefghijklmnopqrstuvwxyz0123456789")?),
+  })
+}
+
+impl<'a> Arbitrary<'a> for Predicate {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    Ok(Predicate {
+      code: u.arbitrary()?,
+      params: u.arbitrary()?,
+    })
+  }
+}
+
+impl<'a> Arbitrary<'a> for Intent {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    let mut intent = Intent::with_calldata(
+      u.arbitrary()?,
+      u.arbitrary()?,
+      u.arbitrary()?,
+      u.arbitrary()?,
+    );
+    intent.terms = u.arbitrary()?;
+    Ok(intent)
+  }
+}
+
+impl<'a> Arbitrary<'a> for Transaction {
+  fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+    let mut transaction = Transaction::new(u.arbitrary()?, u.arbitrary()?);
+    transaction.solver = u.arbitrary()?;
+    Ok(transaction)
+  }
+}
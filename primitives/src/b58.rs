@@ -16,6 +16,12 @@ impl ToBase58String for ed25519_dalek::Signature {
   }
 }
 
+impl ToBase58String for ed25519_dalek::PublicKey {
+  fn to_b58(&self) -> String {
+    bs58::encode(self.as_bytes()).into_string()
+  }
+}
+
 impl ToBase58String for &[u8] {
   fn to_b58(&self) -> String {
     bs58::encode(self).into_string()
@@ -1,56 +1,303 @@
 use {
-  crate::{b58::ToBase58String, Transaction},
+  crate::{
+    b58::ToBase58String,
+    encoding,
+    leaf_hash,
+    Digest,
+    MerkleProof,
+    MerkleTree,
+    Transaction,
+  },
   alloc::{vec, vec::Vec},
-  multihash::{Hasher, Multihash, MultihashDigest, Sha3_256},
+  ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier},
+  multihash::Multihash,
   once_cell::sync::OnceCell,
   serde::{Deserialize, Serialize},
 };
 
+/// Block metadata that commits to the block contents and chain state.
+///
+/// Headers are small and can be relayed and verified without the
+/// transactions they commit to. The hash of a block is the hash of
+/// its header.
 #[derive(Clone, Serialize, Deserialize)]
-pub struct Block {
+pub struct BlockHeader {
   pub height: u64,
+  #[serde(with = "crate::readable::b58")]
   pub parent: Multihash,
-  pub transactions: Vec<Transaction>,
+
+  /// Milliseconds since the unix epoch when the block was produced.
+  pub timestamp: u64,
+
+  /// Merkle root of the hashes of all transactions in the block body.
+  #[serde(with = "crate::readable::b58")]
+  pub transactions_root: Multihash,
+
+  /// Root of the chain state that this block is applied to, that is
+  /// the state after applying all blocks up to and including the parent.
+  #[serde(with = "crate::readable::b58")]
+  pub state_root: Multihash,
+
+  /// Public key of the validator that produced the block.
+  #[serde(with = "crate::readable::b58")]
+  pub proposer: PublicKey,
+
+  /// Public keys of validators authorized to propose blocks on top of
+  /// this block. It is configured in the genesis block and carried over
+  /// unchanged by all following blocks.
+  #[serde(with = "crate::readable::b58_seq")]
+  pub validators: Vec<PublicKey>,
 
   #[serde(skip)]
   hash_cache: OnceCell<Multihash>,
 }
 
+impl BlockHeader {
+  pub fn hash(&self) -> &Multihash {
+    self.hash_cache.get_or_init(|| encoding::hash(self))
+  }
+
+  /// Verifies that a transaction is included in the body of the block
+  /// with this header, without having access to the body.
+  pub fn verify_transaction(
+    &self,
+    transaction: &Transaction,
+    proof: &MerkleProof,
+  ) -> bool {
+    proof.verify(&transaction_leaf(transaction), &self.transactions_root)
+  }
+}
+
+/// All transactions included in a block.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockBody {
+  pub transactions: Vec<Transaction>,
+}
+
+impl BlockBody {
+  /// Merkle root of the hashes of all transactions in the body.
+  pub fn transactions_root(&self) -> Multihash {
+    self.tree().root()
+  }
+
+  /// Produces a proof that the transaction at a given position is
+  /// included in this body, verifiable against a block header.
+  pub fn prove(&self, index: usize) -> Option<MerkleProof> {
+    self.tree().proof(index)
+  }
+
+  fn tree(&self) -> MerkleTree {
+    MerkleTree::new(self.transactions.iter().map(transaction_leaf).collect())
+  }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Block {
+  pub header: BlockHeader,
+  pub body: BlockBody,
+
+  /// Signature of the header hash by the block proposer.
+  /// Only the genesis block is not signed.
+  #[serde(with = "crate::readable::b58_option")]
+  pub signature: Option<Signature>,
+}
+
 impl Block {
-  pub fn new(parent: &Block, transactions: Vec<Transaction>) -> Self {
-    Self {
+  /// Produces a new block on top of a parent block, signed by the proposer.
+  pub fn new(
+    parent: &BlockHeader,
+    timestamp: u64,
+    state_root: Multihash,
+    proposer: &Keypair,
+    transactions: Vec<Transaction>,
+  ) -> Self {
+    let body = BlockBody { transactions };
+    let header = BlockHeader {
       height: parent.height + 1,
       parent: *parent.hash(),
-      transactions,
-      hash_cache: Default::default(),
+      timestamp,
+      transactions_root: body.transactions_root(),
+      state_root,
+      proposer: proposer.public,
+      validators: parent.validators.clone(),
+      hash_cache: OnceCell::new(),
+    };
+    let signature = proposer.sign(&header.hash().to_bytes());
+    Self {
+      header,
+      body,
+      signature: Some(signature),
     }
   }
 
-  pub fn zero() -> Self {
+  /// The genesis block with an empty chain state and a given
+  /// set of validators authorized to propose blocks.
+  pub fn genesis(validators: Vec<PublicKey>) -> Self {
+    let body = BlockBody::default();
     Self {
-      height: 0,
-      parent: Multihash::default(),
-      transactions: vec![],
-      hash_cache: OnceCell::new(),
+      header: BlockHeader {
+        height: 0,
+        parent: Multihash::default(),
+        timestamp: 0,
+        transactions_root: body.transactions_root(),
+        state_root: MerkleTree::new(vec![]).root(),
+        proposer: PublicKey::default(),
+        validators,
+        hash_cache: OnceCell::new(),
+      },
+      body,
+      signature: None,
     }
   }
 
+  /// The genesis block with an empty chain state and no validators.
+  pub fn zero() -> Self {
+    Self::genesis(vec![])
+  }
+
   pub fn hash(&self) -> &Multihash {
-    self.hash_cache.get_or_init(|| {
-      let mut hasher = Sha3_256::default();
-      hasher.update(&rmp_serde::to_vec(self).unwrap());
-      multihash::Code::Sha3_256.wrap(hasher.finalize()).unwrap()
-    })
+    self.header.hash()
+  }
+
+  /// Checks if the block is signed by the proposer named in its header.
+  ///
+  /// This does not check if the proposer is a member of the validator set.
+  pub fn verify_signature(&self) -> bool {
+    match self.signature {
+      Some(ref signature) => self
+        .header
+        .proposer
+        .verify(&self.header.hash().to_bytes(), signature)
+        .is_ok(),
+      None => false,
+    }
+  }
+
+  /// Checks if the body contains exactly the transactions
+  /// committed to by the header.
+  pub fn verify_body(&self) -> bool {
+    self.body.transactions_root() == self.header.transactions_root
   }
 }
 
-impl core::fmt::Debug for Block {
+fn transaction_leaf(transaction: &Transaction) -> Digest {
+  leaf_hash(&transaction.hash().to_bytes())
+}
+
+impl core::fmt::Debug for BlockHeader {
   fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-    f.debug_struct("Block")
+    f.debug_struct("BlockHeader")
       .field("height", &self.height)
       .field("parent", &self.parent.to_b58())
       .field("hash", &self.hash().to_b58())
-      .field("transactions", &self.transactions)
+      .field("timestamp", &self.timestamp)
+      .field("transactions_root", &self.transactions_root.to_b58())
+      .field("state_root", &self.state_root.to_b58())
+      .field("proposer", &self.proposer.as_bytes().to_b58())
+      .field(
+        "validators",
+        &self
+          .validators
+          .iter()
+          .map(|v| v.as_bytes().to_b58())
+          .collect::<Vec<_>>(),
+      )
       .finish()
   }
 }
+
+impl core::fmt::Debug for Block {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("Block")
+      .field("header", &self.header)
+      .field("transactions", &self.body.transactions)
+      .field("signature", &self.signature.as_ref().map(|s| s.to_b58()))
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::Block,
+    crate::{AccountChange, Transaction},
+    alloc::collections::BTreeMap,
+    ed25519_dalek::Keypair,
+    rand::rngs::OsRng,
+  };
+
+  fn transactions(count: usize) -> Vec<Transaction> {
+    (0..count)
+      .map(|ix| {
+        let mut proposals = BTreeMap::new();
+        proposals.insert(
+          format!("/account{ix}").parse().unwrap(),
+          AccountChange::DeleteAccount,
+        );
+        Transaction::new(vec![], proposals)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn header_commits_to_body() {
+    let proposer = Keypair::generate(&mut OsRng);
+    let genesis = Block::genesis(vec![proposer.public]);
+    let block = Block::new(
+      &genesis.header,
+      1000,
+      genesis.header.state_root,
+      &proposer,
+      transactions(5),
+    );
+
+    assert!(block.verify_body());
+    assert_eq!(block.hash(), block.header.hash());
+    assert_eq!(block.header.parent, *genesis.hash());
+    assert_eq!(block.header.height, 1);
+
+    // a header can be verified alone
+    for (ix, tx) in block.body.transactions.iter().enumerate() {
+      let proof = block.body.prove(ix).unwrap();
+      assert!(block.header.verify_transaction(tx, &proof));
+    }
+
+    let foreign = &transactions(6)[5];
+    let proof = block.body.prove(4).unwrap();
+    assert!(!block.header.verify_transaction(foreign, &proof));
+
+    let mut tampered = block.clone();
+    tampered.body.transactions.pop();
+    assert!(!tampered.verify_body());
+  }
+
+  #[test]
+  fn signed_blocks() {
+    let proposer = Keypair::generate(&mut OsRng);
+    let other = Keypair::generate(&mut OsRng);
+    let genesis = Block::genesis(vec![proposer.public]);
+    assert!(!genesis.verify_signature());
+
+    let block = Block::new(
+      &genesis.header,
+      1000,
+      genesis.header.state_root,
+      &proposer,
+      transactions(2),
+    );
+    assert!(block.verify_signature());
+    assert_eq!(block.header.validators, vec![proposer.public]);
+
+    // impersonating another proposer
+    let mut forged = block.clone();
+    forged.header = Block::new(
+      &genesis.header,
+      1000,
+      genesis.header.state_root,
+      &other,
+      transactions(2),
+    )
+    .header;
+    assert!(!forged.verify_signature());
+  }
+}
@@ -0,0 +1,702 @@
+//! Fluent builders for predicate trees, intents and transactions.
+//!
+//! Instead of nesting boxed expression tree nodes by hand, trees can be
+//! composed from small helper functions and the [`all!`] and [`any!`]
+//! macros:
+//!
+//! ```ignore
+//! use anoma_primitives::{all, builder::*};
+//!
+//! let expectations = all![
+//!   stdpred::uint_equal(proposal("/token/usdx"), inline(100u64)),
+//!   stdpred::require_ed25519_signature(&keypair.public),
+//! ];
+//! ```
+//!
+//! [`all!`]: crate::all
+//! [`any!`]: crate::any
+
+use {
+  crate::{
+    Account,
+    AccountChange,
+    Address,
+    Calldata,
+    Code,
+    ExpressionTree,
+    Intent,
+    IntentRef,
+    Param,
+    Predicate,
+    PredicateTree,
+    StatePatch,
+    Tip,
+    Trade,
+    Transaction,
+    ValidityWindow,
+  },
+  alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+  },
+  ed25519_dalek::Keypair,
+  multihash::Multihash,
+  serde::Serialize,
+};
+
+/// Types that can be used as account addresses in builders.
+///
+/// String addresses are parsed and builder functions panic if they
+/// are not valid addresses, so they are meant for address literals.
+pub trait IntoAddress {
+  fn into_address(self) -> Address;
+}
+
+impl IntoAddress for Address {
+  fn into_address(self) -> Address {
+    self
+  }
+}
+
+impl IntoAddress for &Address {
+  fn into_address(self) -> Address {
+    self.clone()
+  }
+}
+
+impl IntoAddress for &str {
+  fn into_address(self) -> Address {
+    self.parse().expect("invalid address literal")
+  }
+}
+
+impl IntoAddress for String {
+  fn into_address(self) -> Address {
+    self.as_str().into_address()
+  }
+}
+
+/// An inline parameter holding the serialized form of a value.
+///
+/// Values are encoded using MessagePack, which is the format that
+/// predicates in the standard library decode their parameters from,
+/// see [`crate::TypedParam`].
+pub fn inline<T: Serialize>(value: T) -> Param {
+  Param::typed(&value)
+}
+
+/// An inline parameter holding raw bytes that are passed
+/// to the predicate without any encoding.
+pub fn inline_bytes(bytes: impl Into<Vec<u8>>) -> Param {
+  Param::Inline(bytes.into())
+}
+
+/// A parameter referencing the current state of an account.
+pub fn account(address: impl IntoAddress) -> Param {
+  Param::AccountRef(address.into_address())
+}
+
+/// A parameter referencing the proposed state of an account.
+pub fn proposal(address: impl IntoAddress) -> Param {
+  Param::ProposalRef(address.into_address())
+}
+
+/// A parameter referencing an intent calldata entry.
+pub fn calldata(key: impl Into<String>) -> Param {
+  Param::CalldataRef(key.into())
+}
+
+/// A parameter referencing a calldata entry of a specific intent.
+pub fn intent_calldata(intent: IntentRef, key: impl Into<String>) -> Param {
+  Param::IntentCalldataRef(intent, key.into())
+}
+
+/// Code of a predicate that is an exported function of a wasm
+/// module stored in an account.
+pub fn code(address: impl IntoAddress, entrypoint: &str) -> Code {
+  Code::AccountRef(address.into_address(), entrypoint.to_string())
+}
+
+/// Code of a predicate that is an exported function of a wasm
+/// module stored in the code registry under its hash.
+pub fn registered_code(hash: Multihash, entrypoint: &str) -> Code {
+  Code::Hash(hash, entrypoint.to_string())
+}
+
+/// A tree consisting of a single predicate.
+pub fn predicate(
+  code: Code,
+  params: impl IntoIterator<Item = Param>,
+) -> PredicateTree {
+  ExpressionTree::Id(Predicate {
+    code,
+    params: params.into_iter().collect(),
+  })
+}
+
+/// Negation of a tree.
+pub fn not<T>(tree: ExpressionTree<T>) -> ExpressionTree<T> {
+  ExpressionTree::Not(Box::new(tree))
+}
+
+/// A tree that is satisfied when all given trees are satisfied.
+pub fn all<T>(
+  trees: impl IntoIterator<Item = ExpressionTree<T>>,
+) -> ExpressionTree<T> {
+  ExpressionTree::All(trees.into_iter().collect())
+}
+
+/// A tree that is satisfied when any of the given trees is satisfied.
+pub fn any<T>(
+  trees: impl IntoIterator<Item = ExpressionTree<T>>,
+) -> ExpressionTree<T> {
+  ExpressionTree::Any(trees.into_iter().collect())
+}
+
+/// A tree that is satisfied when at least `k` of the given
+/// trees are satisfied.
+pub fn threshold<T>(
+  k: usize,
+  trees: impl IntoIterator<Item = ExpressionTree<T>>,
+) -> ExpressionTree<T> {
+  ExpressionTree::Threshold(k, trees.into_iter().collect())
+}
+
+/// Combines trees into a tree that is satisfied when
+/// all of them are satisfied.
+#[macro_export]
+macro_rules! all {
+  ($($tree:expr),* $(,)?) => {
+    $crate::builder::all([$($tree),*])
+  };
+}
+
+/// Combines trees into a tree that is satisfied when
+/// any of them is satisfied.
+#[macro_export]
+macro_rules! any {
+  ($($tree:expr),* $(,)?) => {
+    $crate::builder::any([$($tree),*])
+  };
+}
+
+/// Builders for predicates of the standard predicate library.
+///
+/// Parameter lists follow the documentation of each predicate
+/// in the `stdpred` crate.
+pub mod stdpred {
+  use {
+    super::{code, inline, inline_bytes, predicate, IntoAddress},
+    crate::{Param, PredicateTree, Trade},
+    ed25519_dalek::PublicKey,
+  };
+
+  /// Address under which the standard predicate library is installed.
+  pub const ADDRESS: &str = "/stdpred/v1";
+
+  fn invoke<const N: usize>(
+    entrypoint: &str,
+    params: [Param; N],
+  ) -> PredicateTree {
+    predicate(code(ADDRESS, entrypoint), params)
+  }
+
+  pub fn constant(value: bool) -> PredicateTree {
+    invoke("constant", [inline(value)])
+  }
+
+  pub fn immutable_state(address: impl IntoAddress) -> PredicateTree {
+    invoke("immutable_state", [inline(address.into_address())])
+  }
+
+  pub fn immutable_predicates(address: impl IntoAddress) -> PredicateTree {
+    invoke("immutable_predicates", [inline(address.into_address())])
+  }
+
+  pub fn uint_equal(first: Param, second: Param) -> PredicateTree {
+    invoke("uint_equal", [first, second])
+  }
+
+  pub fn uint_greater_than(first: Param, second: Param) -> PredicateTree {
+    invoke("uint_greater_than", [first, second])
+  }
+
+  pub fn uint_greater_than_equal(first: Param, second: Param) -> PredicateTree {
+    invoke("uint_greater_than_equal", [first, second])
+  }
+
+  pub fn uint_less_than(first: Param, second: Param) -> PredicateTree {
+    invoke("uint_less_than", [first, second])
+  }
+
+  pub fn uint_less_than_equal(first: Param, second: Param) -> PredicateTree {
+    invoke("uint_less_than_equal", [first, second])
+  }
+
+  pub fn uint_greater_than_by(
+    first: Param,
+    second: Param,
+    by: Param,
+  ) -> PredicateTree {
+    invoke("uint_greater_than_by", [first, second, by])
+  }
+
+  pub fn uint_less_than_by(
+    first: Param,
+    second: Param,
+    by: Param,
+  ) -> PredicateTree {
+    invoke("uint_less_than_by", [first, second, by])
+  }
+
+  pub fn bytes_equal(first: Param, second: Param) -> PredicateTree {
+    invoke("bytes_equal", [first, second])
+  }
+
+  pub fn require_ed25519_signature(pubkey: &PublicKey) -> PredicateTree {
+    invoke("require_ed25519_signature", [inline_bytes(
+      pubkey.to_bytes(),
+    )])
+  }
+
+  pub fn tip_paid(token: impl IntoAddress, amount: u64) -> PredicateTree {
+    invoke("tip_paid", [inline(token.into_address()), inline(amount)])
+  }
+
+  pub fn trade_satisfied(trade: &Trade) -> PredicateTree {
+    invoke("trade_satisfied", [inline(trade)])
+  }
+}
+
+/// Builds an intent and signs it by any number of keypairs.
+///
+/// Signatures are stored in calldata under the base58 representation
+/// of the signer public key, which is where `require_ed25519_signature`
+/// from the standard library looks for them.
+pub struct IntentBuilder<'k> {
+  chain_id: String,
+  recent_blockhash: Multihash,
+  expectations: PredicateTree,
+  calldata: Calldata,
+  validity: ValidityWindow,
+  tip: Option<Tip>,
+  trade: Option<Trade>,
+  signers: Vec<&'k Keypair>,
+}
+
+impl<'k> IntentBuilder<'k> {
+  pub fn new(
+    chain_id: impl Into<String>,
+    recent_blockhash: Multihash,
+    expectations: PredicateTree,
+  ) -> Self {
+    Self {
+      chain_id: chain_id.into(),
+      recent_blockhash,
+      expectations,
+      calldata: Calldata::new(),
+      validity: ValidityWindow::default(),
+      tip: None,
+      trade: None,
+      signers: Vec::new(),
+    }
+  }
+
+  /// Adds another expectation that must be satisfied
+  /// along with all previous ones.
+  pub fn expect(mut self, expectation: PredicateTree) -> Self {
+    self.expectations = match self.expectations {
+      ExpressionTree::All(mut trees) => {
+        trees.push(expectation);
+        ExpressionTree::All(trees)
+      }
+      tree => all([tree, expectation]),
+    };
+    self
+  }
+
+  pub fn calldata(mut self, key: impl Into<String>, value: Vec<u8>) -> Self {
+    self.calldata.insert(key.into(), value);
+    self
+  }
+
+  /// The intent can't be executed in blocks before this height.
+  pub fn valid_from(mut self, height: u64) -> Self {
+    self.validity.valid_from = Some(height);
+    self
+  }
+
+  /// The intent can't be executed in blocks after this height.
+  pub fn valid_until(mut self, height: u64) -> Self {
+    self.validity.valid_until = Some(height);
+    self
+  }
+
+  /// Offers a tip to the solver that includes the intent in a
+  /// transaction and expects it to be paid, see [`stdpred::tip_paid`].
+  pub fn tip(mut self, token: impl IntoAddress, amount: u64) -> Self {
+    self.tip = Some(Tip {
+      token: token.into_address(),
+      amount,
+    });
+    self
+  }
+
+  /// Declares the tokens that the intent offers and wants and
+  /// expects the trade to happen, see [`stdpred::trade_satisfied`].
+  pub fn trade(mut self, trade: Trade) -> Self {
+    self.trade = Some(trade);
+    self
+  }
+
+  pub fn signed_by(mut self, keypair: &'k Keypair) -> Self {
+    self.signers.push(keypair);
+    self
+  }
+
+  pub fn build(mut self) -> Intent {
+    if let Some(tip) = &self.tip {
+      let expectation = stdpred::tip_paid(&tip.token, tip.amount);
+      self = self.expect(expectation);
+    }
+    if let Some(trade) = &self.trade {
+      let expectation = stdpred::trade_satisfied(trade);
+      self = self.expect(expectation);
+    }
+
+    let mut intent = Intent::with_calldata(
+      self.chain_id,
+      self.recent_blockhash,
+      self.expectations,
+      self.calldata,
+    );
+    if self.validity != ValidityWindow::default() {
+      intent.terms.validity = Some(self.validity);
+    }
+    intent.terms.tip = self.tip;
+    intent.terms.trade = self.trade;
+    for keypair in self.signers {
+      intent.sign(keypair);
+    }
+    intent
+  }
+}
+
+/// Builds a transaction from intents and proposed account changes.
+#[derive(Default)]
+pub struct TransactionBuilder {
+  intents: Vec<Intent>,
+  proposals: BTreeMap<Address, AccountChange>,
+  solver: Option<Address>,
+}
+
+impl TransactionBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn intent(mut self, intent: Intent) -> Self {
+    self.intents.push(intent);
+    self
+  }
+
+  pub fn create(mut self, address: impl IntoAddress, account: Account) -> Self {
+    self.proposals.insert(
+      address.into_address(),
+      AccountChange::CreateAccount(account),
+    );
+    self
+  }
+
+  pub fn replace_state(
+    mut self,
+    address: impl IntoAddress,
+    state: Vec<u8>,
+  ) -> Self {
+    self
+      .proposals
+      .insert(address.into_address(), AccountChange::ReplaceState(state));
+    self
+  }
+
+  pub fn patch_state(
+    mut self,
+    address: impl IntoAddress,
+    patch: StatePatch,
+  ) -> Self {
+    self
+      .proposals
+      .insert(address.into_address(), AccountChange::PatchState(patch));
+    self
+  }
+
+  pub fn replace_predicates(
+    mut self,
+    address: impl IntoAddress,
+    predicates: PredicateTree,
+  ) -> Self {
+    self.proposals.insert(
+      address.into_address(),
+      AccountChange::ReplacePredicates(predicates),
+    );
+    self
+  }
+
+  pub fn delete(mut self, address: impl IntoAddress) -> Self {
+    self
+      .proposals
+      .insert(address.into_address(), AccountChange::DeleteAccount);
+    self
+  }
+
+  pub fn delete_subtree(mut self, address: impl IntoAddress) -> Self {
+    self
+      .proposals
+      .insert(address.into_address(), AccountChange::DeleteSubtree);
+    self
+  }
+
+  pub fn move_subtree(
+    mut self,
+    from: impl IntoAddress,
+    to: impl IntoAddress,
+  ) -> Self {
+    self.proposals.insert(
+      from.into_address(),
+      AccountChange::MoveSubtree(to.into_address()),
+    );
+    self
+  }
+
+  /// Adds bytecode to the code registry, so predicates can reference
+  /// it by its hash, see [`registered_code`].
+  pub fn register_code(self, bytecode: Vec<u8>) -> Self {
+    let address = Code::registry_address(&Code::hash_bytecode(&bytecode));
+    self.create(address, Account {
+      state: bytecode,
+      predicates: ExpressionTree::Any(Vec::new()),
+    })
+  }
+
+  /// Names the solver that assembled the transaction.
+  pub fn solver(mut self, solver: impl IntoAddress) -> Self {
+    self.solver = Some(solver.into_address());
+    self
+  }
+
+  /// Names the solver and credits its wallets with the tips of all
+  /// intents added so far.
+  ///
+  /// The wallets must exist, `balance` returns their current balances.
+  /// Balances are stored as MessagePack encoded 64bit unsigned integers,
+  /// which is how the `tip_paid` predicate reads them.
+  ///
+  /// Only the solver side is credited. Intents don't name the wallet
+  /// that a tip is paid from, so the caller must still debit the tips
+  /// from the wallets of the tippers, usually in the same
+  /// `replace_state` that settles the rest of the intent.
+  ///
+  /// Fails if the credited balance of a wallet doesn't fit into 64 bits.
+  pub fn claim_tips(
+    mut self,
+    solver: impl IntoAddress,
+    balance: impl Fn(&Address) -> u64,
+  ) -> Result<Self, TipOverflow> {
+    let solver = solver.into_address();
+    let mut owed = BTreeMap::new();
+    for tip in self.intents.iter().filter_map(|i| i.terms.tip.as_ref()) {
+      let wallet = tip.payee(&solver);
+      let total = owed.entry(wallet.clone()).or_insert(0u64);
+      *total = total.checked_add(tip.amount).ok_or(TipOverflow(wallet))?;
+    }
+    for (wallet, amount) in owed {
+      let credited = balance(&wallet)
+        .checked_add(amount)
+        .ok_or_else(|| TipOverflow(wallet.clone()))?;
+      let state =
+        rmp_serde::to_vec(&credited).expect("integers are serializable");
+      self = self.replace_state(wallet, state);
+    }
+    Ok(self.solver(solver))
+  }
+
+  pub fn build(self) -> Transaction {
+    let mut transaction = Transaction::new(self.intents, self.proposals);
+    transaction.solver = self.solver;
+    transaction
+  }
+}
+
+/// Error produced when claiming tips would overflow the balance
+/// of a solver wallet, see [`TransactionBuilder::claim_tips`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TipOverflow(pub Address);
+
+impl core::fmt::Display for TipOverflow {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "Claimed tips overflow the balance of {}", self.0)
+  }
+}
+
+// https://github.com/rust-lang/rust/issues/103765
+#[cfg(not(target_family = "wasm"))]
+impl std::error::Error for TipOverflow {}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::Resource,
+    alloc::vec,
+    ed25519_dalek::Keypair,
+    rand::rngs::OsRng,
+  };
+
+  #[test]
+  fn builds_same_trees_as_by_hand() {
+    let built = all![
+      stdpred::uint_equal(proposal("/token/usdx"), inline(5u64)),
+      any![
+        stdpred::constant(false),
+        not(stdpred::bytes_equal(account("/a"), calldata("b"))),
+      ],
+    ];
+
+    let stdaddr: Address = "/stdpred/v1".parse().unwrap();
+    let expected = PredicateTree::All(vec![
+      PredicateTree::Id(Predicate {
+        code: Code::AccountRef(stdaddr.clone(), "uint_equal".into()),
+        params: vec![
+          Param::ProposalRef("/token/usdx".parse().unwrap()),
+          Param::Inline(rmp_serde::to_vec(&5u64).unwrap()),
+        ],
+      }),
+      PredicateTree::Any(vec![
+        PredicateTree::Id(Predicate {
+          code: Code::AccountRef(stdaddr.clone(), "constant".into()),
+          params: vec![Param::Inline(rmp_serde::to_vec(&false).unwrap())],
+        }),
+        PredicateTree::Not(Box::new(PredicateTree::Id(Predicate {
+          code: Code::AccountRef(stdaddr, "bytes_equal".into()),
+          params: vec![
+            Param::AccountRef("/a".parse().unwrap()),
+            Param::CalldataRef("b".into()),
+          ],
+        }))),
+      ]),
+    ]);
+
+    assert_eq!(built, expected);
+    assert_eq!(
+      threshold(2, [stdpred::constant(true), stdpred::constant(false)]),
+      PredicateTree::Threshold(2, vec![
+        stdpred::constant(true),
+        stdpred::constant(false)
+      ])
+    );
+  }
+
+  #[test]
+  fn signed_intents() {
+    let alice = Keypair::generate(&mut OsRng);
+    let bob = Keypair::generate(&mut OsRng);
+
+    let intent = IntentBuilder::new(
+      "testnet",
+      Multihash::default(),
+      stdpred::require_ed25519_signature(&alice.public),
+    )
+    .expect(stdpred::require_ed25519_signature(&bob.public))
+    .calldata("memo", b"hello".to_vec())
+    .signed_by(&alice)
+    .signed_by(&bob)
+    .build();
+
+    assert!(intent.verify_signature(&alice.public));
+    assert!(intent.verify_signature(&bob.public));
+    assert_eq!(intent.calldata.len(), 3);
+
+    let tx = TransactionBuilder::new()
+      .intent(intent.clone())
+      .replace_state("/token/usdx", vec![1])
+      .delete("/token/usdx/old")
+      .build();
+    assert_eq!(tx.intents.len(), 1);
+    assert_eq!(tx.intents[0].hash(), intent.hash());
+    assert_eq!(tx.proposals.len(), 2);
+  }
+
+  #[test]
+  fn tipped_intents() {
+    let alice = Keypair::generate(&mut OsRng);
+    let tipping = |amount| {
+      IntentBuilder::new(
+        "testnet",
+        Multihash::default(),
+        stdpred::constant(true),
+      )
+      .tip("/token/usdx", amount)
+      .signed_by(&alice)
+      .build()
+    };
+
+    let intent = tipping(5);
+    assert_eq!(intent.terms.tip.as_ref().unwrap().amount, 5);
+    assert_eq!(intent.expectations, all![
+      stdpred::constant(true),
+      stdpred::tip_paid("/token/usdx", 5)
+    ]);
+    assert!(intent.verify_signature(&alice.public));
+
+    let solver: Address = "/solver.eth".parse().unwrap();
+    let tx = TransactionBuilder::new()
+      .intent(intent)
+      .intent(tipping(7))
+      .claim_tips(&solver, |_| 100)
+      .unwrap()
+      .build();
+
+    assert_eq!(tx.solver, Some(solver.clone()));
+    assert_eq!(
+      tx.proposals.get(&"/token/usdx/solver.eth".parse().unwrap()),
+      Some(&AccountChange::ReplaceState(
+        rmp_serde::to_vec(&112u64).unwrap()
+      ))
+    );
+
+    let wallet: Address = "/token/usdx/solver.eth".parse().unwrap();
+    let overflowing = TransactionBuilder::new()
+      .intent(tipping(u64::MAX))
+      .intent(tipping(1))
+      .claim_tips(&solver, |_| 0);
+    assert_eq!(overflowing.err(), Some(TipOverflow(wallet.clone())));
+
+    let overflowing = TransactionBuilder::new()
+      .intent(tipping(1))
+      .claim_tips(&solver, |_| u64::MAX);
+    assert_eq!(overflowing.err(), Some(TipOverflow(wallet)));
+  }
+
+  #[test]
+  fn trading_intents() {
+    let trade = Trade::new("/alice.eth".parse().unwrap())
+      .offer(Resource::new("/token/usdx".parse().unwrap(), 100))
+      .want(Resource::new("/token/eurx".parse().unwrap(), 90));
+
+    let intent = IntentBuilder::new(
+      "testnet",
+      Multihash::default(),
+      stdpred::constant(true),
+    )
+    .trade(trade.clone())
+    .build();
+
+    assert_eq!(intent.terms.trade, Some(trade.clone()));
+    assert_eq!(intent.expectations, all![
+      stdpred::constant(true),
+      stdpred::trade_satisfied(&trade)
+    ]);
+  }
+}
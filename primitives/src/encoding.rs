@@ -0,0 +1,261 @@
+//! Canonical binary encoding of primitives.
+//!
+//! This is the encoding used whenever primitives are hashed or signed and
+//! when they are exchanged between nodes. Every value has exactly one valid
+//! encoding, so hashes of equal values are always equal.
+//!
+//! Layout:
+//!
+//! ```text
+//! version: u8
+//! body: MessagePack
+//! ```
+//!
+//! The body is MessagePack with the following rules:
+//!   - integers use the shortest representation that fits their value,
+//!   - structs are arrays of their fields in declaration order,
+//!   - enum variants are single-entry maps from the variant name to the variant
+//!     data, unit variants are just the variant name,
+//!   - maps are written with keys in ascending order,
+//!   - byte vectors are arrays of integers, keys and signatures are binary
+//!     strings,
+//!   - multihashes are arrays of their code, digest size and a 64 byte
+//!     zero-padded digest.
+//!
+//! Any change to primitives that changes their encoding must bump
+//! [`VERSION`]. The golden vectors in tests of this module guard
+//! against accidental changes.
+//!
+//! Human readable serde formats, such as JSON, are not canonical and
+//! render hashes, keys, signatures and bytes as base58 strings and
+//! inline params as typed values instead. They decode back to values
+//! with the same canonical encoding.
+
+use {
+  alloc::vec::Vec,
+  core::fmt::Display,
+  multihash::{Hasher, Multihash, MultihashDigest, Sha3_256},
+  serde::{de::DeserializeOwned, Serialize},
+};
+
+/// Version of the canonical encoding produced by this module.
+pub const VERSION: u8 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+  /// Input has no version byte.
+  Empty,
+
+  /// Input is encoded using an unknown version.
+  UnsupportedVersion(u8),
+
+  /// Input body is not a valid encoding of the expected type.
+  Malformed(rmp_serde::decode::Error),
+
+  /// Input decodes to a valid value, but is not the canonical
+  /// encoding of that value.
+  NonCanonical,
+}
+
+impl Display for Error {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      Error::Empty => write!(f, "Empty input"),
+      Error::UnsupportedVersion(v) => {
+        write!(f, "Unsupported encoding version {v}")
+      }
+      Error::Malformed(e) => write!(f, "Malformed input: {e}"),
+      Error::NonCanonical => write!(f, "Input is not canonically encoded"),
+    }
+  }
+}
+
+/// Produces the canonical encoding of a value.
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+  let mut output = alloc::vec![VERSION];
+  rmp_serde::encode::write(&mut output, value)
+    .expect("primitives are always serializable");
+  output
+}
+
+/// Decodes a value from its canonical encoding.
+///
+/// Rejects inputs that decode to a valid value but differ from
+/// the canonical encoding of that value.
+pub fn decode<T: Serialize + DeserializeOwned>(
+  bytes: &[u8],
+) -> Result<T, Error> {
+  let (version, body) = bytes.split_first().ok_or(Error::Empty)?;
+  if *version != VERSION {
+    return Err(Error::UnsupportedVersion(*version));
+  }
+
+  let value: T = rmp_serde::from_slice(body).map_err(Error::Malformed)?;
+  if encode(&value) != bytes {
+    return Err(Error::NonCanonical);
+  }
+
+  Ok(value)
+}
+
+/// Sha3-256 hash of the canonical encoding of a value.
+pub fn hash<T: Serialize + ?Sized>(value: &T) -> Multihash {
+  let mut hasher = Sha3_256::default();
+  hasher.update(&encode(value));
+  multihash::Code::Sha3_256
+    .wrap(hasher.finalize())
+    .expect("hash length matches hashcode")
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{decode, encode, hash, Error, VERSION},
+    crate::{
+      b58::ToBase58String,
+      Account,
+      AccountChange,
+      Block,
+      Code,
+      Intent,
+      Param,
+      Predicate,
+      PredicateTree,
+      Transaction,
+    },
+    alloc::{collections::BTreeMap, string::String},
+    multihash::Multihash,
+  };
+
+  fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| alloc::format!("{b:02x}")).collect()
+  }
+
+  fn account() -> Account {
+    Account {
+      state: vec![1, 2, 3],
+      predicates: PredicateTree::Id(Predicate {
+        code: Code::AccountRef(
+          "/stdpred/v1".parse().unwrap(),
+          "constant".into(),
+        ),
+        params: vec![Param::Inline(vec![1])],
+      }),
+    }
+  }
+
+  fn intent() -> Intent {
+    Intent::new(
+      "testnet".into(),
+      Multihash::default(),
+      PredicateTree::Id(Predicate {
+        code: Code::Inline(vec![0, 97, 115, 109]),
+        params: vec![Param::ProposalRef("/token".parse().unwrap())],
+      }),
+    )
+  }
+
+  fn transaction() -> Transaction {
+    let mut proposals = BTreeMap::new();
+    proposals.insert(
+      "/token".parse().unwrap(),
+      AccountChange::CreateAccount(account()),
+    );
+    proposals.insert("/old".parse().unwrap(), AccountChange::DeleteAccount);
+    Transaction::new(vec![intent()], proposals)
+  }
+
+  #[test]
+  fn golden_vectors() {
+    assert_eq!(
+      to_hex(&encode(&account())),
+      "01929301020381a249649281aa4163636f756e7452656692ab2f737464707265642f76\
+       31a8636f6e7374616e749181a6496e6c696e659101"
+    );
+    assert_eq!(
+      to_hex(&encode(&intent())),
+      "0194a7746573746e6574930000dc0040000000000000000000000000000000000000\
+       00000000000000000000000000000000000000000000000000000000000000000000\
+       00000000000000000000000081a249649281a6496e6c696e65940061736d9181ab50\
+       726f706f73616c526566a62f746f6b656e80"
+    );
+
+    assert_eq!(
+      hash(&account()).to_b58(),
+      "W1eYVZxoiTtTbVwP1Sonv17fxDbRJr49Dgmn6dZMbscJ2a"
+    );
+    assert_eq!(
+      intent().hash().to_b58(),
+      "W1n72NV3WzV4PnvDLRYMSMH55CUfMd28bkDr9QdHvF1nAm"
+    );
+    assert_eq!(
+      transaction().hash().to_b58(),
+      "W1fkXJkEeST6huhbgcFowC3wszRa25pWQZauAvkcHh6GCw"
+    );
+    assert_eq!(
+      Block::zero().hash().to_b58(),
+      "W1oFr76vbRBj7U4VvJ1nM7R5Uk86ATmiymiLNcanVfSNFS"
+    );
+  }
+
+  #[test]
+  fn roundtrip() {
+    let tx = transaction();
+    let decoded: Transaction = decode(&encode(&tx)).unwrap();
+    assert_eq!(decoded, tx);
+    assert_eq!(decoded.hash(), tx.hash());
+
+    let decoded: Account = decode(&encode(&account())).unwrap();
+    assert_eq!(decoded, account());
+  }
+
+  #[test]
+  fn rejects_non_canonical() {
+    // 5u64 is canonically a positive fixint
+    assert_eq!(decode::<u64>(&[VERSION, 0x05]).unwrap(), 5);
+    assert!(matches!(
+      decode::<u64>(&[VERSION, 0xcf, 0, 0, 0, 0, 0, 0, 0, 5]),
+      Err(Error::NonCanonical)
+    ));
+
+    // trailing bytes
+    let mut bytes = encode(&account());
+    bytes.push(0);
+    assert!(matches!(
+      decode::<Account>(&bytes),
+      Err(Error::NonCanonical)
+    ));
+
+    // unknown version and missing version
+    let mut bytes = encode(&account());
+    bytes[0] = VERSION + 1;
+    assert!(matches!(
+      decode::<Account>(&bytes),
+      Err(Error::UnsupportedVersion(v)) if v == VERSION + 1
+    ));
+    assert!(matches!(decode::<Account>(&[]), Err(Error::Empty)));
+    assert!(matches!(
+      decode::<Account>(&[VERSION, 0xc1]),
+      Err(Error::Malformed(_))
+    ));
+  }
+
+  proptest::proptest! {
+    #[test]
+    fn arbitrary_roundtrip(
+      bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..4096)
+    ) {
+      use {arbitrary::Arbitrary, crate::Limits};
+
+      let mut u = arbitrary::Unstructured::new(&bytes);
+      if let Ok(tx) = Transaction::arbitrary(&mut u) {
+        // only valid transactions are guaranteed to be within
+        // the nesting limits of the decoder.
+        if tx.validate(&Limits::default()).is_ok() {
+          let decoded: Transaction = decode(&encode(&tx)).unwrap();
+          proptest::prop_assert_eq!(decoded.hash(), tx.hash());
+        }
+      }
+    }
+  }
+}
@@ -1,7 +1,16 @@
 use {
-  crate::{b58::ToBase58String, Exact, PredicateTree, Repr},
+  crate::{
+    b58::ToBase58String,
+    encoding,
+    Address,
+    Exact,
+    PredicateTree,
+    Repr,
+    Trade,
+  },
   alloc::{collections::BTreeMap, string::String, vec::Vec},
   core::fmt::Debug,
+  ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier},
   multihash::{Hasher, Multihash, MultihashDigest, Sha3_256},
   once_cell::sync::OnceCell,
   serde::{Deserialize, Serialize},
@@ -12,48 +21,171 @@ use {
 /// or any other arbitrary input parameters to intents, etc.
 pub type Calldata = BTreeMap<String, Vec<u8>>;
 
+/// Domain tag that prefixes every intent signing payload, so intent
+/// signatures can't be confused with signatures over other data.
+pub const INTENT_SIGNING_DOMAIN: &str = "anoma/intent";
+
+/// Version of the intent signing payload layout.
+pub const INTENT_SIGNING_VERSION: u8 = 1;
+
+/// Range of block heights in which an intent can be included in a block.
+///
+/// Both bounds are inclusive and optional, a window with no bounds
+/// is valid at every height.
+#[derive(
+  Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash,
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct ValidityWindow {
+  /// First height at which the intent is valid.
+  pub valid_from: Option<u64>,
+
+  /// Last height at which the intent is valid.
+  pub valid_until: Option<u64>,
+}
+
+impl ValidityWindow {
+  /// Checks if a block at the given height may include the intent.
+  pub fn contains(&self, height: u64) -> bool {
+    self.valid_from.map_or(true, |from| from <= height)
+      && self.valid_until.map_or(true, |until| height <= until)
+  }
+
+  /// Checks if the intent can't be included in a block at the
+  /// given height or any later height.
+  pub fn expired_at(&self, height: u64) -> bool {
+    self.valid_until.map_or(false, |until| until < height)
+  }
+}
+
+/// A payment in tokens that an intent offers to the solver
+/// that includes it in a transaction.
+///
+/// Tips are paid by a transfer to the solver's wallet of the token,
+/// see [`Tip::payee`]. The solver is named in the transaction that
+/// includes the intent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Tip {
+  /// Address of the token, wallets of the token are its children.
+  pub token: Address,
+
+  /// Amount of tokens that the solver receives.
+  pub amount: u64,
+}
+
+impl Tip {
+  /// The wallet of a solver that receives tips in this token.
+  ///
+  /// It is the solver address nested under the token address,
+  /// e.g. `/token/usdx/solver.eth` for solver `/solver.eth`.
+  pub fn payee(&self, solver: &Address) -> Address {
+    Address::new(alloc::format!("{}{}", self.token, solver))
+      .expect("concatenated addresses are valid")
+  }
+}
+
+/// Optional terms under which an intent can be included in a transaction.
+///
+/// Terms are signed along with the intent expectations.
+#[derive(
+  Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash,
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct IntentTerms {
+  /// Block heights in which the intent can be executed.
+  /// Intents without a window are valid at any height.
+  #[serde(default)]
+  pub validity: Option<ValidityWindow>,
+
+  /// Tip paid to the solver that includes the intent in a transaction.
+  ///
+  /// The tip is a declaration that solvers can inspect, the payment
+  /// itself is enforced by the `tip_paid` predicate from the standard
+  /// library in the intent expectations.
+  #[serde(default)]
+  pub tip: Option<Tip>,
+
+  /// Tokens offered and wanted by the intent, so solvers can index
+  /// and match intents without simulating them.
+  ///
+  /// Like the tip, it is enforced by a predicate in the intent
+  /// expectations, `trade_satisfied` from the standard library.
+  #[serde(default)]
+  pub trade: Option<Trade>,
+}
+
+impl IntentTerms {
+  pub fn is_empty(&self) -> bool {
+    self == &Self::default()
+  }
+}
+
 /// Intents are partial transactions created by users describing what state
 /// transition they want to achieve.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Intent<R: Repr = Exact> {
+  /// Identifies the network that this intent is meant for.
+  /// Intents for other networks are rejected.
+  pub chain_id: String,
+
   /// Hash of a block within the last 2 epochs.
   /// Intents that have this value pointing to a
   /// block that is older then 2 epochs are expired
   /// and rejected by the chain.
+  #[serde(with = "crate::readable::b58")]
   pub recent_blockhash: Multihash,
   pub expectations: PredicateTree<R>,
 
   /// If any of the calldata entries is a signature,
-  /// it should sign the recent_blockhash value.
+  /// it should sign the intent signing hash.
+  #[serde(with = "crate::readable::calldata")]
   pub calldata: Calldata,
 
+  /// Optional terms of the intent, such as its validity window.
+  ///
+  /// Terms are omitted from the encoding when none are set, so they
+  /// don't change the hashes of intents that don't use them. All of
+  /// them are kept in one field, because intents are encoded as arrays
+  /// and only trailing fields can be omitted.
+  #[serde(default, skip_serializing_if = "IntentTerms::is_empty")]
+  pub terms: IntentTerms,
+
   #[serde(skip)]
   hash_cache: OnceCell<Multihash>,
+
+  #[serde(skip)]
+  signing_hash_cache: OnceCell<Multihash>,
 }
 
 impl<R: Repr> Intent<R> {
   pub fn new(
+    chain_id: String,
     recent_blockhash: Multihash,
     expectations: PredicateTree<R>,
   ) -> Self {
-    Self {
+    Self::with_calldata(
+      chain_id,
       recent_blockhash,
       expectations,
-      calldata: Calldata::new(),
-      hash_cache: OnceCell::new(),
-    }
+      Calldata::new(),
+    )
   }
 
   pub fn with_calldata(
+    chain_id: String,
     recent_blockhash: Multihash,
     expectations: PredicateTree<R>,
     calldata: Calldata,
   ) -> Self {
     Self {
+      chain_id,
       recent_blockhash,
       expectations,
       calldata,
+      terms: IntentTerms::default(),
       hash_cache: OnceCell::new(),
+      signing_hash_cache: OnceCell::new(),
     }
   }
 }
@@ -61,8 +193,10 @@ impl<R: Repr> Intent<R> {
 impl<R: Repr> Debug for Intent<R> {
   fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
     f.debug_struct("Intent")
+      .field("chain_id", &self.chain_id)
       .field("expectations", &self.expectations)
       .field("calldata", &self.calldata)
+      .field("terms", &self.terms)
       .field("hash", &self.hash().to_b58())
       .finish()
   }
@@ -71,23 +205,238 @@ impl<R: Repr> Debug for Intent<R> {
 impl<R: Repr> Intent<R> {
   /// Hash of the intent that uniquely identitifies it.
   pub fn hash(&self) -> &Multihash {
-    self.hash_cache.get_or_init(|| {
-      let mut hasher = Sha3_256::default();
-      hasher.update(&rmp_serde::to_vec(self).unwrap());
-      multihash::Code::Sha3_256.wrap(hasher.finalize()).unwrap()
-    })
+    self.hash_cache.get_or_init(|| encoding::hash(self))
   }
 
-  /// Hash of the contents of the intent without calldata.
+  /// The message that is signed by signatures attached to the intent.
+  ///
+  /// Covers everything except calldata and is prefixed with a domain
+  /// tag and a version, so a signature is bound to intents on one chain
+  /// and can't be reused for other purposes. The terms are appended only
+  /// when set, so payloads of intents without them are unchanged.
+  pub fn signing_payload(&self) -> Vec<u8> {
+    let payload = (
+      INTENT_SIGNING_DOMAIN,
+      INTENT_SIGNING_VERSION,
+      &self.chain_id,
+      &self.recent_blockhash,
+      &self.expectations,
+    );
+    match self.terms.is_empty() {
+      true => encoding::encode(&payload),
+      false => encoding::encode(&(
+        payload.0,
+        payload.1,
+        payload.2,
+        payload.3,
+        payload.4,
+        &self.terms,
+      )),
+    }
+  }
+
+  /// Checks if a block at the given height may include this intent.
+  pub fn is_valid_at(&self, height: u64) -> bool {
+    self
+      .terms
+      .validity
+      .map_or(true, |window| window.contains(height))
+  }
+
+  /// Checks if this intent can't be included in a block at the
+  /// given height or any later height.
+  pub fn is_expired_at(&self, height: u64) -> bool {
+    self
+      .terms
+      .validity
+      .map_or(false, |window| window.expired_at(height))
+  }
+
+  /// Hash of the signing payload of the intent.
   ///
   /// This hash is used as the message when signatures need
   /// to be attached to intents.
   pub fn signing_hash(&self) -> &Multihash {
-    self.hash_cache.get_or_init(|| {
+    self.signing_hash_cache.get_or_init(|| {
       let mut hasher = Sha3_256::default();
-      hasher.update(&rmp_serde::to_vec(&self.recent_blockhash).unwrap());
-      hasher.update(&rmp_serde::to_vec(&self.expectations).unwrap());
+      hasher.update(&self.signing_payload());
       multihash::Code::Sha3_256.wrap(hasher.finalize()).unwrap()
     })
   }
+
+  /// Signs the intent and stores the signature in calldata under
+  /// the base58 representation of the signing public key.
+  pub fn sign(&mut self, keypair: &Keypair) {
+    let signature = keypair.sign(&self.signing_hash().to_bytes());
+    self.calldata.insert(
+      signature_calldata_key(&keypair.public),
+      signature.to_bytes().to_vec(),
+    );
+    self.hash_cache = OnceCell::new();
+  }
+
+  /// Checks if the intent calldata carries a valid signature by
+  /// the given public key.
+  pub fn verify_signature(&self, pubkey: &PublicKey) -> bool {
+    verify_calldata_signature(self.signing_hash(), &self.calldata, pubkey)
+  }
+}
+
+/// The calldata key under which signatures by a given public key are stored.
+pub fn signature_calldata_key(pubkey: &PublicKey) -> String {
+  pubkey.as_bytes().to_b58()
+}
+
+/// Checks if calldata of an intent with a given signing hash carries
+/// a valid signature by the given public key.
+pub fn verify_calldata_signature(
+  signing_hash: &Multihash,
+  calldata: &Calldata,
+  pubkey: &PublicKey,
+) -> bool {
+  calldata
+    .get(&signature_calldata_key(pubkey))
+    .and_then(|signature| Signature::from_bytes(signature).ok())
+    .map(|signature| {
+      pubkey.verify(&signing_hash.to_bytes(), &signature).is_ok()
+    })
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{Intent, Tip, ValidityWindow},
+    crate::{encoding, Code, Predicate, PredicateTree},
+    ed25519_dalek::Keypair,
+    multihash::Multihash,
+    rand::rngs::OsRng,
+  };
+
+  fn intent(chain_id: &str) -> Intent {
+    Intent::new(
+      chain_id.into(),
+      Multihash::default(),
+      PredicateTree::Id(Predicate {
+        code: Code::Inline(b"some-code".to_vec()),
+        params: vec![],
+      }),
+    )
+  }
+
+  #[test]
+  fn signing_hash_is_not_the_intent_hash() {
+    let keypair = Keypair::generate(&mut OsRng);
+
+    let mut signed = intent("localnet");
+    let signing_hash = *signed.signing_hash();
+    signed.sign(&keypair);
+
+    assert_ne!(signed.hash(), signed.signing_hash());
+    assert_eq!(signed.signing_hash(), &signing_hash);
+    assert!(signed.verify_signature(&keypair.public));
+
+    // computing the intent hash first does not affect the signing hash
+    let unsigned = intent("localnet");
+    unsigned.hash();
+    assert_eq!(unsigned.signing_hash(), &signing_hash);
+  }
+
+  #[test]
+  fn signatures_are_bound_to_chain() {
+    let keypair = Keypair::generate(&mut OsRng);
+    let other = Keypair::generate(&mut OsRng);
+
+    let mut signed = intent("localnet");
+    signed.sign(&keypair);
+    assert!(!signed.verify_signature(&other.public));
+
+    let mut replayed = intent("mainnet");
+    replayed.calldata = signed.calldata.clone();
+    assert_ne!(replayed.signing_hash(), signed.signing_hash());
+    assert!(!replayed.verify_signature(&keypair.public));
+  }
+
+  #[test]
+  fn validity_windows() {
+    let window = |from, until| ValidityWindow {
+      valid_from: from,
+      valid_until: until,
+    };
+
+    assert!(window(None, None).contains(0));
+    assert!(!window(Some(100), None).contains(99));
+    assert!(window(Some(100), None).contains(100));
+    assert!(window(Some(100), Some(120)).contains(120));
+    assert!(!window(Some(100), Some(120)).contains(121));
+    assert!(!window(Some(100), Some(120)).expired_at(120));
+    assert!(window(Some(100), Some(120)).expired_at(121));
+    assert!(!window(Some(100), None).expired_at(u64::MAX));
+
+    let keypair = Keypair::generate(&mut OsRng);
+    let unbounded = intent("localnet");
+    let mut bounded = intent("localnet");
+    bounded.terms.validity = Some(window(Some(100), Some(120)));
+    assert!(unbounded.is_valid_at(0));
+    assert!(!bounded.is_valid_at(99));
+    assert!(bounded.is_expired_at(121));
+
+    // the window is bound by signatures
+    assert_ne!(bounded.signing_hash(), unbounded.signing_hash());
+    bounded.sign(&keypair);
+    let mut extended = intent("localnet");
+    extended.terms.validity = Some(window(Some(100), None));
+    extended.calldata = bounded.calldata.clone();
+    assert!(!extended.verify_signature(&keypair.public));
+
+    let decoded: Intent =
+      encoding::decode(&encoding::encode(&bounded)).unwrap();
+    assert_eq!(decoded.terms.validity, bounded.terms.validity);
+    assert_eq!(decoded.hash(), bounded.hash());
+    let decoded: Intent =
+      encoding::decode(&encoding::encode(&unbounded)).unwrap();
+    assert_eq!(decoded.terms.validity, None);
+  }
+
+  #[test]
+  fn tips() {
+    let tip = Tip {
+      token: "/token/usdx".parse().unwrap(),
+      amount: 10,
+    };
+    assert_eq!(
+      tip.payee(&"/solver.eth".parse().unwrap()),
+      "/token/usdx/solver.eth".parse().unwrap()
+    );
+
+    let keypair = Keypair::generate(&mut OsRng);
+    let plain = intent("localnet");
+    let mut tipping = intent("localnet");
+    tipping.terms.tip = Some(tip.clone());
+    assert_ne!(tipping.signing_hash(), plain.signing_hash());
+
+    // the same window with and without a tip
+    let mut bounded = intent("localnet");
+    bounded.terms.validity = Some(ValidityWindow {
+      valid_from: None,
+      valid_until: Some(5),
+    });
+    let mut both = bounded.clone();
+    both.terms.tip = Some(tip.clone());
+    assert_ne!(both.signing_hash(), bounded.signing_hash());
+    assert_ne!(both.signing_hash(), tipping.signing_hash());
+
+    // the amount is bound by signatures
+    tipping.sign(&keypair);
+    let mut cheaper = intent("localnet");
+    cheaper.terms.tip = Some(Tip { amount: 1, ..tip });
+    cheaper.calldata = tipping.calldata.clone();
+    assert!(!cheaper.verify_signature(&keypair.public));
+
+    let decoded: Intent =
+      encoding::decode(&encoding::encode(&tipping)).unwrap();
+    assert_eq!(decoded.terms.tip, tipping.terms.tip);
+    assert_eq!(decoded.hash(), tipping.hash());
+    assert!(decoded.verify_signature(&keypair.public));
+  }
 }
@@ -4,11 +4,24 @@ extern crate alloc;
 
 mod account;
 mod address;
+#[cfg(feature = "arbitrary")]
+mod arb;
 mod b58;
 mod block;
 mod intent;
+mod merkle;
+mod normalize;
+mod patch;
 mod predicate;
+mod readable;
+mod syntax;
+mod trade;
 mod transaction;
+mod typed;
+mod validate;
+
+pub mod builder;
+pub mod encoding;
 
 use {
   core::fmt::Debug,
@@ -59,6 +72,12 @@ pub use {
   address::*,
   block::*,
   intent::*,
+  merkle::*,
+  patch::*,
   predicate::*,
+  syntax::*,
+  trade::*,
   transaction::*,
+  typed::*,
+  validate::*,
 };
@@ -0,0 +1,247 @@
+use {
+  alloc::{vec, vec::Vec},
+  multihash::{Hasher, Multihash, MultihashDigest, Sha3_256},
+  serde::{Deserialize, Serialize},
+};
+
+/// Raw Sha3-256 digest of a leaf or an inner node of a merkle tree.
+pub type Digest = [u8; 32];
+
+// Leaves and inner nodes are hashed with different prefixes,
+// otherwise an inner node could be presented as a leaf in a
+// proof (second preimage attack).
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Hashes arbitrary data into a merkle tree leaf.
+pub fn leaf_hash(data: &[u8]) -> Digest {
+  digest(&[&[LEAF_PREFIX], data])
+}
+
+fn node_hash(left: &Digest, right: &Digest) -> Digest {
+  digest(&[&[NODE_PREFIX], left, right])
+}
+
+fn digest(parts: &[&[u8]]) -> Digest {
+  let mut hasher = Sha3_256::default();
+  for part in parts {
+    hasher.update(part);
+  }
+  let mut output = Digest::default();
+  output.copy_from_slice(hasher.finalize());
+  output
+}
+
+fn wrap(digest: &Digest) -> Multihash {
+  multihash::Code::Sha3_256
+    .wrap(digest)
+    .expect("hash length matches hashcode")
+}
+
+/// A binary merkle tree over an ordered list of leaves.
+///
+/// If a level has an odd number of nodes, then the last node is promoted
+/// to the next level unchanged. The root of an empty tree is the hash of
+/// an empty input.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+  levels: Vec<Vec<Digest>>,
+}
+
+impl MerkleTree {
+  /// Builds a tree from already hashed leaves, see [`leaf_hash`].
+  pub fn new(leaves: Vec<Digest>) -> Self {
+    let mut levels = vec![leaves];
+    while levels.last().expect("at least one level").len() > 1 {
+      let next = levels
+        .last()
+        .expect("at least one level")
+        .chunks(2)
+        .map(|pair| match pair {
+          [left, right] => node_hash(left, right),
+          [single] => *single,
+          _ => unreachable!("chunks of two"),
+        })
+        .collect();
+      levels.push(next);
+    }
+    Self { levels }
+  }
+
+  /// Number of leaves in the tree.
+  pub fn len(&self) -> usize {
+    self.levels[0].len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// The root hash of the tree.
+  pub fn root(&self) -> Multihash {
+    match self.levels.last().and_then(|level| level.first()) {
+      Some(root) => wrap(root),
+      None => wrap(&digest(&[])),
+    }
+  }
+
+  /// Replaces the leaf at a given index and rehashes only the nodes
+  /// on its path to the root.
+  ///
+  /// Returns `false` and leaves the tree unchanged if the index is out
+  /// of bounds.
+  pub fn update(&mut self, index: usize, leaf: Digest) -> bool {
+    if index >= self.len() {
+      return false;
+    }
+
+    self.levels[0][index] = leaf;
+    let mut position = index;
+    for depth in 1..self.levels.len() {
+      let below = &self.levels[depth - 1];
+      let first = position & !1;
+      let hash = match below.get(first + 1) {
+        Some(right) => node_hash(&below[first], right),
+        None => below[first],
+      };
+      position /= 2;
+      self.levels[depth][position] = hash;
+    }
+    true
+  }
+
+  /// Produces a proof that the leaf at a given index is part of the tree.
+  pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+    if index >= self.len() {
+      return None;
+    }
+
+    let mut siblings = vec![];
+    let mut position = index;
+    for level in &self.levels[..self.levels.len() - 1] {
+      let sibling = position ^ 1;
+      if sibling < level.len() {
+        siblings.push(level[sibling]);
+      }
+      position /= 2;
+    }
+
+    Some(MerkleProof {
+      index: index as u64,
+      size: self.len() as u64,
+      siblings,
+    })
+  }
+}
+
+/// Proves that a leaf at a given position is part of a merkle tree
+/// of a given size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+  /// Position of the proven leaf in the tree.
+  pub index: u64,
+
+  /// Number of leaves in the tree.
+  pub size: u64,
+
+  /// Hashes of sibling nodes on the path from the leaf to the root.
+  #[serde(with = "crate::readable::b58_seq")]
+  pub siblings: Vec<Digest>,
+}
+
+impl MerkleProof {
+  /// Computes the root of the tree that contains the given leaf at the
+  /// position described by this proof.
+  ///
+  /// Returns `None` if the proof is malformed.
+  pub fn compute_root(&self, leaf: &Digest) -> Option<Multihash> {
+    if self.index >= self.size {
+      return None;
+    }
+
+    let mut siblings = self.siblings.iter();
+    let mut hash = *leaf;
+    let mut position = self.index;
+    let mut width = self.size;
+
+    while width > 1 {
+      if position % 2 == 1 {
+        hash = node_hash(siblings.next()?, &hash);
+      } else if position + 1 < width {
+        hash = node_hash(&hash, siblings.next()?);
+      } // else: last node on an odd level, promoted unchanged
+
+      position /= 2;
+      width = (width + 1) / 2;
+    }
+
+    match siblings.next() {
+      Some(_) => None, // unused siblings
+      None => Some(wrap(&hash)),
+    }
+  }
+
+  /// Verifies that the given leaf is part of a tree with the given root.
+  pub fn verify(&self, leaf: &Digest, root: &Multihash) -> bool {
+    self.compute_root(leaf).as_ref() == Some(root)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{leaf_hash, MerkleTree};
+
+  #[test]
+  fn proofs_for_all_sizes() {
+    for size in 1..=17u8 {
+      let leaves: Vec<_> = (0..size).map(|i| leaf_hash(&[i])).collect();
+      let tree = MerkleTree::new(leaves.clone());
+      let root = tree.root();
+
+      for (index, leaf) in leaves.iter().enumerate() {
+        let proof = tree.proof(index).unwrap();
+        assert!(proof.verify(leaf, &root));
+
+        // wrong leaf
+        assert!(!proof.verify(&leaf_hash(b"other"), &root));
+
+        // wrong position
+        let mut moved = proof.clone();
+        moved.index = (moved.index + 1) % moved.size;
+        assert!(size == 1 || !moved.verify(leaf, &root));
+      }
+
+      assert!(tree.proof(size as usize).is_none());
+    }
+  }
+
+  #[test]
+  fn updates_match_rebuilt_trees() {
+    for size in 1..=17u8 {
+      let mut leaves: Vec<_> = (0..size).map(|i| leaf_hash(&[i])).collect();
+      let mut tree = MerkleTree::new(leaves.clone());
+
+      for index in 0..size as usize {
+        leaves[index] = leaf_hash(&[size, index as u8]);
+        assert!(tree.update(index, leaves[index]));
+        assert_eq!(tree.root(), MerkleTree::new(leaves.clone()).root());
+      }
+
+      assert!(!tree.update(size as usize, leaf_hash(b"other")));
+    }
+  }
+
+  #[test]
+  fn root_depends_on_order() {
+    let a = leaf_hash(b"a");
+    let b = leaf_hash(b"b");
+    assert_ne!(
+      MerkleTree::new(vec![a, b]).root(),
+      MerkleTree::new(vec![b, a]).root()
+    );
+    assert_ne!(
+      MerkleTree::new(vec![]).root(),
+      MerkleTree::new(vec![a]).root()
+    );
+  }
+}
@@ -0,0 +1,247 @@
+use {
+  crate::{encoding, ExpressionTree},
+  alloc::{boxed::Box, vec::Vec},
+  serde::Serialize,
+};
+
+impl<T: Serialize> ExpressionTree<T> {
+  /// Rewrites the tree into an equivalent canonical form.
+  ///
+  /// The normalized tree:
+  ///   - uses `All` and `Any` instead of binary `And` and `Or`, with nested
+  ///     nodes of the same kind flattened into their parent,
+  ///   - has negations pushed inward, so `Not` only wraps leaves,
+  ///   - has no duplicate subexpressions in `All` and `Any`,
+  ///   - has constant subexpressions folded, with `All([])` being true and
+  ///     `Any([])` being false,
+  ///   - has thresholds that are equivalent to `All` or `Any` replaced by them,
+  ///   - has subexpressions sorted by their canonical encoding.
+  ///
+  /// Two trees that differ only in the above are normalized to the same
+  /// tree, so normalized trees can be compared and hashed by structure.
+  /// Normalization is idempotent.
+  pub fn normalize(self) -> Self {
+    self.normalized(false)
+  }
+
+  fn normalized(self, negate: bool) -> Self {
+    let children = |trees: Vec<Self>| -> Vec<Self> {
+      trees.into_iter().map(|t| t.normalized(negate)).collect()
+    };
+
+    match self {
+      ExpressionTree::Id(v) => match negate {
+        true => ExpressionTree::Not(Box::new(ExpressionTree::Id(v))),
+        false => ExpressionTree::Id(v),
+      },
+      ExpressionTree::Not(t) => t.normalized(!negate),
+      ExpressionTree::And(l, r) => match negate {
+        true => disjunction(children(alloc::vec![*l, *r])),
+        false => conjunction(children(alloc::vec![*l, *r])),
+      },
+      ExpressionTree::Or(l, r) => match negate {
+        true => conjunction(children(alloc::vec![*l, *r])),
+        false => disjunction(children(alloc::vec![*l, *r])),
+      },
+      ExpressionTree::All(ts) => match negate {
+        true => disjunction(children(ts)),
+        false => conjunction(children(ts)),
+      },
+      ExpressionTree::Any(ts) => match negate {
+        true => conjunction(children(ts)),
+        false => disjunction(children(ts)),
+      },
+      ExpressionTree::Threshold(k, ts) => match negate {
+        // fewer than k satisfied is the same as
+        // at least n - k + 1 not satisfied.
+        true => threshold((ts.len() + 1).saturating_sub(k), children(ts)),
+        false => threshold(k, children(ts)),
+      },
+    }
+  }
+
+  fn is_true(&self) -> bool {
+    matches!(self, ExpressionTree::All(ts) if ts.is_empty())
+  }
+
+  fn is_false(&self) -> bool {
+    matches!(self, ExpressionTree::Any(ts) if ts.is_empty())
+  }
+}
+
+/// All of already normalized trees.
+fn conjunction<T: Serialize>(
+  trees: Vec<ExpressionTree<T>>,
+) -> ExpressionTree<T> {
+  if trees.iter().any(ExpressionTree::is_false) {
+    return ExpressionTree::Any(Vec::new());
+  }
+
+  let mut flat = Vec::with_capacity(trees.len());
+  for tree in trees {
+    match tree {
+      ExpressionTree::All(ts) => flat.extend(ts),
+      tree => flat.push(tree),
+    }
+  }
+
+  match sorted(flat, true) {
+    mut ts if ts.len() == 1 => ts.remove(0),
+    ts => ExpressionTree::All(ts),
+  }
+}
+
+/// Any of already normalized trees.
+fn disjunction<T: Serialize>(
+  trees: Vec<ExpressionTree<T>>,
+) -> ExpressionTree<T> {
+  if trees.iter().any(ExpressionTree::is_true) {
+    return ExpressionTree::All(Vec::new());
+  }
+
+  let mut flat = Vec::with_capacity(trees.len());
+  for tree in trees {
+    match tree {
+      ExpressionTree::Any(ts) => flat.extend(ts),
+      tree => flat.push(tree),
+    }
+  }
+
+  match sorted(flat, true) {
+    mut ts if ts.len() == 1 => ts.remove(0),
+    ts => ExpressionTree::Any(ts),
+  }
+}
+
+/// At least `k` of already normalized trees.
+fn threshold<T: Serialize>(
+  mut k: usize,
+  trees: Vec<ExpressionTree<T>>,
+) -> ExpressionTree<T> {
+  // constant subexpressions either always
+  // count towards k or never do.
+  let mut remaining = Vec::with_capacity(trees.len());
+  for tree in trees {
+    if tree.is_true() {
+      k = k.saturating_sub(1);
+    } else if !tree.is_false() {
+      remaining.push(tree);
+    }
+  }
+
+  match k {
+    0 => ExpressionTree::All(Vec::new()),
+    1 => disjunction(remaining),
+    k if k == remaining.len() => conjunction(remaining),
+    k if k > remaining.len() => ExpressionTree::Any(Vec::new()),
+    // duplicates are counted more than once, so they are kept
+    k => ExpressionTree::Threshold(k, sorted(remaining, false)),
+  }
+}
+
+/// Sorts trees by their canonical encoding, optionally removing duplicates.
+fn sorted<T: Serialize>(
+  trees: Vec<ExpressionTree<T>>,
+  dedup: bool,
+) -> Vec<ExpressionTree<T>> {
+  let mut keyed: Vec<_> = trees
+    .into_iter()
+    .map(|tree| (encoding::encode(&tree), tree))
+    .collect();
+  keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+  if dedup {
+    keyed.dedup_by(|(a, _), (b, _)| a == b);
+  }
+  keyed.into_iter().map(|(_, tree)| tree).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    crate::ExpressionTree::{self, *},
+    alloc::{boxed::Box, vec::Vec},
+  };
+
+  fn not(tree: ExpressionTree<u8>) -> ExpressionTree<u8> {
+    Not(Box::new(tree))
+  }
+
+  fn and(l: ExpressionTree<u8>, r: ExpressionTree<u8>) -> ExpressionTree<u8> {
+    And(Box::new(l), Box::new(r))
+  }
+
+  fn or(l: ExpressionTree<u8>, r: ExpressionTree<u8>) -> ExpressionTree<u8> {
+    Or(Box::new(l), Box::new(r))
+  }
+
+  /// Evaluates a tree where leaf `i` is the i-th bit of `bits`.
+  fn evaluate(tree: &ExpressionTree<u8>, bits: u8) -> bool {
+    tree.clone().reduce(
+      |v| bits & (1 << v) != 0,
+      |v| !v,
+      |a, b| a && b,
+      |a, b| a || b,
+      |k, vs| vs.into_iter().filter(|v| *v).count() >= k,
+    )
+  }
+
+  fn trees() -> Vec<ExpressionTree<u8>> {
+    vec![
+      not(not(Id(0))),
+      and(Id(1), and(Id(0), Id(1))),
+      or(and(Id(0), Id(1)), not(or(Id(2), Id(0)))),
+      not(All(vec![Id(0), Any(vec![Id(1), not(Id(2))]), Id(3)])),
+      Threshold(2, vec![Id(0), Id(1), Id(2)]),
+      not(Threshold(2, vec![Id(0), not(Id(1)), Id(2), Id(3)])),
+      Threshold(2, vec![Id(0), Id(0), Id(1)]),
+      Threshold(2, vec![All(vec![]), Id(1), Any(vec![]), Id(2)]),
+      Threshold(5, vec![Id(0), Id(1)]),
+      not(Threshold(0, vec![Id(0)])),
+      All(vec![Id(0), Any(vec![]), Id(1)]),
+      Any(vec![Id(2), not(Any(vec![])), Id(1)]),
+      All(vec![Any(vec![Id(0), Id(1)]), Any(vec![Id(1), Id(0)])]),
+    ]
+  }
+
+  #[test]
+  fn preserves_semantics() {
+    for tree in trees() {
+      let normalized = tree.clone().normalize();
+      for bits in 0..16 {
+        assert_eq!(
+          evaluate(&tree, bits),
+          evaluate(&normalized, bits),
+          "{tree:?} normalized to {normalized:?}"
+        );
+      }
+      assert_eq!(normalized.clone().normalize(), normalized);
+    }
+  }
+
+  #[test]
+  fn canonical_forms() {
+    assert_eq!(not(not(Id(0))).normalize(), Id(0));
+    assert_eq!(
+      and(Id(1), and(Id(0), Id(1))).normalize(),
+      All(vec![Id(0), Id(1)])
+    );
+    assert_eq!(and(Id(1), Id(0)).normalize(), and(Id(0), Id(1)).normalize());
+    assert_eq!(
+      not(or(Id(0), Id(1))).normalize(),
+      All(vec![not(Id(0)), not(Id(1))])
+    );
+    assert_eq!(
+      Threshold(2, vec![All(vec![]), Id(1), Any(vec![]), Id(2)]).normalize(),
+      Any(vec![Id(1), Id(2)])
+    );
+    assert_eq!(
+      Threshold(3, vec![Id(2), Id(0), Id(2), Id(1)]).normalize(),
+      Threshold(3, vec![Id(0), Id(1), Id(2), Id(2)])
+    );
+    assert_eq!(
+      All(vec![Any(vec![Id(0), Id(1)]), Any(vec![Id(1), Id(0)])]).normalize(),
+      Any(vec![Id(0), Id(1)])
+    );
+    assert_eq!(not(Threshold(0, vec![Id(0)])).normalize(), Any(vec![]));
+  }
+}
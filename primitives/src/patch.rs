@@ -0,0 +1,178 @@
+use {
+  alloc::{collections::BTreeMap, string::String, vec::Vec},
+  core::fmt::Display,
+  serde::{Deserialize, Serialize},
+};
+
+/// A partial update of an account state.
+///
+/// Patches let transactions change a part of a large account state
+/// without carrying the entire new state. They are applied to the
+/// current state of the account when the transaction is executed.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum StatePatch {
+  /// Overwrites the state starting at a byte offset. The offset must not
+  /// be past the end of the current state, the state grows if the bytes
+  /// extend beyond its end.
+  Range {
+    offset: u64,
+    #[serde(with = "crate::readable::b58")]
+    bytes: Vec<u8>,
+  },
+
+  /// Appends bytes to the end of the state.
+  Append(#[serde(with = "crate::readable::b58")] Vec<u8>),
+
+  /// Sets entries of a map-shaped state to new values, or removes
+  /// them when the value is `None`.
+  ///
+  /// Map-shaped state is a MessagePack map of string keys to byte
+  /// values, see [`StatePatch::decode_map`]. An empty state is an
+  /// empty map.
+  Entries(
+    #[serde(with = "crate::readable::b58_entries")]
+    BTreeMap<String, Option<Vec<u8>>>,
+  ),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+  /// A byte range patch starts past the end of the current state.
+  OutOfBounds { offset: u64, len: usize },
+
+  /// Entries are patched in a state that is not map-shaped.
+  NotAMap,
+}
+
+impl Display for PatchError {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      PatchError::OutOfBounds { offset, len } => {
+        write!(f, "Patch offset {offset} is past the state end at {len}")
+      }
+      PatchError::NotAMap => write!(f, "State is not a map"),
+    }
+  }
+}
+
+// https://github.com/rust-lang/rust/issues/103765
+#[cfg(not(target_family = "wasm"))]
+impl std::error::Error for PatchError {}
+
+impl StatePatch {
+  /// Produces the state that results from applying this
+  /// patch to the given current state.
+  pub fn apply(&self, state: &[u8]) -> Result<Vec<u8>, PatchError> {
+    match self {
+      StatePatch::Range { offset, bytes } => {
+        let start = usize::try_from(*offset)
+          .ok()
+          .filter(|start| *start <= state.len())
+          .ok_or(PatchError::OutOfBounds {
+            offset: *offset,
+            len: state.len(),
+          })?;
+        let mut output = state.to_vec();
+        let overlap = bytes.len().min(state.len() - start);
+        output[start..start + overlap].copy_from_slice(&bytes[..overlap]);
+        output.extend_from_slice(&bytes[overlap..]);
+        Ok(output)
+      }
+      StatePatch::Append(bytes) => {
+        let mut output = state.to_vec();
+        output.extend_from_slice(bytes);
+        Ok(output)
+      }
+      StatePatch::Entries(entries) => {
+        let mut map = Self::decode_map(state)?;
+        for (key, value) in entries {
+          match value {
+            Some(value) => map.insert(key.clone(), value.clone()),
+            None => map.remove(key),
+          };
+        }
+        Ok(Self::encode_map(&map))
+      }
+    }
+  }
+
+  /// Decodes a map-shaped account state.
+  pub fn decode_map(
+    state: &[u8],
+  ) -> Result<BTreeMap<String, Vec<u8>>, PatchError> {
+    match state.is_empty() {
+      true => Ok(BTreeMap::new()),
+      false => rmp_serde::from_slice(state).map_err(|_| PatchError::NotAMap),
+    }
+  }
+
+  /// Encodes a map-shaped account state, entries are
+  /// always written with keys in ascending order.
+  pub fn encode_map(map: &BTreeMap<String, Vec<u8>>) -> Vec<u8> {
+    rmp_serde::to_vec(map).expect("maps are always serializable")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{PatchError, StatePatch},
+    alloc::collections::BTreeMap,
+  };
+
+  #[test]
+  fn byte_patches() {
+    let range = |offset, bytes: &[u8]| StatePatch::Range {
+      offset,
+      bytes: bytes.to_vec(),
+    };
+
+    assert_eq!(range(1, b"xy").apply(b"abcd").unwrap(), b"axyd");
+    assert_eq!(range(3, b"xy").apply(b"abcd").unwrap(), b"abcxy");
+    assert_eq!(range(4, b"xy").apply(b"abcd").unwrap(), b"abcdxy");
+    assert_eq!(range(0, b"").apply(b"").unwrap(), b"");
+    assert!(matches!(
+      range(5, b"x").apply(b"abcd"),
+      Err(PatchError::OutOfBounds { offset: 5, len: 4 })
+    ));
+    assert!(matches!(
+      range(u64::MAX, b"x").apply(b"abcd"),
+      Err(PatchError::OutOfBounds { .. })
+    ));
+
+    let append = StatePatch::Append(b"ef".to_vec());
+    assert_eq!(append.apply(b"abcd").unwrap(), b"abcdef");
+  }
+
+  #[test]
+  fn map_patches() {
+    let mut donors = BTreeMap::new();
+    donors.insert("alice".into(), vec![1]);
+    donors.insert("bob".into(), vec![2]);
+    let state = StatePatch::encode_map(&donors);
+
+    let mut entries = BTreeMap::new();
+    entries.insert("bob".into(), None);
+    entries.insert("carol".into(), Some(vec![3]));
+    entries.insert("dave".into(), None);
+    let patched = StatePatch::Entries(entries).apply(&state).unwrap();
+
+    let mut expected = BTreeMap::new();
+    expected.insert("alice".into(), vec![1]);
+    expected.insert("carol".into(), vec![3]);
+    assert_eq!(StatePatch::decode_map(&patched).unwrap(), expected);
+    assert_eq!(patched, StatePatch::encode_map(&expected));
+
+    let mut entries = BTreeMap::new();
+    entries.insert("alice".into(), Some(vec![1]));
+    assert_eq!(
+      StatePatch::Entries(entries.clone()).apply(&[]).unwrap(),
+      StatePatch::encode_map(&[("alice".into(), vec![1])].into())
+    );
+    assert!(matches!(
+      StatePatch::Entries(entries).apply(&rmp_serde::to_vec(&5u64).unwrap()),
+      Err(PatchError::NotAMap)
+    ));
+  }
+}
@@ -1,5 +1,13 @@
 use {
-  crate::{Address, Calldata, Exact, ExpandedAccountChange, Repr},
+  crate::{
+    b58::ToBase58String,
+    verify_calldata_signature,
+    Address,
+    Calldata,
+    Exact,
+    ExpandedAccountChange,
+    Repr,
+  },
   alloc::{
     boxed::Box,
     collections::BTreeMap,
@@ -8,24 +16,55 @@ use {
     vec::Vec,
   },
   core::fmt::Debug,
-  multihash::Multihash,
+  ed25519_dalek::PublicKey,
+  multihash::{Multihash, MultihashDigest},
   serde::{Deserialize, Serialize},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Param {
-  Inline(Vec<u8>),
+  Inline(#[serde(with = "crate::readable::inline")] Vec<u8>),
   AccountRef(Address),
   ProposalRef(Address),
+
+  /// A calldata entry with a given key.
+  ///
+  /// In intent predicates this is an entry in the calldata of the same
+  /// intent. In account predicates this is an entry in the calldata of
+  /// any intent in the transaction, and all intents that have an entry
+  /// with this key must agree on its value.
   CalldataRef(String),
+
+  /// A calldata entry with a given key in a specific intent
+  /// of the transaction.
+  IntentCalldataRef(IntentRef, String),
+}
+
+/// Identifies an intent within a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum IntentRef {
+  /// Position of the intent in the transaction.
+  Index(usize),
+
+  /// Signing hash of the intent. If more than one intent in the
+  /// transaction has this signing hash, then they must agree on the
+  /// value of the referenced calldata entry.
+  SigningHash(#[serde(with = "crate::readable::b58")] Multihash),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ExpandedParam {
-  Inline(Vec<u8>),
-  AccountRef(Address, Vec<u8>),
+  Inline(#[serde(with = "crate::readable::inline")] Vec<u8>),
+  AccountRef(Address, #[serde(with = "crate::readable::b58")] Vec<u8>),
   ProposalRef(Address, ExpandedAccountChange),
-  CalldataRef(String, Vec<u8>),
+  CalldataRef(String, #[serde(with = "crate::readable::b58")] Vec<u8>),
+  IntentCalldataRef(
+    IntentRef,
+    String,
+    #[serde(with = "crate::readable::b58")] Vec<u8>,
+  ),
 }
 
 impl ExpandedParam {
@@ -36,20 +75,50 @@ impl ExpandedParam {
       Self::ProposalRef(_, ac) => match ac {
         ExpandedAccountChange::CreateAccount(acc) => &acc.state,
         ExpandedAccountChange::ReplaceState { proposed, .. } => proposed,
+        ExpandedAccountChange::PatchState { proposed, .. } => proposed,
         ExpandedAccountChange::ReplacePredicates { .. } => &[],
         ExpandedAccountChange::DeleteAccount { .. } => &[],
       },
       Self::CalldataRef(_, v) => v,
+      Self::IntentCalldataRef(_, _, v) => v,
     }
   }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Code {
   /// If the predicate code is inlined then it must export a predicate
   /// named "invoke" and it will be the entrypoint.
-  Inline(Vec<u8>),
+  Inline(#[serde(with = "crate::readable::b58")] Vec<u8>),
   AccountRef(Address, String), // (address, entrypoint)
+
+  /// Code stored in the code registry under its hash, along with the
+  /// entrypoint, see [`Code::registry_address`]. Unlike code stored in
+  /// an account, registered code can't be replaced, so the predicate
+  /// always runs the same bytecode.
+  Hash(#[serde(with = "crate::readable::b58")] Multihash, String),
+}
+
+/// Address of the account whose children are the code registry.
+///
+/// Every registry entry is an account at `/code/<base58 hash>` that holds
+/// the bytecode with that hash as its state. Entries can only be created
+/// with the bytecode matching their address and can't be changed later.
+pub const CODE_REGISTRY: &str = "/code";
+
+impl Code {
+  /// Hash that identifies bytecode in the code registry
+  /// and in the compiled predicates cache.
+  pub fn hash_bytecode(bytecode: &[u8]) -> Multihash {
+    multihash::Code::Sha3_256.digest(bytecode)
+  }
+
+  /// Address of the code registry entry for bytecode with a given hash.
+  pub fn registry_address(hash: &Multihash) -> Address {
+    Address::new(format!("{CODE_REGISTRY}/{}", hash.to_b58()))
+      .expect("base58 is a valid address segment")
+  }
 }
 
 impl core::fmt::Debug for Code {
@@ -62,12 +131,18 @@ impl core::fmt::Debug for Code {
       Self::AccountRef(arg0, arg1) => {
         f.debug_tuple("AccountRef").field(arg0).field(arg1).finish()
       }
+      Self::Hash(arg0, arg1) => f
+        .debug_tuple("Hash")
+        .field(&arg0.to_b58())
+        .field(arg1)
+        .finish(),
     }
   }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct ExpandedCode {
+  #[serde(with = "crate::readable::b58")]
   pub code: Vec<u8>,
   pub entrypoint: String,
 }
@@ -91,11 +166,24 @@ pub struct Predicate<R: Repr = Exact> {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum ExpressionTree<T> {
   Id(T),
   Not(Box<ExpressionTree<T>>),
   And(Box<ExpressionTree<T>>, Box<ExpressionTree<T>>),
   Or(Box<ExpressionTree<T>>, Box<ExpressionTree<T>>),
+
+  /// Satisfied when all subexpressions are satisfied.
+  /// An empty list is always satisfied.
+  All(Vec<ExpressionTree<T>>),
+
+  /// Satisfied when at least one subexpression is satisfied.
+  /// An empty list is never satisfied.
+  Any(Vec<ExpressionTree<T>>),
+
+  /// Satisfied when at least `k` of the subexpressions are satisfied,
+  /// for example a 2-of-3 multisig.
+  Threshold(usize, Vec<ExpressionTree<T>>),
 }
 
 pub type PredicateTree<T = Exact> = ExpressionTree<Predicate<T>>;
@@ -118,6 +206,16 @@ impl<T> ExpressionTree<T> {
         Box::new(l.map(op.clone())),
         Box::new(r.map(op)),
       ),
+      ExpressionTree::All(ts) => ExpressionTree::<V>::All(
+        ts.into_iter().map(|t| t.map(op.clone())).collect(),
+      ),
+      ExpressionTree::Any(ts) => ExpressionTree::<V>::Any(
+        ts.into_iter().map(|t| t.map(op.clone())).collect(),
+      ),
+      ExpressionTree::Threshold(k, ts) => ExpressionTree::<V>::Threshold(
+        k,
+        ts.into_iter().map(|t| t.map(op.clone())).collect(),
+      ),
     }
   }
 
@@ -138,6 +236,22 @@ impl<T> ExpressionTree<T> {
         Box::new(l.try_map(op.clone())?),
         Box::new(r.try_map(op)?),
       ),
+      ExpressionTree::All(ts) => ExpressionTree::<V>::All(
+        ts.into_iter()
+          .map(|t| t.try_map(op.clone()))
+          .collect::<Result<_, _>>()?,
+      ),
+      ExpressionTree::Any(ts) => ExpressionTree::<V>::Any(
+        ts.into_iter()
+          .map(|t| t.try_map(op.clone()))
+          .collect::<Result<_, _>>()?,
+      ),
+      ExpressionTree::Threshold(k, ts) => ExpressionTree::<V>::Threshold(
+        k,
+        ts.into_iter()
+          .map(|t| t.try_map(op.clone()))
+          .collect::<Result<_, _>>()?,
+      ),
     })
   }
 
@@ -158,33 +272,80 @@ impl<T> ExpressionTree<T> {
         l.for_each(op);
         r.for_each(op);
       }
+      ExpressionTree::All(ts)
+      | ExpressionTree::Any(ts)
+      | ExpressionTree::Threshold(_, ts) => {
+        for t in ts {
+          t.for_each(op);
+        }
+      }
     };
   }
 
-  pub fn reduce<IdFn, NotFn, AndFn, OrFn, R>(
+  /// Folds the tree bottom-up into a single value.
+  ///
+  /// `All` and `Any` nodes are folded using `threshold` with `k` equal to
+  /// the number of their subexpressions and one respectively.
+  pub fn reduce<IdFn, NotFn, AndFn, OrFn, ThresholdFn, R>(
     self,
     id: IdFn,
     not: NotFn,
     and: AndFn,
     or: OrFn,
+    threshold: ThresholdFn,
   ) -> R
   where
     IdFn: Fn(T) -> R + Clone,
     NotFn: Fn(R) -> R + Clone,
     AndFn: Fn(R, R) -> R + Clone,
     OrFn: Fn(R, R) -> R + Clone,
+    ThresholdFn: Fn(usize, Vec<R>) -> R + Clone,
   {
+    let reduce_all = |ts: Vec<ExpressionTree<T>>| -> Vec<R> {
+      ts.into_iter()
+        .map(|t| {
+          t.reduce(
+            id.clone(),
+            not.clone(),
+            and.clone(),
+            or.clone(),
+            threshold.clone(),
+          )
+        })
+        .collect()
+    };
+
     match self {
       ExpressionTree::Id(v) => id(v),
-      ExpressionTree::Not(t) => not(t.reduce(id, not.clone(), and, or)),
+      ExpressionTree::Not(t) => {
+        not(t.reduce(id, not.clone(), and, or, threshold))
+      }
       ExpressionTree::And(t1, t2) => and(
-        t1.reduce(id.clone(), not.clone(), and.clone(), or.clone()),
-        t2.reduce(id, not, and.clone(), or),
+        t1.reduce(
+          id.clone(),
+          not.clone(),
+          and.clone(),
+          or.clone(),
+          threshold.clone(),
+        ),
+        t2.reduce(id, not, and.clone(), or, threshold),
       ),
       ExpressionTree::Or(t1, t2) => or(
-        t1.reduce(id.clone(), not.clone(), and.clone(), or.clone()),
-        t2.reduce(id, not, and, or.clone()),
+        t1.reduce(
+          id.clone(),
+          not.clone(),
+          and.clone(),
+          or.clone(),
+          threshold.clone(),
+        ),
+        t2.reduce(id, not, and, or.clone(), threshold),
       ),
+      ExpressionTree::All(ts) => {
+        let k = ts.len();
+        threshold(k, reduce_all(ts))
+      }
+      ExpressionTree::Any(ts) => threshold(1, reduce_all(ts)),
+      ExpressionTree::Threshold(k, ts) => threshold(k, reduce_all(ts)),
     }
   }
 }
@@ -194,13 +355,36 @@ impl<T> ExpressionTree<T> {
 /// a list of all mutated accounts by a transaction.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PredicateContext {
-  /// Intent input key-value pair groupped by the intent hash.
+  /// Intent input key-value pair groupped by the intent signing hash.
   /// Could include things like signature or other arbitrary
   /// input parameters to predicates.
+  #[serde(with = "crate::readable::calldata_by_hash")]
   pub calldata: BTreeMap<Multihash, Calldata>,
 
   /// Changes to accounts that are modified by a transaction.
   pub proposals: BTreeMap<Address, ExpandedAccountChange>,
+
+  /// Sum of the tips declared by all intents of the transaction
+  /// in each token, see [`crate::Tip`].
+  ///
+  /// Intents that tip in the same token are paid to the same wallet
+  /// of the solver, so the wallet must receive all of their tips.
+  #[serde(default)]
+  pub tips: BTreeMap<Address, u64>,
+
+  /// The solver named in the transaction, see [`crate::Tip`].
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub solver: Option<Address>,
+}
+
+impl PredicateContext {
+  /// Checks if any intent in the transaction carries a valid
+  /// signature by the given public key.
+  pub fn is_signed_by(&self, pubkey: &PublicKey) -> bool {
+    self.calldata.iter().any(|(signing_hash, calldata)| {
+      verify_calldata_signature(signing_hash, calldata, pubkey)
+    })
+  }
 }
 
 #[cfg(test)]
@@ -325,6 +509,10 @@ mod tests {
           code: a.to_string().to_uppercase().as_bytes().to_vec(),
           entrypoint: e.to_uppercase(),
         },
+        Code::Hash(h, e) => ExpandedCode {
+          code: h.to_bytes(),
+          entrypoint: e.to_uppercase(),
+        },
       },
       params: pred
         .params
@@ -346,10 +534,70 @@ mod tests {
             c.clone(),
             c.to_uppercase().as_bytes().to_vec(),
           ),
+          Param::IntentCalldataRef(i, c) => ExpandedParam::IntentCalldataRef(
+            i.clone(),
+            c.clone(),
+            c.to_uppercase().as_bytes().to_vec(),
+          ),
         })
         .collect(),
     });
 
     assert_eq!(expected_output_tree, actual_output_tree);
   }
+
+  #[test]
+  fn n_ary_expressions() {
+    use crate::ExpressionTree::{self, *};
+
+    let evaluate = |tree: ExpressionTree<bool>| {
+      tree.reduce(
+        |v| v,
+        |v| !v,
+        |a, b| a && b,
+        |a, b| a || b,
+        |k, vs| vs.into_iter().filter(|v| *v).count() >= k,
+      )
+    };
+
+    assert!(evaluate(All(vec![])));
+    assert!(!evaluate(Any(vec![])));
+    assert!(evaluate(Threshold(0, vec![])));
+    assert!(!evaluate(Threshold(1, vec![Id(false)])));
+    assert!(evaluate(All(vec![
+      Id(true),
+      Any(vec![Id(false), Id(true)])
+    ])));
+    assert!(!evaluate(All(vec![Id(true), Id(false), Id(true)])));
+
+    // 2-of-3
+    let multisig = |a, b, c| Threshold(2, vec![Id(a), Id(b), Id(c)]);
+    assert!(evaluate(multisig(true, false, true)));
+    assert!(evaluate(multisig(true, true, true)));
+    assert!(!evaluate(multisig(false, false, true)));
+    assert!(!evaluate(Not(Box::new(multisig(true, true, false)))));
+
+    // map, try_map and for_each visit leaves of n-ary nodes in order
+    let tree = Threshold(2, vec![
+      Id(1),
+      All(vec![Id(2), Not(Box::new(Id(3)))]),
+      Any(vec![Id(4)]),
+    ]);
+    let mut visited = vec![];
+    tree.for_each(&mut |v| visited.push(*v));
+    assert_eq!(visited, vec![1, 2, 3, 4]);
+
+    let mapped = tree.clone().map(|v| v % 2 == 1);
+    assert_eq!(
+      mapped,
+      Threshold(2, vec![
+        Id(true),
+        All(vec![Id(false), Not(Box::new(Id(true)))]),
+        Any(vec![Id(false)]),
+      ])
+    );
+    assert!(!evaluate(mapped));
+
+    assert_eq!(tree.try_map(|v| if v < 4 { Ok(v) } else { Err(v) }), Err(4));
+  }
 }
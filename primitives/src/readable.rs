@@ -0,0 +1,484 @@
+//! Human readable representation of primitives.
+//!
+//! Serializers that report themselves as human readable, such as JSON,
+//! render primitives differently than the canonical binary encoding:
+//!   - hashes, public keys, signatures and raw bytes are base58 strings,
+//!   - inline params are typed values tagged with their type, one of `bool`,
+//!     `u64`, `address`, `str` or `bytes`, picked the same way as the text
+//!     syntax picks them, so they decode back to the exact bytes,
+//!   - maps keyed by hashes use base58 strings as keys.
+//!
+//! Everything else, including addresses, keeps its serde representation.
+//! Binary serializers are not affected, so the canonical encoding and
+//! hashes of primitives stay the same.
+
+use {
+  crate::{b58::ToBase58String, Address, Calldata},
+  alloc::{collections::BTreeMap, string::String, vec::Vec},
+  ed25519_dalek::{PublicKey, Signature},
+  multihash::Multihash,
+  serde::{
+    de::{DeserializeOwned, Error as _},
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+  },
+};
+
+/// Byte-like values that are rendered as base58 strings
+/// in human readable formats.
+pub(crate) trait Base58:
+  ToBase58String + Serialize + DeserializeOwned
+{
+  fn from_b58_bytes(bytes: Vec<u8>) -> Option<Self>;
+}
+
+impl Base58 for Vec<u8> {
+  fn from_b58_bytes(bytes: Vec<u8>) -> Option<Self> {
+    Some(bytes)
+  }
+}
+
+impl<const N: usize> Base58 for [u8; N]
+where
+  [u8; N]: Serialize + DeserializeOwned,
+{
+  fn from_b58_bytes(bytes: Vec<u8>) -> Option<Self> {
+    bytes.try_into().ok()
+  }
+}
+
+impl Base58 for Multihash {
+  fn from_b58_bytes(bytes: Vec<u8>) -> Option<Self> {
+    Multihash::from_bytes(&bytes).ok()
+  }
+}
+
+impl Base58 for PublicKey {
+  fn from_b58_bytes(bytes: Vec<u8>) -> Option<Self> {
+    PublicKey::from_bytes(&bytes).ok()
+  }
+}
+
+impl Base58 for Signature {
+  fn from_b58_bytes(bytes: Vec<u8>) -> Option<Self> {
+    Signature::try_from(bytes.as_slice()).ok()
+  }
+}
+
+/// Serializes the wrapped value as a base58 string in human readable
+/// formats and as the value itself otherwise.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct B58<T>(T);
+
+impl<T: Base58> Serialize for B58<&T> {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    match s.is_human_readable() {
+      true => s.serialize_str(&self.0.to_b58()),
+      false => self.0.serialize(s),
+    }
+  }
+}
+
+impl<'de, T: Base58> Deserialize<'de> for B58<T> {
+  fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    match d.is_human_readable() {
+      true => bs58::decode(String::deserialize(d)?)
+        .into_vec()
+        .ok()
+        .and_then(T::from_b58_bytes)
+        .map(B58)
+        .ok_or_else(|| D::Error::custom("invalid base58 value")),
+      false => T::deserialize(d).map(B58),
+    }
+  }
+}
+
+/// A single value rendered as base58, for use with `#[serde(with)]`.
+pub(crate) mod b58 {
+  use super::*;
+
+  pub fn serialize<T: Base58, S: Serializer>(
+    value: &T,
+    s: S,
+  ) -> Result<S::Ok, S::Error> {
+    B58(value).serialize(s)
+  }
+
+  pub fn deserialize<'de, T: Base58, D: Deserializer<'de>>(
+    d: D,
+  ) -> Result<T, D::Error> {
+    B58::deserialize(d).map(|B58(value)| value)
+  }
+}
+
+/// An optional value rendered as base58, for use with `#[serde(with)]`.
+pub(crate) mod b58_option {
+  use super::*;
+
+  pub fn serialize<T: Base58, S: Serializer>(
+    value: &Option<T>,
+    s: S,
+  ) -> Result<S::Ok, S::Error> {
+    value.as_ref().map(B58).serialize(s)
+  }
+
+  pub fn deserialize<'de, T: Base58, D: Deserializer<'de>>(
+    d: D,
+  ) -> Result<Option<T>, D::Error> {
+    Option::<B58<T>>::deserialize(d).map(|v| v.map(|B58(value)| value))
+  }
+}
+
+/// A list of values rendered as base58, for use with `#[serde(with)]`.
+pub(crate) mod b58_seq {
+  use super::*;
+
+  pub fn serialize<T: Base58, S: Serializer>(
+    values: &[T],
+    s: S,
+  ) -> Result<S::Ok, S::Error> {
+    s.collect_seq(values.iter().map(B58))
+  }
+
+  pub fn deserialize<'de, T: Base58, D: Deserializer<'de>>(
+    d: D,
+  ) -> Result<Vec<T>, D::Error> {
+    Vec::<B58<T>>::deserialize(d)
+      .map(|v| v.into_iter().map(|B58(value)| value).collect())
+  }
+}
+
+/// Calldata with values rendered as base58, for use with `#[serde(with)]`.
+pub(crate) mod calldata {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(
+    calldata: &Calldata,
+    s: S,
+  ) -> Result<S::Ok, S::Error> {
+    s.collect_map(calldata.iter().map(|(k, v)| (k, B58(v))))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    d: D,
+  ) -> Result<Calldata, D::Error> {
+    BTreeMap::<String, B58<Vec<u8>>>::deserialize(d)
+      .map(|m| m.into_iter().map(|(k, B58(v))| (k, v)).collect())
+  }
+}
+
+/// Map-shaped state patch entries with values rendered as base58,
+/// for use with `#[serde(with)]`.
+pub(crate) mod b58_entries {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(
+    entries: &BTreeMap<String, Option<Vec<u8>>>,
+    s: S,
+  ) -> Result<S::Ok, S::Error> {
+    s.collect_map(entries.iter().map(|(k, v)| (k, v.as_ref().map(B58))))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    d: D,
+  ) -> Result<BTreeMap<String, Option<Vec<u8>>>, D::Error> {
+    BTreeMap::<String, Option<B58<Vec<u8>>>>::deserialize(d)
+      .map(|m| m.into_iter().map(|(k, v)| (k, v.map(|B58(v)| v))).collect())
+  }
+}
+
+/// Calldata grouped by intent signing hashes, with hashes and calldata
+/// values rendered as base58, for use with `#[serde(with)]`.
+pub(crate) mod calldata_by_hash {
+  use super::*;
+
+  struct Readable<T>(T);
+
+  impl Serialize for Readable<&Calldata> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+      calldata::serialize(self.0, s)
+    }
+  }
+
+  impl<'de> Deserialize<'de> for Readable<Calldata> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+      calldata::deserialize(d).map(Readable)
+    }
+  }
+
+  pub fn serialize<S: Serializer>(
+    map: &BTreeMap<Multihash, Calldata>,
+    s: S,
+  ) -> Result<S::Ok, S::Error> {
+    s.collect_map(map.iter().map(|(k, v)| (B58(k), Readable(v))))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    d: D,
+  ) -> Result<BTreeMap<Multihash, Calldata>, D::Error> {
+    BTreeMap::<B58<Multihash>, Readable<Calldata>>::deserialize(d)
+      .map(|m| m.into_iter().map(|(B58(k), Readable(v))| (k, v)).collect())
+  }
+}
+
+/// Inline param bytes rendered as a typed value, for use
+/// with `#[serde(with)]`.
+pub(crate) mod inline {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(
+    bytes: &Vec<u8>,
+    s: S,
+  ) -> Result<S::Ok, S::Error> {
+    match s.is_human_readable() {
+      true => InlineValue::from_bytes(bytes).serialize(s),
+      false => bytes.serialize(s),
+    }
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    d: D,
+  ) -> Result<Vec<u8>, D::Error> {
+    match d.is_human_readable() {
+      true => InlineValue::deserialize(d).map(InlineValue::into_bytes),
+      false => Vec::deserialize(d),
+    }
+  }
+}
+
+/// Typed interpretation of inline param bytes.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum InlineValue {
+  Bool(bool),
+  U64(u64),
+  Address(Address),
+  Str(String),
+  Bytes(#[serde(with = "b58")] Vec<u8>),
+}
+
+impl InlineValue {
+  /// Picks the first of `bool`, `u64`, `address`, `str` and `bytes`
+  /// that encodes to the exact inline bytes.
+  pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+    /// Decodes a value only if the bytes are its exact encoding,
+    /// so encoding it again gives back the same bytes.
+    fn exact<T>(bytes: &[u8]) -> Option<T>
+    where
+      T: Serialize + DeserializeOwned,
+    {
+      rmp_serde::from_slice(bytes)
+        .ok()
+        .filter(|value| rmp_serde::to_vec(value).ok().as_deref() == Some(bytes))
+    }
+
+    if let Some(value) = exact::<bool>(bytes) {
+      Self::Bool(value)
+    } else if let Some(value) = exact::<u64>(bytes) {
+      Self::U64(value)
+    } else if let Some(value) = exact::<String>(bytes) {
+      match Address::new(&value) {
+        Ok(addr) => Self::Address(addr),
+        Err(_) => Self::Str(value),
+      }
+    } else {
+      Self::Bytes(bytes.to_vec())
+    }
+  }
+
+  /// Inline param bytes of the value, encoded the same way
+  /// as [`crate::builder::inline`] encodes them.
+  pub(crate) fn into_bytes(self) -> Vec<u8> {
+    let encoded = match self {
+      Self::Bool(value) => rmp_serde::to_vec(&value),
+      Self::U64(value) => rmp_serde::to_vec(&value),
+      Self::Address(value) => rmp_serde::to_vec(&value),
+      Self::Str(value) => rmp_serde::to_vec(&value),
+      Self::Bytes(bytes) => return bytes,
+    };
+    encoded.expect("serializable parameter")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    crate::{
+      b58::ToBase58String,
+      builder::{self, IntentBuilder, TransactionBuilder},
+      encoding,
+      Account,
+      Address,
+      Block,
+      BlockHeader,
+      MerkleProof,
+      Param,
+      PredicateContext,
+      StatePatch,
+      Transaction,
+    },
+    alloc::collections::BTreeMap,
+    ed25519_dalek::Keypair,
+    multihash::MultihashDigest,
+    rand::rngs::OsRng,
+    serde_json::json,
+  };
+
+  fn transaction(keypair: &Keypair) -> Transaction {
+    let blockhash = multihash::Code::Sha3_256.digest(b"block");
+    let intent = IntentBuilder::new(
+      "testnet",
+      blockhash,
+      builder::stdpred::uint_equal(
+        builder::proposal("/token/usdx"),
+        builder::inline(1500u64),
+      ),
+    )
+    .calldata("memo", b"hello".to_vec())
+    .signed_by(keypair)
+    .build();
+
+    TransactionBuilder::new()
+      .intent(intent)
+      .create("/token/usdx", Account {
+        state: vec![0xff, 0x00],
+        predicates: builder::predicate(
+          builder::code("/token", "predicate"),
+          vec![
+            builder::inline("/token/usdx".parse::<Address>().unwrap()),
+            builder::inline(true),
+            builder::inline("not an address"),
+            builder::inline_bytes(keypair.public.to_bytes()),
+          ],
+        ),
+      })
+      .replace_state("/token/usdx/wallet", vec![1, 2, 3])
+      .patch_state(
+        "/pgqf/1/project/1",
+        StatePatch::Entries(
+          [("alice".into(), Some(vec![1])), ("bob".into(), None)].into(),
+        ),
+      )
+      .build()
+  }
+
+  #[test]
+  fn human_readable_json() {
+    let keypair = Keypair::generate(&mut OsRng);
+    let tx = transaction(&keypair);
+    let value = serde_json::to_value(&tx).unwrap();
+
+    let intent = &value["intents"][0];
+    assert_eq!(
+      intent["recent_blockhash"],
+      json!(tx.intents[0].recent_blockhash.to_b58())
+    );
+    assert_eq!(intent["calldata"]["memo"], json!(b"hello".to_b58()));
+    assert_eq!(
+      intent["expectations"]["Id"]["params"][1],
+      json!({ "Inline": { "u64": 1500 } })
+    );
+
+    let created = &value["proposals"]["/token/usdx"]["CreateAccount"];
+    assert_eq!(created["state"], json!([0xffu8, 0x00].to_b58()));
+    assert_eq!(
+      created["predicates"]["Id"]["params"],
+      json!([
+        { "Inline": { "address": "/token/usdx" } },
+        { "Inline": { "bool": true } },
+        { "Inline": { "str": "not an address" } },
+        { "Inline": { "bytes": keypair.public.to_bytes().to_b58() } },
+      ])
+    );
+
+    let alice = [1u8].to_b58();
+    assert_eq!(
+      value["proposals"]["/pgqf/1/project/1"]["PatchState"]["Entries"],
+      json!({ "alice": alice, "bob": null })
+    );
+
+    let block = Block::new(
+      &Block::genesis(vec![keypair.public]).header,
+      1000,
+      multihash::Code::Sha3_256.digest(b"state"),
+      &keypair,
+      vec![tx],
+    );
+    let value = serde_json::to_value(&block).unwrap();
+    assert_eq!(
+      value["header"]["parent"],
+      json!(block.header.parent.to_b58())
+    );
+    assert_eq!(
+      value["header"]["validators"],
+      json!([keypair.public.to_b58()])
+    );
+    assert_eq!(value["signature"], json!(block.signature.unwrap().to_b58()));
+  }
+
+  #[test]
+  fn json_round_trips() {
+    let keypair = Keypair::generate(&mut OsRng);
+    let tx = transaction(&keypair);
+
+    let json = serde_json::to_string(&tx).unwrap();
+    let decoded: Transaction = serde_json::from_str(&json).unwrap();
+    assert_eq!(encoding::encode(&decoded), encoding::encode(&tx));
+    assert_eq!(decoded.hash(), tx.hash());
+
+    let block = Block::new(
+      &Block::genesis(vec![keypair.public]).header,
+      1000,
+      multihash::Code::Sha3_256.digest(b"state"),
+      &keypair,
+      vec![tx.clone()],
+    );
+    let json = serde_json::to_string(&block).unwrap();
+    let decoded: Block = serde_json::from_str(&json).unwrap();
+    assert_eq!(encoding::encode(&decoded), encoding::encode(&block));
+    assert!(decoded.verify_signature());
+
+    let proof = block.body.prove(0).unwrap();
+    let json = serde_json::to_string(&proof).unwrap();
+    assert_eq!(serde_json::from_str::<MerkleProof>(&json).unwrap(), proof);
+
+    let header: BlockHeader =
+      serde_json::from_str(&serde_json::to_string(&block.header).unwrap())
+        .unwrap();
+    assert!(header.verify_transaction(&tx, &proof));
+
+    let context = PredicateContext {
+      calldata: tx
+        .intents
+        .iter()
+        .map(|intent| (*intent.signing_hash(), intent.calldata.clone()))
+        .collect(),
+      proposals: BTreeMap::new(),
+      tips: BTreeMap::new(),
+      solver: None,
+    };
+    let json = serde_json::to_string(&context).unwrap();
+    let decoded: PredicateContext = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, context);
+    assert!(decoded.is_signed_by(&keypair.public));
+  }
+
+  #[test]
+  fn rejects_invalid_json() {
+    let invalid = [
+      json!({ "Inline": { "u64": -1 } }),
+      json!({ "Inline": { "bytes": "0OIl" } }),
+      json!({ "Inline": [1, 2, 3] }),
+    ];
+    for value in invalid {
+      assert!(serde_json::from_value::<Param>(value).is_err());
+    }
+
+    let keypair = Keypair::generate(&mut OsRng);
+    let mut value = serde_json::to_value(transaction(&keypair)).unwrap();
+    value["intents"][0]["recent_blockhash"] = json!([1, 2, 3].to_b58());
+    assert!(serde_json::from_value::<Transaction>(value).is_err());
+  }
+}
@@ -0,0 +1,774 @@
+//! Human-readable text syntax for predicate trees.
+//!
+//! ```text
+//! all(
+//!   /stdpred/v1:uint_equal(proposal(/token/usdx), u64(1500)),
+//!   not(/stdpred/v1:bytes_equal(account(/a), calldata("b"))),
+//!   threshold(2, /b:sig(bytes(0x01)), /c:sig(bytes(0x02)), /d:sig())
+//! )
+//! ```
+//!
+//! Grammar:
+//!
+//! ```text
+//! tree       := "not" "(" tree ")"
+//!             | ("and" | "or") "(" tree "," tree ")"
+//!             | ("all" | "any") "(" [tree ("," tree)*] ")"
+//!             | "threshold" "(" uint ("," tree)* ")"
+//!             | code "(" [param ("," param)*] ")"
+//! code       := address ":" entrypoint
+//!             | "wasm" "(" hex ")"
+//!             | "hash" "(" hex ")" ":" entrypoint
+//! entrypoint := ident | string
+//! param      := ("account" | "proposal" | "address") "(" address ")"
+//!             | "calldata" "(" [intent ","] string ")"
+//!             | "str" "(" string ")"
+//!             | "u64" "(" uint ")"
+//!             | "bool" "(" ("true" | "false") ")"
+//!             | "bytes" "(" hex ")"
+//! intent     := uint | hex
+//! hex        := "0x" [0-9a-f]*
+//! string     := '"' (any char except '"' and '\' | '\"' | '\\')* '"'
+//! ```
+//!
+//! An intent in a calldata reference is either its position in the
+//! transaction or the hex multihash of its signing hash.
+//!
+//! Trees nested deeper than [`crate::Limits`] allows by default are
+//! rejected while parsing, so untrusted input can't exhaust the stack.
+//!
+//! Whitespace between tokens is ignored. Typed inline parameters `u64`,
+//! `bool`, `str` and `address` are encoded the same way as
+//! [`crate::builder::inline`] encodes them, `bytes` are inlined as is.
+//!
+//! Printing a tree and parsing it back always gives the same tree. Since
+//! inline parameters don't carry their type, printing picks the first
+//! of `bool`, `u64`, `address`, `str` and `bytes` that encodes to the
+//! exact inline bytes.
+
+use {
+  crate::{
+    readable::InlineValue,
+    Address,
+    Code,
+    Exact,
+    ExpressionTree,
+    IntentRef,
+    Limits,
+    Param,
+    Predicate,
+  },
+  alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+  },
+  core::{
+    fmt::{Display, Formatter, Result as FmtResult, Write},
+    str::FromStr,
+  },
+  multihash::Multihash,
+};
+
+/// Error produced when parsing the text syntax of predicate trees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+  /// Byte offset in the input where the error occured.
+  pub position: usize,
+
+  /// Description of what was expected at that position.
+  pub expected: &'static str,
+}
+
+impl Display for SyntaxError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(
+      f,
+      "expected {} at position {}",
+      self.expected, self.position
+    )
+  }
+}
+
+// https://github.com/rust-lang/rust/issues/103765
+#[cfg(not(target_family = "wasm"))]
+impl std::error::Error for SyntaxError {}
+
+impl Display for Param {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Param::AccountRef(addr) => write!(f, "account({addr})"),
+      Param::ProposalRef(addr) => write!(f, "proposal({addr})"),
+      Param::CalldataRef(key) => {
+        f.write_str("calldata(")?;
+        write_string(f, key)?;
+        f.write_char(')')
+      }
+      Param::IntentCalldataRef(intent, key) => {
+        f.write_str("calldata(")?;
+        match intent {
+          IntentRef::Index(ix) => write!(f, "{ix}")?,
+          IntentRef::SigningHash(hash) => write_hex(f, &hash.to_bytes())?,
+        }
+        f.write_str(", ")?;
+        write_string(f, key)?;
+        f.write_char(')')
+      }
+      Param::Inline(bytes) => write_inline(f, bytes),
+    }
+  }
+}
+
+impl Display for Code {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    match self {
+      Code::AccountRef(addr, entrypoint) => {
+        write!(f, "{addr}:")?;
+        write_entrypoint(f, entrypoint)
+      }
+      Code::Inline(wasm) => {
+        f.write_str("wasm(")?;
+        write_hex(f, wasm)?;
+        f.write_char(')')
+      }
+      Code::Hash(hash, entrypoint) => {
+        f.write_str("hash(")?;
+        write_hex(f, &hash.to_bytes())?;
+        f.write_str("):")?;
+        write_entrypoint(f, entrypoint)
+      }
+    }
+  }
+}
+
+impl Display for Predicate<Exact> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(f, "{}(", self.code)?;
+    for (ix, param) in self.params.iter().enumerate() {
+      if ix != 0 {
+        f.write_str(", ")?;
+      }
+      write!(f, "{param}")?;
+    }
+    f.write_char(')')
+  }
+}
+
+/// Prints the tree on a single line, or with `{:#}`
+/// with every subexpression on its own indented line.
+impl Display for ExpressionTree<Predicate<Exact>> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let indent = f.alternate().then_some(0);
+    write_tree(f, self, indent)
+  }
+}
+
+impl FromStr for ExpressionTree<Predicate<Exact>> {
+  type Err = SyntaxError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut parser = Parser {
+      input: s,
+      pos: 0,
+      depth: 0,
+      max_depth: Limits::default().max_tree_depth,
+    };
+    let tree = parser.tree()?;
+    parser.skip_whitespace();
+    match parser.pos == s.len() {
+      true => Ok(tree),
+      false => Err(parser.error("end of input")),
+    }
+  }
+}
+
+/// Predicate tree that has a text syntax.
+type Tree = ExpressionTree<Predicate<Exact>>;
+
+fn write_tree(
+  f: &mut Formatter<'_>,
+  tree: &Tree,
+  indent: Option<usize>,
+) -> FmtResult {
+  let (name, k, children): (_, _, Vec<_>) = match tree {
+    ExpressionTree::Id(pred) => return write!(f, "{pred}"),
+    ExpressionTree::Not(t) => ("not", None, alloc::vec![t.as_ref()]),
+    ExpressionTree::And(l, r) => {
+      ("and", None, alloc::vec![l.as_ref(), r.as_ref()])
+    }
+    ExpressionTree::Or(l, r) => {
+      ("or", None, alloc::vec![l.as_ref(), r.as_ref()])
+    }
+    ExpressionTree::All(ts) => ("all", None, ts.iter().collect()),
+    ExpressionTree::Any(ts) => ("any", None, ts.iter().collect()),
+    ExpressionTree::Threshold(k, ts) => {
+      ("threshold", Some(k), ts.iter().collect())
+    }
+  };
+
+  write!(f, "{name}(")?;
+  if let Some(k) = k {
+    write!(f, "{k}")?;
+  }
+
+  let inner = indent.map(|i| i + 1);
+  for (ix, child) in children.iter().enumerate() {
+    if ix != 0 || k.is_some() {
+      f.write_char(',')?;
+      if inner.is_none() {
+        f.write_char(' ')?;
+      }
+    }
+    if let Some(depth) = inner {
+      f.write_char('\n')?;
+      write_indent(f, depth)?;
+    }
+    write_tree(f, child, inner)?;
+  }
+
+  if let (Some(depth), false) = (indent, children.is_empty()) {
+    f.write_char('\n')?;
+    write_indent(f, depth)?;
+  }
+  f.write_char(')')
+}
+
+fn write_indent(f: &mut Formatter<'_>, depth: usize) -> FmtResult {
+  for _ in 0..depth {
+    f.write_str("  ")?;
+  }
+  Ok(())
+}
+
+fn write_inline(f: &mut Formatter<'_>, bytes: &[u8]) -> FmtResult {
+  match InlineValue::from_bytes(bytes) {
+    InlineValue::Bool(value) => write!(f, "bool({value})"),
+    InlineValue::U64(value) => write!(f, "u64({value})"),
+    InlineValue::Address(addr) => write!(f, "address({addr})"),
+    InlineValue::Str(value) => {
+      f.write_str("str(")?;
+      write_string(f, &value)?;
+      f.write_char(')')
+    }
+    InlineValue::Bytes(bytes) => {
+      f.write_str("bytes(")?;
+      write_hex(f, &bytes)?;
+      f.write_char(')')
+    }
+  }
+}
+
+fn write_entrypoint(f: &mut Formatter<'_>, entrypoint: &str) -> FmtResult {
+  match is_ident(entrypoint) {
+    true => f.write_str(entrypoint),
+    false => write_string(f, entrypoint),
+  }
+}
+
+fn write_hex(f: &mut Formatter<'_>, bytes: &[u8]) -> FmtResult {
+  f.write_str("0x")?;
+  for byte in bytes {
+    write!(f, "{byte:02x}")?;
+  }
+  Ok(())
+}
+
+fn write_string(f: &mut Formatter<'_>, value: &str) -> FmtResult {
+  f.write_char('"')?;
+  for c in value.chars() {
+    if c == '"' || c == '\\' {
+      f.write_char('\\')?;
+    }
+    f.write_char(c)?;
+  }
+  f.write_char('"')
+}
+
+fn is_ident_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_ident(value: &str) -> bool {
+  !value.is_empty() && value.chars().all(is_ident_char)
+}
+
+fn is_address_char(c: char) -> bool {
+  c.is_alphanumeric() || matches!(c, '/' | '.' | '-' | '_')
+}
+
+struct Parser<'s> {
+  input: &'s str,
+  pos: usize,
+
+  /// Depth of the tree currently being parsed, a predicate has depth 1.
+  depth: usize,
+  max_depth: usize,
+}
+
+impl<'s> Parser<'s> {
+  fn error(&self, expected: &'static str) -> SyntaxError {
+    SyntaxError {
+      position: self.pos,
+      expected,
+    }
+  }
+
+  fn rest(&self) -> &'s str {
+    &self.input[self.pos..]
+  }
+
+  fn skip_whitespace(&mut self) {
+    let rest = self.rest();
+    self.pos += rest.len() - rest.trim_start().len();
+  }
+
+  /// Consumes a character if it is next in the input.
+  fn eat(&mut self, c: char) -> bool {
+    self.skip_whitespace();
+    match self.rest().starts_with(c) {
+      true => {
+        self.pos += c.len_utf8();
+        true
+      }
+      false => false,
+    }
+  }
+
+  fn expect(
+    &mut self,
+    c: char,
+    expected: &'static str,
+  ) -> Result<(), SyntaxError> {
+    match self.eat(c) {
+      true => Ok(()),
+      false => Err(self.error(expected)),
+    }
+  }
+
+  /// Consumes the longest prefix of characters matching a predicate.
+  fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'s str {
+    let rest = self.rest();
+    let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+    self.pos += len;
+    &rest[..len]
+  }
+
+  fn ident(&mut self) -> &'s str {
+    self.skip_whitespace();
+    self.take_while(is_ident_char)
+  }
+
+  fn tree(&mut self) -> Result<Tree, SyntaxError> {
+    self.skip_whitespace();
+    if self.depth == self.max_depth {
+      return Err(self.error("a less deeply nested tree"));
+    }
+    self.depth += 1;
+    let tree = self.node();
+    self.depth -= 1;
+    tree
+  }
+
+  fn node(&mut self) -> Result<Tree, SyntaxError> {
+    if self.rest().starts_with('/') {
+      return self.predicate();
+    }
+
+    let start = self.pos;
+    let tree = match self.ident() {
+      "not" => {
+        self.expect('(', "'('")?;
+        let tree = self.tree()?;
+        ExpressionTree::Not(Box::new(tree))
+      }
+      "and" => {
+        let (l, r) = self.pair()?;
+        ExpressionTree::And(Box::new(l), Box::new(r))
+      }
+      "or" => {
+        let (l, r) = self.pair()?;
+        ExpressionTree::Or(Box::new(l), Box::new(r))
+      }
+      "all" => {
+        self.expect('(', "'('")?;
+        ExpressionTree::All(self.trees(false)?)
+      }
+      "any" => {
+        self.expect('(', "'('")?;
+        ExpressionTree::Any(self.trees(false)?)
+      }
+      "threshold" => {
+        self.expect('(', "'('")?;
+        let k = self.uint()?;
+        ExpressionTree::Threshold(k as usize, self.trees(true)?)
+      }
+      "wasm" | "hash" => {
+        self.pos = start;
+        return self.predicate();
+      }
+      _ => {
+        self.pos = start;
+        return Err(self.error("an expression or a predicate"));
+      }
+    };
+
+    self.expect(')', "')'")?;
+    Ok(tree)
+  }
+
+  fn pair(&mut self) -> Result<(Tree, Tree), SyntaxError> {
+    self.expect('(', "'('")?;
+    let l = self.tree()?;
+    self.expect(',', "','")?;
+    let r = self.tree()?;
+    Ok((l, r))
+  }
+
+  /// Comma separated list of trees up to but excluding the closing
+  /// parenthesis, optionally preceded by a comma.
+  fn trees(&mut self, leading_comma: bool) -> Result<Vec<Tree>, SyntaxError> {
+    let mut trees = Vec::new();
+    self.skip_whitespace();
+    while !self.rest().starts_with(')') {
+      if leading_comma || !trees.is_empty() {
+        self.expect(',', "',' or ')'")?;
+      }
+      trees.push(self.tree()?);
+      self.skip_whitespace();
+    }
+    Ok(trees)
+  }
+
+  fn predicate(&mut self) -> Result<Tree, SyntaxError> {
+    let code = self.code()?;
+    self.expect('(', "'('")?;
+
+    let mut params = Vec::new();
+    self.skip_whitespace();
+    while !self.eat(')') {
+      if !params.is_empty() {
+        self.expect(',', "',' or ')'")?;
+      }
+      params.push(self.param()?);
+    }
+
+    Ok(ExpressionTree::Id(Predicate { code, params }))
+  }
+
+  fn code(&mut self) -> Result<Code, SyntaxError> {
+    self.skip_whitespace();
+    if self.rest().starts_with('/') {
+      let address = self.address()?;
+      self.expect(':', "':'")?;
+      Ok(Code::AccountRef(address, self.entrypoint()?))
+    } else {
+      let start = self.pos;
+      match self.ident() {
+        "wasm" => {
+          self.expect('(', "'('")?;
+          let wasm = self.hex()?;
+          self.expect(')', "')'")?;
+          Ok(Code::Inline(wasm))
+        }
+        "hash" => {
+          self.expect('(', "'('")?;
+          let hash = self.multihash()?;
+          self.expect(')', "')'")?;
+          self.expect(':', "':'")?;
+          Ok(Code::Hash(hash, self.entrypoint()?))
+        }
+        _ => {
+          self.pos = start;
+          Err(self.error("an address, wasm or hash code"))
+        }
+      }
+    }
+  }
+
+  fn entrypoint(&mut self) -> Result<String, SyntaxError> {
+    self.skip_whitespace();
+    match self.rest().starts_with('"') {
+      true => self.string(),
+      false => match self.ident() {
+        "" => Err(self.error("an entrypoint")),
+        ident => Ok(ident.to_string()),
+      },
+    }
+  }
+
+  fn param(&mut self) -> Result<Param, SyntaxError> {
+    self.skip_whitespace();
+    let start = self.pos;
+    let kind = self.ident();
+    self.expect('(', "'('")?;
+
+    let param = match kind {
+      "account" => Param::AccountRef(self.address()?),
+      "proposal" => Param::ProposalRef(self.address()?),
+      "calldata" => {
+        self.skip_whitespace();
+        match self.rest().starts_with('"') {
+          true => Param::CalldataRef(self.string()?),
+          false => {
+            let intent = self.intent()?;
+            self.expect(',', "','")?;
+            Param::IntentCalldataRef(intent, self.string()?)
+          }
+        }
+      }
+      "address" => crate::builder::inline(self.address()?),
+      "str" => crate::builder::inline(self.string()?),
+      "u64" => crate::builder::inline(self.uint()?),
+      "bool" => match self.ident() {
+        "true" => crate::builder::inline(true),
+        "false" => crate::builder::inline(false),
+        _ => return Err(self.error("'true' or 'false'")),
+      },
+      "bytes" => Param::Inline(self.hex()?),
+      _ => {
+        self.pos = start;
+        return Err(self.error("a parameter"));
+      }
+    };
+
+    self.expect(')', "')'")?;
+    Ok(param)
+  }
+
+  fn address(&mut self) -> Result<Address, SyntaxError> {
+    self.skip_whitespace();
+    let start = self.pos;
+    let path = self.take_while(is_address_char);
+    Address::new(path).map_err(|_| SyntaxError {
+      position: start,
+      expected: "an address",
+    })
+  }
+
+  fn intent(&mut self) -> Result<IntentRef, SyntaxError> {
+    self.skip_whitespace();
+    match self.rest().starts_with("0x") {
+      true => Ok(IntentRef::SigningHash(self.multihash()?)),
+      false => Ok(IntentRef::Index(self.uint()? as usize)),
+    }
+  }
+
+  fn multihash(&mut self) -> Result<Multihash, SyntaxError> {
+    self.skip_whitespace();
+    let start = self.pos;
+    Multihash::from_bytes(&self.hex()?).map_err(|_| SyntaxError {
+      position: start,
+      expected: "a multihash",
+    })
+  }
+
+  fn uint(&mut self) -> Result<u64, SyntaxError> {
+    self.skip_whitespace();
+    let start = self.pos;
+    let digits = self.take_while(|c| c.is_ascii_digit());
+    digits.parse().map_err(|_| SyntaxError {
+      position: start,
+      expected: "an unsigned integer",
+    })
+  }
+
+  fn hex(&mut self) -> Result<Vec<u8>, SyntaxError> {
+    self.skip_whitespace();
+    if !self.rest().starts_with("0x") {
+      return Err(self.error("'0x'"));
+    }
+    self.pos += 2;
+
+    let start = self.pos;
+    let digits = self.take_while(|c| c.is_ascii_hexdigit());
+    if digits.len() % 2 != 0 {
+      self.pos = start;
+      return Err(self.error("an even number of hex digits"));
+    }
+
+    Ok(
+      (0..digits.len())
+        .step_by(2)
+        .map(|ix| u8::from_str_radix(&digits[ix..ix + 2], 16).expect("hex"))
+        .collect(),
+    )
+  }
+
+  fn string(&mut self) -> Result<String, SyntaxError> {
+    self.expect('"', "'\"'")?;
+    let mut output = String::new();
+    let mut chars = self.rest().chars();
+    loop {
+      match chars.next() {
+        Some('"') => break,
+        Some('\\') => match chars.next() {
+          Some(c @ ('"' | '\\')) => output.push(c),
+          _ => return Err(self.error("a valid escape sequence")),
+        },
+        Some(c) => output.push(c),
+        None => return Err(self.error("a closing '\"'")),
+      }
+    }
+    self.pos = self.input.len() - chars.as_str().len();
+    Ok(output)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::SyntaxError,
+    crate::{
+      builder::{
+        account,
+        calldata,
+        code,
+        inline,
+        inline_bytes,
+        intent_calldata,
+        predicate,
+        proposal,
+        stdpred,
+      },
+      Code,
+      ExpressionTree,
+      IntentRef,
+      Limits,
+      PredicateTree,
+    },
+    alloc::{boxed::Box, string::ToString},
+    multihash::Multihash,
+  };
+
+  fn tree() -> PredicateTree {
+    ExpressionTree::All(vec![
+      stdpred::uint_equal(proposal("/a"), inline(5u64)),
+      ExpressionTree::Not(Box::new(stdpred::bytes_equal(
+        account("/b/c"),
+        calldata("key with \"quotes\" and \\"),
+      ))),
+      ExpressionTree::Or(
+        Box::new(predicate(code("/x", "weird entrypoint"), [])),
+        Box::new(predicate(Code::Inline(vec![0, 97, 115, 109]), [
+          inline(true),
+          inline("not an address"),
+          inline("/an/address"),
+          inline_bytes(vec![0xc1, 0xff]),
+          inline_bytes(vec![]),
+          // non-canonical encoding of 5u64
+          inline_bytes(vec![0xcf, 0, 0, 0, 0, 0, 0, 0, 5]),
+        ])),
+      ),
+      ExpressionTree::Threshold(2, vec![
+        stdpred::constant(true),
+        ExpressionTree::Any(vec![]),
+      ]),
+      predicate(
+        Code::Hash(Multihash::wrap(0, b"code").unwrap(), "run".into()),
+        [],
+      ),
+      stdpred::bytes_equal(
+        intent_calldata(IntentRef::Index(1), "a"),
+        intent_calldata(
+          IntentRef::SigningHash(Multihash::wrap(0, b"hash").unwrap()),
+          "b",
+        ),
+      ),
+    ])
+  }
+
+  #[test]
+  fn round_trips() {
+    let text = tree().to_string();
+    assert_eq!(
+      text,
+      "all(/stdpred/v1:uint_equal(proposal(/a), u64(5)), \
+       not(/stdpred/v1:bytes_equal(account(/b/c), calldata(\"key with \
+       \\\"quotes\\\" and \\\\\"))), or(/x:\"weird entrypoint\"(), \
+       wasm(0x0061736d)(bool(true), str(\"not an address\"), \
+       address(/an/address), bytes(0xc1ff), bytes(0x), \
+       bytes(0xcf0000000000000005))), threshold(2, \
+       /stdpred/v1:constant(bool(true)), any()), hash(0x0004636f6465):run(), \
+       /stdpred/v1:bytes_equal(calldata(1, \"a\"), calldata(0x000468617368, \
+       \"b\")))"
+    );
+    assert_eq!(text.parse::<PredicateTree>(), Ok(tree()));
+
+    let pretty = alloc::format!("{:#}", tree());
+    assert!(pretty.lines().count() > 1);
+    assert_eq!(pretty.parse::<PredicateTree>(), Ok(tree()));
+  }
+
+  #[test]
+  fn parses_hand_written_trees() {
+    assert_eq!(
+      "and( /stdpred/v1:uint_equal(proposal(/a),u64(5)) \
+       ,\nnot(/stdpred/v1:constant(bool(false))))"
+        .parse::<PredicateTree>(),
+      Ok(ExpressionTree::And(
+        Box::new(stdpred::uint_equal(proposal("/a"), inline(5u64))),
+        Box::new(ExpressionTree::Not(Box::new(stdpred::constant(false)))),
+      ))
+    );
+
+    let error = |position, expected| Err(SyntaxError { position, expected });
+    assert_eq!(
+      "".parse::<PredicateTree>(),
+      error(0, "an expression or a predicate")
+    );
+    assert_eq!(
+      "nope(/a:b())".parse::<PredicateTree>(),
+      error(0, "an expression or a predicate")
+    );
+    assert_eq!(
+      "/a:b(u64(x))".parse::<PredicateTree>(),
+      error(9, "an unsigned integer")
+    );
+    assert_eq!("/a/:b()".parse::<PredicateTree>(), error(0, "an address"));
+    assert_eq!(
+      "/a:b(bytes(0x1))".parse::<PredicateTree>(),
+      error(13, "an even number of hex digits")
+    );
+    assert_eq!(
+      "/a:b(calldata(0x01, \"k\"))".parse::<PredicateTree>(),
+      error(14, "a multihash")
+    );
+    assert_eq!(
+      "/a:b() /a:b()".parse::<PredicateTree>(),
+      error(7, "end of input")
+    );
+    assert_eq!(
+      "all(/a:b() /a:b())".parse::<PredicateTree>(),
+      error(11, "',' or ')'")
+    );
+  }
+
+  #[test]
+  fn rejects_too_deep_trees() {
+    let max_depth = Limits::default().max_tree_depth;
+    let nested = |depth: usize| {
+      alloc::format!(
+        "{}/stdpred/v1:constant(bool(true)){}",
+        "not(".repeat(depth - 1),
+        ")".repeat(depth - 1)
+      )
+    };
+
+    let mut deepest = stdpred::constant(true);
+    for _ in 1..max_depth {
+      deepest = ExpressionTree::Not(Box::new(deepest));
+    }
+    assert_eq!(nested(max_depth).parse::<PredicateTree>(), Ok(deepest));
+
+    let position = "not(".len() * max_depth;
+    let expected = "a less deeply nested tree";
+    assert_eq!(
+      nested(max_depth + 1).parse::<PredicateTree>(),
+      Err(SyntaxError { position, expected })
+    );
+    assert_eq!(
+      nested(1_000_000).parse::<PredicateTree>(),
+      Err(SyntaxError { position, expected })
+    );
+  }
+}
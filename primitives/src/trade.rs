@@ -0,0 +1,154 @@
+use {
+  crate::Address,
+  alloc::vec::Vec,
+  serde::{Deserialize, Serialize},
+};
+
+/// An amount of tokens that an intent gives away or asks for.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Resource {
+  /// Address of the token, wallets of the token are its children.
+  pub token: Address,
+
+  /// Number of tokens. Offers give away at most this amount,
+  /// wants receive at least this amount.
+  pub amount: u64,
+
+  /// The only account that may be on the other side of the exchange,
+  /// or `None` if the intent trades with anyone.
+  #[serde(default)]
+  pub counterparty: Option<Address>,
+}
+
+impl Resource {
+  pub fn new(token: Address, amount: u64) -> Self {
+    Self {
+      token,
+      amount,
+      counterparty: None,
+    }
+  }
+
+  pub fn with_counterparty(self, counterparty: Address) -> Self {
+    Self {
+      counterparty: Some(counterparty),
+      ..self
+    }
+  }
+
+  /// The wallet of an account in the token of this resource.
+  ///
+  /// It is the account address nested under the token address,
+  /// e.g. `/token/usdx/alice.eth` for `/alice.eth`.
+  pub fn wallet(&self, owner: &Address) -> Address {
+    Address::new(alloc::format!("{}{}", self.token, owner))
+      .expect("concatenated addresses are valid")
+  }
+}
+
+/// Declares what an intent offers and what it wants in return.
+///
+/// Solvers can't tell what an intent does from its predicates without
+/// simulating it, so intents may describe the exchange they expect in
+/// this form. The description is enforced by the `trade_satisfied`
+/// predicate from the standard library in the intent expectations.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Trade {
+  /// The account trading, whose wallets give away offered
+  /// tokens and receive wanted tokens, see [`Resource::wallet`].
+  pub owner: Address,
+
+  pub offers: Vec<Resource>,
+  pub wants: Vec<Resource>,
+}
+
+impl Trade {
+  pub fn new(owner: Address) -> Self {
+    Self {
+      owner,
+      offers: Vec::new(),
+      wants: Vec::new(),
+    }
+  }
+
+  pub fn offer(mut self, resource: Resource) -> Self {
+    self.offers.push(resource);
+    self
+  }
+
+  pub fn want(mut self, resource: Resource) -> Self {
+    self.wants.push(resource);
+    self
+  }
+
+  /// Checks if the two trades can settle each other, that is each of
+  /// them offers everything the other one wants, to an owner that is
+  /// an acceptable counterparty.
+  pub fn matches(&self, other: &Trade) -> bool {
+    self.covers(other) && other.covers(self)
+  }
+
+  /// Checks if this trade offers everything that the other one wants.
+  fn covers(&self, other: &Trade) -> bool {
+    let accepts = |resource: &Resource, owner: &Address| {
+      resource.counterparty.as_ref().map_or(true, |c| c == owner)
+    };
+
+    other.wants.iter().all(|want| {
+      accepts(want, &self.owner)
+        && self.offers.iter().any(|offer| {
+          offer.token == want.token
+            && offer.amount >= want.amount
+            && accepts(offer, &other.owner)
+        })
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Resource, Trade};
+
+  fn usdx(amount: u64) -> Resource {
+    Resource::new("/token/usdx".parse().unwrap(), amount)
+  }
+
+  fn eurx(amount: u64) -> Resource {
+    Resource::new("/token/eurx".parse().unwrap(), amount)
+  }
+
+  #[test]
+  fn matching_trades() {
+    let alice = Trade::new("/alice.eth".parse().unwrap())
+      .offer(usdx(100))
+      .want(eurx(90));
+    let bob = Trade::new("/bob.eth".parse().unwrap())
+      .offer(eurx(95))
+      .want(usdx(100));
+
+    assert!(alice.matches(&bob));
+    assert!(bob.matches(&alice));
+    assert_eq!(
+      usdx(1).wallet(&alice.owner),
+      "/token/usdx/alice.eth".parse().unwrap()
+    );
+
+    // not enough tokens offered
+    let cheap = Trade::new("/carol.eth".parse().unwrap())
+      .offer(eurx(80))
+      .want(usdx(100));
+    assert!(!alice.matches(&cheap));
+
+    // the counterparty is not acceptable
+    let picky = Trade::new("/dave.eth".parse().unwrap())
+      .offer(eurx(95).with_counterparty("/carol.eth".parse().unwrap()))
+      .want(usdx(100));
+    assert!(!alice.matches(&picky));
+    let picky = Trade::new("/dave.eth".parse().unwrap())
+      .offer(eurx(95))
+      .want(usdx(100).with_counterparty("/alice.eth".parse().unwrap()));
+    assert!(alice.matches(&picky));
+  }
+}
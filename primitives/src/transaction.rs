@@ -1,25 +1,56 @@
 use {
-  crate::{Account, Address, Exact, Intent, PredicateTree, Repr},
+  crate::{
+    encoding,
+    Account,
+    Address,
+    Exact,
+    Intent,
+    PredicateTree,
+    Repr,
+    StatePatch,
+  },
   alloc::{collections::BTreeMap, vec::Vec},
   core::fmt::Debug,
-  multihash::{Hasher, Multihash, MultihashDigest, Sha3_256},
+  multihash::Multihash,
   once_cell::sync::OnceCell,
   serde::{Deserialize, Serialize},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum AccountChange {
   CreateAccount(Account),
-  ReplaceState(Vec<u8>),
+  ReplaceState(#[serde(with = "crate::readable::b58")] Vec<u8>),
   ReplacePredicates(PredicateTree<Exact>),
   DeleteAccount,
+
+  /// Updates a part of the account state, see [`StatePatch`].
+  PatchState(StatePatch),
+
+  /// Deletes the account and all its descendants.
+  ///
+  /// Predicates see this change as a [`ExpandedAccountChange::DeleteAccount`]
+  /// of every account in the subtree, so predicates of all of them and
+  /// their ancestors must be satisfied.
+  DeleteSubtree,
+
+  /// Moves the account and all its descendants under a new address,
+  /// keeping their paths relative to the subtree root.
+  ///
+  /// Predicates see this change as a [`ExpandedAccountChange::DeleteAccount`]
+  /// of every account in the subtree and a
+  /// [`ExpandedAccountChange::CreateAccount`] of its copy under the new
+  /// address. None of the new addresses may exist before the move.
+  MoveSubtree(Address),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub enum ExpandedAccountChange {
   CreateAccount(Account),
   ReplaceState {
+    #[serde(with = "crate::readable::b58")]
     current: Vec<u8>,
+    #[serde(with = "crate::readable::b58")]
     proposed: Vec<u8>,
   },
   ReplacePredicates {
@@ -29,6 +60,16 @@ pub enum ExpandedAccountChange {
   DeleteAccount {
     current: Account,
   },
+
+  /// A patch along with the account state before
+  /// and after it is applied.
+  PatchState {
+    patch: StatePatch,
+    #[serde(with = "crate::readable::b58")]
+    current: Vec<u8>,
+    #[serde(with = "crate::readable::b58")]
+    proposed: Vec<u8>,
+  },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,6 +87,14 @@ pub struct Transaction<R: Repr = Exact> {
   /// this value.
   pub proposals: BTreeMap<Address, R::AccountChange>,
 
+  /// Address of the solver that assembled this transaction.
+  ///
+  /// Tips declared by intents are paid to the solver's wallets,
+  /// see [`crate::Tip::payee`]. It is omitted from the encoding
+  /// when not set.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub solver: Option<Address>,
+
   #[serde(skip)]
   hash_cache: OnceCell<Multihash>,
 }
@@ -58,16 +107,13 @@ impl<R: Repr> Transaction<R> {
     Self {
       intents,
       proposals,
+      solver: None,
       hash_cache: OnceCell::new(),
     }
   }
 
   pub fn hash(&self) -> &Multihash {
-    self.hash_cache.get_or_init(|| {
-      let mut hasher = Sha3_256::default();
-      hasher.update(&rmp_serde::to_vec(self).unwrap());
-      multihash::Code::Sha3_256.wrap(hasher.finalize()).unwrap()
-    })
+    self.hash_cache.get_or_init(|| encoding::hash(self))
   }
 }
 
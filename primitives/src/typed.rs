@@ -0,0 +1,182 @@
+use {
+  crate::{ExpandedParam, Param},
+  alloc::vec::Vec,
+  core::{fmt::Display, marker::PhantomData},
+  serde::{de::DeserializeOwned, Serialize},
+};
+
+#[derive(Debug)]
+pub enum ParamError {
+  /// Param bytes are not a valid encoding of the expected type.
+  Malformed(rmp_serde::decode::Error),
+
+  /// Param bytes hold a valid value followed by more data.
+  TrailingBytes(usize),
+}
+
+impl Display for ParamError {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      ParamError::Malformed(e) => write!(f, "Malformed param: {e}"),
+      ParamError::TrailingBytes(n) => {
+        write!(f, "Param has {n} unexpected trailing bytes")
+      }
+    }
+  }
+}
+
+// https://github.com/rust-lang/rust/issues/103765
+#[cfg(not(target_family = "wasm"))]
+impl std::error::Error for ParamError {}
+
+/// Inline param bytes that hold an encoded value of type `T`.
+///
+/// This is the codec shared by clients that build predicate params and
+/// predicates that read them. Values are encoded using MessagePack, the
+/// same way as [`crate::builder::inline`] encodes them.
+pub struct TypedParam<T> {
+  bytes: Vec<u8>,
+  _type: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedParam<T> {
+  /// Wraps bytes that are expected to hold an encoded `T`.
+  /// They are not checked until decoded.
+  pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+    Self {
+      bytes: bytes.into(),
+      _type: PhantomData,
+    }
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    &self.bytes
+  }
+}
+
+impl<T: Serialize> TypedParam<T> {
+  pub fn new(value: &T) -> Self {
+    Self::from_bytes(rmp_serde::to_vec(value).expect("serializable parameter"))
+  }
+}
+
+impl<T: DeserializeOwned> TypedParam<T> {
+  pub fn decode(&self) -> Result<T, ParamError> {
+    decode(&self.bytes)
+  }
+}
+
+impl<T> From<TypedParam<T>> for Param {
+  fn from(param: TypedParam<T>) -> Self {
+    Param::Inline(param.bytes)
+  }
+}
+
+impl<T> Clone for TypedParam<T> {
+  fn clone(&self) -> Self {
+    Self::from_bytes(self.bytes.clone())
+  }
+}
+
+impl<T> core::fmt::Debug for TypedParam<T> {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_tuple("TypedParam")
+      .field(&core::any::type_name::<T>())
+      .field(&self.bytes)
+      .finish()
+  }
+}
+
+impl Param {
+  /// An inline param holding an encoded value.
+  pub fn typed<T: Serialize>(value: &T) -> Self {
+    TypedParam::new(value).into()
+  }
+}
+
+impl ExpandedParam {
+  /// Decodes the data of this param as a value of type `T`,
+  /// see [`ExpandedParam::data`].
+  pub fn decode<T: DeserializeOwned>(&self) -> Result<T, ParamError> {
+    decode(self.data())
+  }
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ParamError> {
+  let mut deserializer = rmp_serde::Deserializer::new(bytes);
+  let value =
+    T::deserialize(&mut deserializer).map_err(ParamError::Malformed)?;
+  match deserializer.into_inner().len() {
+    0 => Ok(value),
+    n => Err(ParamError::TrailingBytes(n)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{ParamError, TypedParam},
+    crate::{Address, ExpandedAccountChange, ExpandedParam, Param},
+    ed25519_dalek::{Keypair, PublicKey},
+    rand::rngs::OsRng,
+  };
+
+  #[test]
+  fn round_trips() {
+    let address: Address = "/token/usdx".parse().unwrap();
+    let pubkey = Keypair::generate(&mut OsRng).public;
+
+    let param = Param::typed(&1500u64);
+    assert_eq!(param, crate::builder::inline(1500u64));
+    let Param::Inline(bytes) = param else {
+      panic!("typed params are inline");
+    };
+    assert_eq!(ExpandedParam::Inline(bytes).decode::<u64>().unwrap(), 1500);
+
+    let param = TypedParam::new(&address);
+    assert_eq!(param.decode().unwrap(), address);
+    let expanded = ExpandedParam::AccountRef(
+      address.clone(),
+      TypedParam::new(&pubkey).as_bytes().to_vec(),
+    );
+    assert_eq!(expanded.decode::<PublicKey>().unwrap(), pubkey);
+
+    let expanded = ExpandedParam::ProposalRef(
+      address,
+      ExpandedAccountChange::ReplaceState {
+        current: TypedParam::new(&1u64).as_bytes().to_vec(),
+        proposed: TypedParam::new(&2u64).as_bytes().to_vec(),
+      },
+    );
+    assert_eq!(expanded.decode::<u64>().unwrap(), 2);
+  }
+
+  #[test]
+  fn rejects_invalid_params() {
+    assert!(matches!(
+      TypedParam::<u64>::from_bytes(vec![]).decode(),
+      Err(ParamError::Malformed(_))
+    ));
+    assert!(matches!(
+      TypedParam::<u64>::new(&u64::MAX).decode(),
+      Ok(u64::MAX)
+    ));
+    assert!(matches!(
+      TypedParam::<u8>::from_bytes(TypedParam::new(&u64::MAX).as_bytes())
+        .decode(),
+      Err(ParamError::Malformed(_))
+    ));
+    assert!(matches!(
+      TypedParam::<Address>::from_bytes(TypedParam::new(&true).as_bytes())
+        .decode(),
+      Err(ParamError::Malformed(_))
+    ));
+
+    let mut bytes = TypedParam::new(&7u64).as_bytes().to_vec();
+    bytes.extend([1, 2]);
+    assert!(matches!(
+      ExpandedParam::CalldataRef("key".into(), bytes).decode::<u64>(),
+      Err(ParamError::TrailingBytes(2))
+    ));
+  }
+}
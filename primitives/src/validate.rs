@@ -0,0 +1,576 @@
+use {
+  crate::{
+    AccountChange,
+    Address,
+    AddressError,
+    Code,
+    ExpressionTree,
+    Intent,
+    IntentRef,
+    Param,
+    PredicateTree,
+    StatePatch,
+    Transaction,
+  },
+  alloc::{string::ToString, vec},
+  core::fmt::Display,
+};
+
+/// Bounds on the size and shape of transactions.
+///
+/// They are checked by [`Transaction::validate`] before a transaction
+/// touches any state, so malformed transactions are rejected cheaply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+  /// Maximum number of intents in a transaction.
+  pub max_intents: usize,
+
+  /// Maximum number of proposals in a transaction.
+  pub max_proposals: usize,
+
+  /// Maximum length of an address in bytes.
+  pub max_address_len: usize,
+
+  /// Maximum number of calldata entries in an intent.
+  pub max_calldata_entries: usize,
+
+  /// Maximum size of all calldata keys and values of an intent in bytes.
+  pub max_calldata_size: usize,
+
+  /// Maximum size of a proposed account state or state patch in bytes.
+  pub max_state_size: usize,
+
+  /// Maximum size of inline predicate bytecode in bytes.
+  pub max_inline_code_size: usize,
+
+  /// Maximum size of an inline predicate param in bytes.
+  pub max_inline_param_size: usize,
+
+  /// Maximum number of params of a predicate.
+  pub max_params: usize,
+
+  /// Maximum depth of a predicate tree, a single predicate has depth 1.
+  pub max_tree_depth: usize,
+
+  /// Maximum number of nodes in a predicate tree.
+  pub max_tree_nodes: usize,
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Self {
+      max_intents: 64,
+      max_proposals: 1024,
+      max_address_len: 256,
+      max_calldata_entries: 64,
+      max_calldata_size: 64 * 1024,
+      max_state_size: 4 * 1024 * 1024,
+      max_inline_code_size: 1024 * 1024,
+      max_inline_param_size: 64 * 1024,
+      max_params: 16,
+      max_tree_depth: 32,
+      max_tree_nodes: 256,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+  /// The transaction has more intents than allowed.
+  TooManyIntents(usize),
+
+  /// The transaction does not propose any changes.
+  NoProposals,
+
+  /// The transaction has more proposals than allowed.
+  TooManyProposals(usize),
+
+  /// An address is not well formed.
+  InvalidAddress(Address, AddressError),
+
+  /// An address is longer than allowed.
+  AddressTooLong(usize),
+
+  /// An intent has no chain id.
+  MissingChainId,
+
+  /// An intent has more calldata entries than allowed.
+  TooManyCalldataEntries(usize),
+
+  /// Calldata of an intent is larger than allowed.
+  CalldataTooLarge(usize),
+
+  /// A calldata entry or reference has an empty key.
+  EmptyCalldataKey,
+
+  /// A validity window ends before it starts.
+  EmptyValidityWindow,
+
+  /// A proposed account state or patch is larger than allowed.
+  StateTooLarge(Address, usize),
+
+  /// A subtree is moved into itself.
+  InvalidSubtreeMove(Address),
+
+  /// A predicate tree is deeper than allowed.
+  TreeTooDeep(usize),
+
+  /// A predicate tree has more nodes than allowed.
+  TreeTooLarge(usize),
+
+  /// A predicate has more params than allowed.
+  TooManyParams(usize),
+
+  /// Inline predicate bytecode is larger than allowed.
+  InlineCodeTooLarge(usize),
+
+  /// An inline predicate param is larger than allowed.
+  InlineParamTooLarge(usize),
+
+  /// A predicate code has an empty entrypoint.
+  EmptyEntrypoint,
+
+  /// A calldata reference points to an intent index past
+  /// the end of the transaction intents.
+  IntentIndexOutOfRange(usize),
+}
+
+impl Display for ValidationError {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    match self {
+      Self::TooManyIntents(n) => write!(f, "Too many intents: {n}"),
+      Self::NoProposals => write!(f, "Transaction proposes no changes"),
+      Self::TooManyProposals(n) => write!(f, "Too many proposals: {n}"),
+      Self::InvalidAddress(a, e) => write!(f, "Invalid address {a}: {e}"),
+      Self::AddressTooLong(n) => write!(f, "Address too long: {n} bytes"),
+      Self::MissingChainId => write!(f, "Intent has no chain id"),
+      Self::TooManyCalldataEntries(n) => {
+        write!(f, "Too many calldata entries: {n}")
+      }
+      Self::CalldataTooLarge(n) => write!(f, "Calldata too large: {n} bytes"),
+      Self::EmptyCalldataKey => write!(f, "Empty calldata key"),
+      Self::EmptyValidityWindow => write!(f, "Validity window is empty"),
+      Self::StateTooLarge(a, n) => {
+        write!(f, "Proposed state of {a} too large: {n} bytes")
+      }
+      Self::InvalidSubtreeMove(a) => {
+        write!(f, "Subtree {a} can't be moved into itself")
+      }
+      Self::TreeTooDeep(n) => write!(f, "Predicate tree too deep: {n}"),
+      Self::TreeTooLarge(n) => {
+        write!(f, "Predicate tree too large: {n} nodes")
+      }
+      Self::TooManyParams(n) => write!(f, "Too many predicate params: {n}"),
+      Self::InlineCodeTooLarge(n) => {
+        write!(f, "Inline predicate code too large: {n} bytes")
+      }
+      Self::InlineParamTooLarge(n) => {
+        write!(f, "Inline predicate param too large: {n} bytes")
+      }
+      Self::EmptyEntrypoint => write!(f, "Empty predicate entrypoint"),
+      Self::IntentIndexOutOfRange(i) => {
+        write!(f, "Intent index {i} out of range")
+      }
+    }
+  }
+}
+
+// https://github.com/rust-lang/rust/issues/103765
+#[cfg(not(target_family = "wasm"))]
+impl std::error::Error for ValidationError {}
+
+impl Transaction {
+  /// Checks that the transaction is well formed and within limits.
+  ///
+  /// This check does not read any state, so it is meant to run before
+  /// transactions are accepted or scheduled for execution. Passing it
+  /// does not mean that the transaction executes successfully.
+  pub fn validate(&self, limits: &Limits) -> Result<(), ValidationError> {
+    if self.intents.len() > limits.max_intents {
+      return Err(ValidationError::TooManyIntents(self.intents.len()));
+    }
+
+    if self.proposals.is_empty() {
+      return Err(ValidationError::NoProposals);
+    }
+
+    if self.proposals.len() > limits.max_proposals {
+      return Err(ValidationError::TooManyProposals(self.proposals.len()));
+    }
+
+    let checker = Checker {
+      limits,
+      intents: Some(self.intents.len()),
+    };
+
+    for intent in &self.intents {
+      checker.intent(intent)?;
+    }
+
+    if let Some(solver) = &self.solver {
+      checker.address(solver)?;
+    }
+
+    for (address, change) in &self.proposals {
+      checker.address(address)?;
+      checker.change(address, change)?;
+    }
+
+    Ok(())
+  }
+}
+
+impl Intent {
+  /// Checks that the intent is well formed and within limits.
+  ///
+  /// References to other intents by their position can't be checked
+  /// without the transaction, [`Transaction::validate`] checks them.
+  pub fn validate(&self, limits: &Limits) -> Result<(), ValidationError> {
+    Checker {
+      limits,
+      intents: None,
+    }
+    .intent(self)
+  }
+}
+
+struct Checker<'l> {
+  limits: &'l Limits,
+
+  /// Number of intents in the transaction, if known.
+  intents: Option<usize>,
+}
+
+impl Checker<'_> {
+  fn intent(&self, intent: &Intent) -> Result<(), ValidationError> {
+    if intent.chain_id.is_empty() {
+      return Err(ValidationError::MissingChainId);
+    }
+
+    if intent.calldata.len() > self.limits.max_calldata_entries {
+      return Err(ValidationError::TooManyCalldataEntries(
+        intent.calldata.len(),
+      ));
+    }
+
+    let mut size = 0;
+    for (key, value) in &intent.calldata {
+      if key.is_empty() {
+        return Err(ValidationError::EmptyCalldataKey);
+      }
+      size += key.len() + value.len();
+    }
+
+    if size > self.limits.max_calldata_size {
+      return Err(ValidationError::CalldataTooLarge(size));
+    }
+
+    if let Some(window) = &intent.terms.validity {
+      if let (Some(from), Some(until)) = (window.valid_from, window.valid_until)
+      {
+        if from > until {
+          return Err(ValidationError::EmptyValidityWindow);
+        }
+      }
+    }
+
+    if let Some(tip) = &intent.terms.tip {
+      self.address(&tip.token)?;
+    }
+
+    if let Some(trade) = &intent.terms.trade {
+      self.address(&trade.owner)?;
+      for resource in trade.offers.iter().chain(trade.wants.iter()) {
+        self.address(&resource.token)?;
+        if let Some(counterparty) = &resource.counterparty {
+          self.address(counterparty)?;
+        }
+      }
+    }
+
+    self.tree(&intent.expectations)
+  }
+
+  fn change(
+    &self,
+    address: &Address,
+    change: &AccountChange,
+  ) -> Result<(), ValidationError> {
+    let state_size = |size: usize| match size > self.limits.max_state_size {
+      true => Err(ValidationError::StateTooLarge(address.clone(), size)),
+      false => Ok(()),
+    };
+
+    match change {
+      AccountChange::CreateAccount(account) => {
+        state_size(account.state.len())?;
+        self.tree(&account.predicates)
+      }
+      AccountChange::ReplaceState(state) => state_size(state.len()),
+      AccountChange::ReplacePredicates(predicates) => self.tree(predicates),
+      AccountChange::PatchState(patch) => match patch {
+        StatePatch::Range { bytes, .. } | StatePatch::Append(bytes) => {
+          state_size(bytes.len())
+        }
+        StatePatch::Entries(entries) => state_size(
+          entries
+            .iter()
+            .map(|(k, v)| k.len() + v.as_ref().map_or(0, |v| v.len()))
+            .sum(),
+        ),
+      },
+      AccountChange::DeleteAccount | AccountChange::DeleteSubtree => Ok(()),
+      AccountChange::MoveSubtree(target) => {
+        self.address(target)?;
+        match target == address || address.is_parent_of(target) {
+          true => Err(ValidationError::InvalidSubtreeMove(address.clone())),
+          false => Ok(()),
+        }
+      }
+    }
+  }
+
+  /// Walks the tree without recursion, so deep trees are
+  /// rejected without exhausting the stack.
+  fn tree(&self, tree: &PredicateTree) -> Result<(), ValidationError> {
+    let mut nodes = 0;
+    let mut stack = vec![(tree, 1)];
+    while let Some((node, depth)) = stack.pop() {
+      nodes += 1;
+      if nodes > self.limits.max_tree_nodes {
+        return Err(ValidationError::TreeTooLarge(nodes));
+      }
+      if depth > self.limits.max_tree_depth {
+        return Err(ValidationError::TreeTooDeep(depth));
+      }
+
+      match node {
+        ExpressionTree::Id(predicate) => {
+          self.code(&predicate.code)?;
+          if predicate.params.len() > self.limits.max_params {
+            return Err(ValidationError::TooManyParams(predicate.params.len()));
+          }
+          for param in &predicate.params {
+            self.param(param)?;
+          }
+        }
+        ExpressionTree::Not(t) => stack.push((t, depth + 1)),
+        ExpressionTree::And(l, r) | ExpressionTree::Or(l, r) => {
+          stack.push((l, depth + 1));
+          stack.push((r, depth + 1));
+        }
+        ExpressionTree::All(ts)
+        | ExpressionTree::Any(ts)
+        | ExpressionTree::Threshold(_, ts) => {
+          stack.extend(ts.iter().map(|t| (t, depth + 1)));
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn code(&self, code: &Code) -> Result<(), ValidationError> {
+    match code {
+      Code::Inline(bytecode) => {
+        match bytecode.len() > self.limits.max_inline_code_size {
+          true => Err(ValidationError::InlineCodeTooLarge(bytecode.len())),
+          false => Ok(()),
+        }
+      }
+      Code::AccountRef(address, entrypoint) => {
+        self.address(address)?;
+        match entrypoint.is_empty() {
+          true => Err(ValidationError::EmptyEntrypoint),
+          false => Ok(()),
+        }
+      }
+      Code::Hash(_, entrypoint) => match entrypoint.is_empty() {
+        true => Err(ValidationError::EmptyEntrypoint),
+        false => Ok(()),
+      },
+    }
+  }
+
+  fn param(&self, param: &Param) -> Result<(), ValidationError> {
+    match param {
+      Param::Inline(bytes) => {
+        match bytes.len() > self.limits.max_inline_param_size {
+          true => Err(ValidationError::InlineParamTooLarge(bytes.len())),
+          false => Ok(()),
+        }
+      }
+      Param::AccountRef(address) | Param::ProposalRef(address) => {
+        self.address(address)
+      }
+      Param::CalldataRef(key) => match key.is_empty() {
+        true => Err(ValidationError::EmptyCalldataKey),
+        false => Ok(()),
+      },
+      Param::IntentCalldataRef(intent, key) => {
+        if key.is_empty() {
+          return Err(ValidationError::EmptyCalldataKey);
+        }
+        match (intent, self.intents) {
+          (IntentRef::Index(ix), Some(len)) if *ix >= len => {
+            Err(ValidationError::IntentIndexOutOfRange(*ix))
+          }
+          _ => Ok(()),
+        }
+      }
+    }
+  }
+
+  /// Addresses decoded from untrusted input did not go
+  /// through the checks of [`Address::new`].
+  fn address(&self, address: &Address) -> Result<(), ValidationError> {
+    let len = address.to_string().len();
+    if len > self.limits.max_address_len {
+      return Err(ValidationError::AddressTooLong(len));
+    }
+    Address::new(address.to_string())
+      .map(|_| ())
+      .map_err(|e| ValidationError::InvalidAddress(address.clone(), e))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{Limits, ValidationError},
+    crate::{all, builder::*, Account, Code, IntentRef, Transaction},
+    alloc::{collections::BTreeMap, vec::Vec},
+    multihash::Multihash,
+  };
+
+  fn intent(expectations: crate::PredicateTree) -> IntentBuilder<'static> {
+    IntentBuilder::new("testnet", Multihash::default(), expectations)
+  }
+
+  fn check(tx: TransactionBuilder) -> Result<(), ValidationError> {
+    tx.build().validate(&Limits::default())
+  }
+
+  #[test]
+  fn valid_transactions() {
+    let tx = TransactionBuilder::new()
+      .intent(
+        intent(all![
+          stdpred::uint_equal(proposal("/token/usdx"), inline(5u64)),
+          stdpred::bytes_equal(
+            calldata("memo"),
+            intent_calldata(IntentRef::Index(0), "memo")
+          ),
+        ])
+        .calldata("memo", b"hello".to_vec())
+        .valid_from(10)
+        .valid_until(10)
+        .build(),
+      )
+      .replace_state("/token/usdx", vec![5])
+      .move_subtree("/a/b", "/a/c");
+    assert_eq!(check(tx), Ok(()));
+  }
+
+  #[test]
+  fn malformed_transactions() {
+    let limits = Limits::default();
+    let noop = || intent(stdpred::constant(true));
+
+    assert_eq!(
+      Transaction::new(vec![], BTreeMap::new()).validate(&limits),
+      Err(ValidationError::NoProposals)
+    );
+
+    assert_eq!(
+      check(
+        TransactionBuilder::new()
+          .intent(noop().calldata("", vec![]).build())
+          .delete("/a")
+      ),
+      Err(ValidationError::EmptyCalldataKey)
+    );
+
+    assert_eq!(
+      check(
+        TransactionBuilder::new()
+          .intent(noop().calldata("big", vec![0; 64 * 1024]).build())
+          .delete("/a")
+      ),
+      Err(ValidationError::CalldataTooLarge(64 * 1024 + 3))
+    );
+
+    assert_eq!(
+      check(
+        TransactionBuilder::new()
+          .intent(noop().valid_from(5).valid_until(4).build())
+          .delete("/a")
+      ),
+      Err(ValidationError::EmptyValidityWindow)
+    );
+
+    assert_eq!(
+      check(
+        TransactionBuilder::new()
+          .intent(
+            intent(stdpred::bytes_equal(
+              calldata("a"),
+              intent_calldata(IntentRef::Index(1), "a")
+            ))
+            .build()
+          )
+          .delete("/a")
+      ),
+      Err(ValidationError::IntentIndexOutOfRange(1))
+    );
+
+    assert_eq!(
+      check(TransactionBuilder::new().move_subtree("/a", "/a/b")),
+      Err(ValidationError::InvalidSubtreeMove("/a".parse().unwrap()))
+    );
+
+    assert_eq!(
+      check(TransactionBuilder::new().create("/code", Account {
+        state: vec![],
+        predicates: predicate(Code::Inline(vec![0; 2 * 1024 * 1024]), []),
+      })),
+      Err(ValidationError::InlineCodeTooLarge(2 * 1024 * 1024))
+    );
+
+    // addresses that bypassed parsing
+    let invalid: crate::Address = serde_json::from_str("\"/a//b\"").unwrap();
+    assert!(matches!(
+      check(TransactionBuilder::new().delete(invalid)),
+      Err(ValidationError::InvalidAddress(..))
+    ));
+  }
+
+  #[test]
+  fn tree_limits() {
+    let limits = Limits::default();
+
+    let mut deep = stdpred::constant(true);
+    for _ in 0..limits.max_tree_depth {
+      deep = not(deep);
+    }
+    assert_eq!(
+      intent(deep).build().validate(&limits),
+      Err(ValidationError::TreeTooDeep(limits.max_tree_depth + 1))
+    );
+
+    let wide: Vec<_> = (0..limits.max_tree_nodes)
+      .map(|_| stdpred::constant(true))
+      .collect();
+    assert_eq!(
+      intent(all(wide)).build().validate(&limits),
+      Err(ValidationError::TreeTooLarge(limits.max_tree_nodes + 1))
+    );
+
+    let params = (0..=limits.max_params).map(|i| inline(i as u64));
+    assert_eq!(
+      intent(predicate(code("/stdpred/v1", "constant"), params))
+        .build()
+        .validate(&limits),
+      Err(ValidationError::TooManyParams(limits.max_params + 1))
+    );
+  }
+}
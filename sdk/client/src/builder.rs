@@ -1,6 +1,16 @@
 use {
   anoma_primitives::{Account, Address, Block, Code, Predicate, PredicateTree},
-  anoma_vm::{execute_many, State, StateDiff},
+  anoma_vm::{
+    execute_many,
+    HistoryError,
+    MerkleState,
+    Rules,
+    State,
+    StateDiff,
+    StateTree,
+    StateView,
+    VersionedStateStore,
+  },
   multihash::{Multihash, MultihashDigest},
   std::{collections::VecDeque, num::NonZeroUsize},
   thiserror::Error,
@@ -15,17 +25,78 @@ pub enum Error {
   #[error("Invalid block parent {0:?}. Expected {1:?}")]
   InvalidBlockParent(Multihash, Multihash),
 
+  #[error("Block {0:?} is not signed by an authorized validator")]
+  UnauthorizedBlock(Multihash),
+
+  #[error("Block {0:?} changes the validator set configured in genesis")]
+  ValidatorSetChanged(Multihash),
+
+  #[error("Block transactions do not match the header transactions root")]
+  InvalidTransactionsRoot,
+
+  #[error("Block state root {0:?} does not match the chain state root {1:?}")]
+  InvalidStateRoot(Multihash, Multihash),
+
   #[error("Must be initialized with at least one existing block")]
   NoInitialBlocks,
+
+  #[error("Versioned state store is at height {0}, last block is at {1}")]
+  StoreHeightMismatch(u64, u64),
+
+  #[error("Historical state is not retained by this builder")]
+  HistoryNotRetained,
+
+  #[error("Historical state query failed: {0}")]
+  History(#[from] HistoryError),
+}
+
+/// The accounts store that receives state changes from consumed blocks.
+enum Storage<'s> {
+  Latest(&'s mut dyn MerkleState),
+  Versioned(&'s mut VersionedStateStore),
+}
+
+impl<'s> Storage<'s> {
+  fn state_tree(&self) -> StateTree {
+    match self {
+      Storage::Latest(state) => state.state_tree(),
+      Storage::Versioned(state) => state.state_tree(),
+    }
+  }
+}
+
+impl<'s> State for Storage<'s> {
+  fn get(&self, address: &Address) -> Option<Account> {
+    match self {
+      Storage::Latest(state) => state.get(address),
+      Storage::Versioned(state) => state.get(address),
+    }
+  }
+
+  fn descendants(&self, address: &Address) -> Vec<Address> {
+    match self {
+      Storage::Latest(state) => state.descendants(address),
+      Storage::Versioned(state) => state.descendants(address),
+    }
+  }
+
+  fn apply(&mut self, diff: StateDiff) {
+    match self {
+      Storage::Latest(state) => state.apply(diff),
+      Storage::Versioned(state) => state.apply(diff),
+    }
+  }
 }
 
 /// This type can be used to accumulate state changes from blocks produced
 /// by validators. It is useful everywhere where a node (solver, client, etc)
-/// need to maintain an up-to-date state of the chain and be able to query 
+/// need to maintain an up-to-date state of the chain and be able to query
 /// current accounts state.
 pub struct BlockStateBuilder<'s> {
   history_len: usize,
-  state: &'s mut dyn State,
+  rules: Rules,
+  state: Storage<'s>,
+  tree: StateTree,
   codecache: &'s mut dyn State,
   recent: VecDeque<Block>,
 }
@@ -35,11 +106,19 @@ impl<'s> State for BlockStateBuilder<'s> {
     self.state.get(address)
   }
 
-  fn apply(&mut self, _: StateDiff) {
-    unimplemented!(
-      "Direct state mutation is not allowed on this type. State mutation in \
-       BlockConsumer happens only by consuming blocks."
-    )
+  fn descendants(&self, address: &Address) -> Vec<Address> {
+    self.state.descendants(address)
+  }
+
+  /// Applies changes that do not come from consumed blocks directly to the
+  /// underlying state, such as genesis accounts or restored snapshots.
+  ///
+  /// Speculative changes should be applied to a [`StateView`] created
+  /// using [`BlockStateBuilder::view`] instead.
+  fn apply(&mut self, diff: StateDiff) {
+    self.codecache.apply(try_precompile_predicates(&diff));
+    self.tree.apply(&diff);
+    self.state.apply(diff);
   }
 }
 
@@ -47,7 +126,8 @@ impl<'s> BlockStateBuilder<'s> {
   #[allow(clippy::result_large_err)]
   pub fn new(
     history_len: NonZeroUsize,
-    state: &'s mut dyn State,
+    rules: Rules,
+    state: &'s mut dyn MerkleState,
     codecache: &'s mut dyn State,
     recent: impl Iterator<Item = Block>,
   ) -> Result<Self, Error> {
@@ -57,12 +137,65 @@ impl<'s> BlockStateBuilder<'s> {
       return Err(Error::NoInitialBlocks);
     }
 
-    Ok(Self {
+    Ok(Self::with_storage(
+      history_len,
+      rules,
+      Storage::Latest(state),
+      codecache,
+      recent,
+    ))
+  }
+
+  /// Creates a builder that records every consumed block as a new version
+  /// of accounts in a versioned store, so accounts can later be queried as
+  /// they were at past block heights using [`BlockStateBuilder::get_at`].
+  ///
+  /// The height of the store must match the height of the most recent block.
+  #[allow(clippy::result_large_err)]
+  pub fn versioned(
+    history_len: NonZeroUsize,
+    rules: Rules,
+    state: &'s mut VersionedStateStore,
+    codecache: &'s mut dyn State,
+    recent: impl Iterator<Item = Block>,
+  ) -> Result<Self, Error> {
+    let recent: VecDeque<_> = recent.collect();
+
+    let last = match recent.front() {
+      Some(block) => block.header.height,
+      None => return Err(Error::NoInitialBlocks),
+    };
+
+    if state.height() != last {
+      return Err(Error::StoreHeightMismatch(state.height(), last));
+    }
+
+    Ok(Self::with_storage(
+      history_len,
+      rules,
+      Storage::Versioned(state),
+      codecache,
+      recent,
+    ))
+  }
+
+  /// The state tree is built once here and then kept up to date with
+  /// every applied change, blocks are checked against its root.
+  fn with_storage(
+    history_len: NonZeroUsize,
+    rules: Rules,
+    state: Storage<'s>,
+    codecache: &'s mut dyn State,
+    recent: VecDeque<Block>,
+  ) -> Self {
+    Self {
       history_len: history_len.get(),
+      rules,
+      tree: state.state_tree(),
       state,
       codecache,
       recent,
-    })
+    }
   }
 
   pub fn last(&self) -> &Block {
@@ -76,17 +209,75 @@ impl<'s> BlockStateBuilder<'s> {
     self.recent.iter()
   }
 
+  /// Creates a copy-on-write view over the current chain state for
+  /// speculative execution of transactions.
+  pub fn view(&self) -> StateView<'_> {
+    StateView::new(&self.state)
+  }
+
+  /// Retreive an account as it was after applying the block at
+  /// a given height.
+  ///
+  /// Past heights are available only if the builder was created
+  /// using [`BlockStateBuilder::versioned`] and the height is still
+  /// within the retention window of the versioned store.
+  #[allow(clippy::result_large_err)]
+  pub fn get_at(
+    &self,
+    address: &Address,
+    height: u64,
+  ) -> Result<Option<Account>, Error> {
+    match &self.state {
+      Storage::Versioned(state) => Ok(state.get_at(address, height)?),
+      Storage::Latest(state) => {
+        if height == self.last().header.height {
+          Ok(state.get(address))
+        } else {
+          Err(Error::HistoryNotRetained)
+        }
+      }
+    }
+  }
+
+  /// Validates a block on top of the most recent block, executes all its
+  /// transactions under the rules of the chain and applies their changes
+  /// to the underlying state.
+  ///
+  /// Blocks must be signed by one of the validators that are configured
+  /// in the genesis block, and must carry the root of the current state
+  /// that they are applied to.
+  ///
+  /// Returns all account changes produced by the block.
   #[allow(clippy::result_large_err)]
-  pub fn consume(&mut self, block: Block) -> Result<(), Error> {
-    let prev_height = self.last().height;
+  pub fn consume(&mut self, block: Block) -> Result<StateDiff, Error> {
+    let prev_height = self.last().header.height;
     let prev_hash = *self.last().hash();
+    let header = &block.header;
 
-    if prev_hash != block.parent {
-      return Err(Error::InvalidBlockParent(block.parent, prev_hash));
+    if prev_hash != header.parent {
+      return Err(Error::InvalidBlockParent(header.parent, prev_hash));
     }
 
-    if prev_height + 1 != block.height {
-      return Err(Error::InvalidBlockHeight(block.height, prev_height + 1));
+    if prev_height + 1 != header.height {
+      return Err(Error::InvalidBlockHeight(header.height, prev_height + 1));
+    }
+
+    let validators = &self.last().header.validators;
+    if !validators.contains(&header.proposer) || !block.verify_signature() {
+      return Err(Error::UnauthorizedBlock(*block.hash()));
+    }
+
+    if validators != &header.validators {
+      return Err(Error::ValidatorSetChanged(*block.hash()));
+    }
+
+    if !block.verify_body() {
+      return Err(Error::InvalidTransactionsRoot);
+    }
+
+    let state_root = self.tree.root();
+    if header.state_root != state_root {
+      return Err(Error::InvalidStateRoot(header.state_root, state_root));
     }
 
     self.recent.push_front(block.clone());
@@ -94,10 +285,13 @@ impl<'s> BlockStateBuilder<'s> {
       self.recent.pop_back();
     }
 
+    let height = block.header.height;
     let results = execute_many(
-      self.state, //
+      height,
+      &self.rules,
+      &self.state,
       self.codecache,
-      block.transactions.into_iter(),
+      block.body.transactions.into_iter(),
     );
 
     let statediff = results
@@ -107,8 +301,12 @@ impl<'s> BlockStateBuilder<'s> {
       .unwrap_or_default();
 
     self.codecache.apply(try_precompile_predicates(&statediff));
-    self.state.apply(statediff);
-    Ok(())
+    match &mut self.state {
+      Storage::Latest(state) => state.apply(statediff.clone()),
+      Storage::Versioned(state) => state.commit(height, statediff.clone())?,
+    }
+    self.tree.apply(&statediff);
+    Ok(statediff)
   }
 }
 
@@ -145,3 +343,57 @@ fn try_precompile_predicates(diff: &StateDiff) -> StateDiff {
   }
   output
 }
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{BlockStateBuilder, Error},
+    anoma_primitives::{Account, Block, Code, Predicate, PredicateTree},
+    anoma_vm::{InMemoryStateStore, Rules, State, StateDiff, StateTree},
+    ed25519_dalek::{Keypair, PublicKey, SecretKey},
+    std::num::NonZeroUsize,
+  };
+
+  #[test]
+  fn rejects_invalid_state_root() {
+    let secret = SecretKey::from_bytes(&[7; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+    let validator = Keypair { secret, public };
+    let genesis = Block::genesis(vec![validator.public]);
+
+    let mut state = InMemoryStateStore::default();
+    let mut codecache = InMemoryStateStore::default();
+    let mut builder = BlockStateBuilder::new(
+      NonZeroUsize::new(8).unwrap(),
+      Rules::new("testnet"),
+      &mut state,
+      &mut codecache,
+      std::iter::once(genesis.clone()),
+    )
+    .unwrap();
+
+    let mut accounts = StateDiff::default();
+    accounts.set("/token".parse().unwrap(), Account {
+      state: vec![1],
+      predicates: PredicateTree::Id(Predicate {
+        code: Code::Inline(vec![]),
+        params: vec![],
+      }),
+    });
+    builder.apply(accounts.clone());
+
+    // the root of the state before the accounts were added
+    let stale = genesis.header.state_root;
+    let block = Block::new(&genesis.header, 1, stale, &validator, vec![]);
+    assert!(matches!(
+      builder.consume(block),
+      Err(Error::InvalidStateRoot(..))
+    ));
+
+    let mut expected = InMemoryStateStore::default();
+    expected.apply(accounts);
+    let root = StateTree::new(expected.iter()).root();
+    let block = Block::new(&genesis.header, 1, root, &validator, vec![]);
+    assert!(builder.consume(block).is_ok());
+  }
+}
@@ -4,5 +4,13 @@ mod watcher;
 pub use {
   builder::{BlockStateBuilder, Error as BlockStateBuilderError},
   watcher::BlockchainWatcher,
-  anoma_vm::{State, StateDiff, InMemoryStateStore}, 
+  anoma_vm::{
+    State,
+    StateDiff,
+    StateView,
+    InMemoryStateStore,
+    Pruning,
+    Rules,
+    VersionedStateStore,
+  },
 };
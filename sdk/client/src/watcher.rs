@@ -1,7 +1,7 @@
 use {
   crate::{builder, BlockStateBuilder},
   anoma_primitives::{Account, Address, Block, Transaction},
-  anoma_vm::State,
+  anoma_vm::{MerkleState, Rules, State},
   dashmap::DashMap,
   futures::{Stream, StreamExt},
   multihash::Multihash,
@@ -38,20 +38,29 @@ impl BlockchainWatcher {
   #[allow(clippy::result_large_err)]
   pub fn new(
     history_len: NonZeroUsize,
-    state: &'static mut dyn State,
+    rules: Rules,
+    state: &'static mut dyn MerkleState,
     codecache: &'static mut dyn State,
     recent: impl Iterator<Item = Block>,
     stream: impl Stream<Item = Block> + Unpin + Send + 'static,
   ) -> Result<Self, builder::Error> {
+    Ok(Self::with_builder(
+      BlockStateBuilder::new(history_len, rules, state, codecache, recent)?,
+      stream,
+    ))
+  }
+
+  /// Creates a watcher on top of an existing state builder, for example
+  /// one created using [`BlockStateBuilder::versioned`] that allows
+  /// querying accounts at past block heights.
+  pub fn with_builder(
+    state_builder: BlockStateBuilder<'static>,
+    stream: impl Stream<Item = Block> + Unpin + Send + 'static,
+  ) -> Self {
     let watchlist =
       Arc::new(DashMap::<WatchlistKey, Sender<WatchlistValue>>::new());
 
-    let state_builder = Arc::new(RwLock::new(BlockStateBuilder::new(
-      history_len,
-      state,
-      codecache,
-      recent,
-    )?));
+    let state_builder = Arc::new(RwLock::new(state_builder));
 
     let watchlist_clone = watchlist.clone();
     let state_builder_clone = state_builder.clone();
@@ -61,7 +70,14 @@ impl BlockchainWatcher {
       let watchlist = watchlist_clone;
       let state_builder = state_builder_clone;
       while let Some(block) = stream.next().await {
-        for tx in block.transactions.iter() {
+        // only blocks that are signed by an authorized validator and
+        // apply cleanly on top of the current state resolve awaiters.
+        if let Err(e) = state_builder.write().await.consume(block.clone()) {
+          error!("block rejected: {e:?}");
+          continue;
+        }
+
+        for tx in block.body.transactions.iter() {
           let txwatchkey = WatchlistKey::Transaction(*tx.hash());
           if let Some((_, signal)) = watchlist.remove(&txwatchkey) {
             if signal
@@ -103,23 +119,28 @@ impl BlockchainWatcher {
             }
           }
         }
-
-        if let Err(e) = state_builder.write().await.consume(block) {
-          error!("block rejected: {e:?}");
-        }
       }
     });
 
-    Ok(Self {
+    Self {
       watchlist,
       state_builder,
-    })
+    }
   }
 
   pub async fn get(&self, address: &Address) -> Option<Account> {
     self.state_builder.read().await.get(address)
   }
 
+  #[allow(clippy::result_large_err)]
+  pub async fn get_at(
+    &self,
+    address: &Address,
+    height: u64,
+  ) -> Result<Option<Account>, builder::Error> {
+    self.state_builder.read().await.get_at(address, height)
+  }
+
   pub async fn most_recent_block(&self) -> Block {
     self.state_builder.read().await.last().clone()
   }
@@ -7,12 +7,16 @@ use {
     Keypair,
     Network,
   },
-  anoma_primitives::Block,
-  anoma_vm::InMemoryStateStore,
+  anoma_primitives::{encoding, Block, Transaction},
+  anoma_vm::{snapshot, InMemoryStateStore, StateTree},
   clap::Parser,
+  ed25519_dalek::{PublicKey, SecretKey, SECRET_KEY_LENGTH},
   futures::StreamExt,
-  rmp_serde::{from_slice, to_vec},
-  std::num::NonZeroUsize,
+  std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Write},
+    num::NonZeroUsize,
+  },
   tokio::time::{interval, MissedTickBehavior},
   tracing::{info, warn},
 };
@@ -44,6 +48,44 @@ fn start_network(settings: &SystemSettings) -> anyhow::Result<(Topic, Topic)> {
   Ok((txs_topic, blocks_topic))
 }
 
+// loads the block signing key or creates a new one
+fn validator_key(
+  settings: &SystemSettings,
+) -> anyhow::Result<ed25519_dalek::Keypair> {
+  let path = match settings.validator_key() {
+    Some(path) => path,
+    None => {
+      return Ok(ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng))
+    }
+  };
+
+  if path.exists() {
+    let bytes = std::fs::read(&path)?;
+    if bytes.len() != SECRET_KEY_LENGTH {
+      anyhow::bail!(
+        "validator key file {} must contain a {SECRET_KEY_LENGTH} bytes \
+         ed25519 secret key, found {} bytes",
+        path.display(),
+        bytes.len()
+      );
+    }
+    let secret = SecretKey::from_bytes(&bytes)?;
+    let public = PublicKey::from(&secret);
+    Ok(ed25519_dalek::Keypair { secret, public })
+  } else {
+    let keypair = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng);
+
+    // the secret key must not be readable by other users
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path)?.write_all(keypair.secret.as_bytes())?;
+
+    Ok(keypair)
+  }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
   tracing_subscriber::fmt::init();
@@ -60,35 +102,115 @@ async fn main() -> anyhow::Result<()> {
   interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
   let history_length = unsafe { NonZeroUsize::new_unchecked(64) };
+  let proposer = validator_key(&settings)?;
+  info!(
+    "producing blocks as validator {}",
+    bs58::encode(proposer.public.as_bytes()).into_string()
+  );
+
   let mut code_cache = InMemoryStateStore::default();
   let mut state_store = InMemoryStateStore::default();
-  let mut mempool = Mempool::new(BlockStateBuilder::new(
-    history_length,
-    &mut state_store,
-    &mut code_cache,
-    std::iter::once(Block::zero()),
-  )?);
+
+  // either start from an empty genesis or from a known chain state
+  let initial_block = match settings.snapshot() {
+    Some(path) => {
+      let reader = BufReader::new(File::open(path)?);
+      // the next block produced on top of the snapshot
+      // commits to the verified root of the imported state.
+      let (block, state_root) = snapshot::import(reader, &mut state_store)?;
+      let state_root = bs58::encode(&state_root.to_bytes()).into_string();
+      if let Some(trusted) = settings.snapshot_root() {
+        if state_root != trusted {
+          anyhow::bail!(
+            "snapshot state root {state_root} does not match the trusted root \
+             {trusted}"
+          );
+        }
+      }
+      info!(
+        "imported snapshot of {} accounts at block {} (#{}) with state root {}",
+        state_store.iter().count(),
+        bs58::encode(&block.hash().to_bytes()).into_string(),
+        block.header.height,
+        state_root
+      );
+      block
+    }
+    None => Block::genesis(vec![proposer.public]),
+  };
+
+  if !initial_block.header.validators.contains(&proposer.public) {
+    anyhow::bail!("validator key is not in the validator set of the chain");
+  }
+
+  let state_snapshot = state_store.snapshot();
+
+  let mut mempool = Mempool::new(
+    BlockStateBuilder::new(
+      history_length,
+      settings.rules(),
+      &mut state_store,
+      &mut code_cache,
+      std::iter::once(initial_block),
+    )?,
+    state_snapshot,
+    proposer,
+  );
 
   loop {
     tokio::select! {
       Some(tx) = txs_topic.next() => {
-        if let Ok(tx) = from_slice(&tx) {
-          mempool.consume(tx);
+        if let Ok(tx) = encoding::decode::<Transaction>(&tx) {
+          // intents signed for other networks are rejected during
+          // execution, don't let them take up space in blocks.
+          if tx.intents.iter().all(|i| i.chain_id == settings.network_id()) {
+            let hash = *tx.hash();
+            if let Err(reason) = mempool.consume(tx) {
+              warn!("rejected transaction {}: {reason}",
+                bs58::encode(&hash.to_bytes()).into_string());
+            }
+          } else {
+            warn!("rejected transaction {} for another chain",
+              bs58::encode(&tx.hash().to_bytes()).into_string());
+          }
         }
       }
       _ = interval.tick() => {
         let block = mempool.produce();
         info!("produced block {} (#{}) on top of {} with {} transactions.",
           bs58::encode(&block.hash().to_bytes()).into_string(),
-          bs58::encode(&block.parent.to_bytes()).into_string(),
-          block.height,
-          block.transactions.len());
+          bs58::encode(&block.header.parent.to_bytes()).into_string(),
+          block.header.height,
+          block.body.transactions.len());
 
         // broadcast through p2p to all other nodes
-        if let Err(e) = blocks_topic.gossip(to_vec(&block)?) {
+        if let Err(e) = blocks_topic.gossip(encoding::encode(&block)) {
           warn!("failed to gossip block: {e:?}");
         }
       }
+      _ = tokio::signal::ctrl_c() => {
+        info!("shutting down");
+        break;
+      }
     }
   }
+
+  if let Some(path) = settings.dump_snapshot() {
+    let last = mempool.last().clone();
+    drop(mempool); // release the state store
+
+    let writer = BufWriter::new(File::create(path)?);
+    let state_root = StateTree::new(state_store.iter()).root();
+    let summary =
+      snapshot::export(&last, state_root, state_store.iter(), writer)?;
+    info!(
+      "wrote snapshot of {} accounts at block #{} with state root {} to {}",
+      summary.accounts,
+      last.header.height,
+      bs58::encode(&state_root.to_bytes()).into_string(),
+      path.display()
+    );
+  }
+
+  Ok(())
 }
@@ -1,33 +1,100 @@
 use {
   anoma_client_sdk::BlockStateBuilder,
-  anoma_primitives::{Block, Transaction},
+  anoma_primitives::{Block, Limits, Transaction, ValidationError},
+  anoma_vm::{InMemoryStateStore, State, StateTree},
+  ed25519_dalek::Keypair,
+  std::{
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+  },
 };
 
+/// Reasons for not accepting a transaction into the mempool.
+#[derive(Debug)]
+pub enum Rejection {
+  /// The transaction is malformed.
+  Invalid(ValidationError),
+
+  /// The transaction has intents that expired before the next block.
+  Expired,
+}
+
+impl Display for Rejection {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Rejection::Invalid(e) => write!(f, "invalid transaction: {e}"),
+      Rejection::Expired => write!(f, "expired intents"),
+    }
+  }
+}
+
 pub struct Mempool<'s> {
   txs: Vec<Transaction>,
   blocks: BlockStateBuilder<'s>,
+  proposer: Keypair,
+
+  // snapshot of the chain state that is kept in sync with
+  // the block consumer and used to compute state roots.
+  state: InMemoryStateStore,
 }
 
 impl<'s> Mempool<'s> {
-  pub fn new(block_consumer: BlockStateBuilder<'s>) -> Self {
+  pub fn new(
+    block_consumer: BlockStateBuilder<'s>,
+    state: InMemoryStateStore,
+    proposer: Keypair,
+  ) -> Self {
     Self {
       txs: vec![],
       blocks: block_consumer,
+      proposer,
+      state,
     }
   }
 
-  pub fn consume(&mut self, tx: Transaction) {
+  pub fn last(&self) -> &Block {
+    self.blocks.last()
+  }
+
+  /// Queues a transaction for inclusion in one of the next blocks.
+  ///
+  /// Malformed transactions and transactions with intents that
+  /// expired before the next block are not queued.
+  pub fn consume(&mut self, tx: Transaction) -> Result<(), Rejection> {
+    tx.validate(&Limits::default())
+      .map_err(Rejection::Invalid)?;
+
+    let height = self.blocks.last().header.height + 1;
+    if tx.intents.iter().any(|i| i.is_expired_at(height)) {
+      return Err(Rejection::Expired);
+    }
     self.txs.push(tx);
+    Ok(())
   }
 
   pub fn produce(&mut self) -> Block {
-    let txs = std::mem::take(&mut self.txs);
-    let parent = self.blocks.last();
-    let block = Block::new(parent, txs);
-    self
+    // transactions with intents that are not valid yet wait
+    // for later blocks, those that expired in the meantime
+    // are dropped.
+    let height = self.blocks.last().header.height + 1;
+    let (txs, pending) = std::mem::take(&mut self.txs)
+      .into_iter()
+      .filter(|tx| !tx.intents.iter().any(|i| i.is_expired_at(height)))
+      .partition(|tx| tx.intents.iter().all(|i| i.is_valid_at(height)));
+    self.txs = pending;
+
+    let parent = &self.blocks.last().header;
+    let timestamp = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .expect("system clock set after unix epoch")
+      .as_millis() as u64;
+    let state_root = StateTree::new(self.state.iter()).root();
+    let block = Block::new(parent, timestamp, state_root, &self.proposer, txs);
+    let diff = self
       .blocks
       .consume(block.clone())
-      .expect("height and parent verified here");
+      .expect("produced on top of the last block by a known validator");
+    self.state.apply(diff);
 
     block
   }
@@ -1,10 +1,12 @@
 use {
   anoma_network::multiaddr::{Protocol, Multiaddr},
+  anoma_vm::Rules,
   clap::Parser,
   humantime::Duration,
   std::{
     net::SocketAddr,
-    net::{IpAddr, Ipv4Addr, Ipv6Addr}
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
   },
 };
 
@@ -45,6 +47,30 @@ pub struct SystemSettings {
     value_name = "DURATION",
     default_value = "2s")]
   block_time: Duration,
+
+  /// Enforce account hierarchy rules, all nodes of a chain must
+  /// agree on this setting
+  #[clap(long)]
+  hierarchy: bool,
+
+  /// Start the chain from a state snapshot instead of an empty genesis
+  #[clap(long, value_name = "PATH")]
+  snapshot: Option<PathBuf>,
+
+  /// Base58 state root published by a trusted source that the snapshot
+  /// state must match, otherwise the snapshot is not imported
+  #[clap(long, value_name = "ROOT", requires = "snapshot")]
+  snapshot_root: Option<String>,
+
+  /// Write a snapshot of the chain state to this file on shutdown
+  #[clap(long, value_name = "PATH")]
+  dump_snapshot: Option<PathBuf>,
+
+  /// File with the secret key used to sign produced blocks. A new key
+  /// is generated and written to this file if it does not exist.
+  /// Without this option an ephemeral key is used.
+  #[clap(long, value_name = "PATH")]
+  validator_key: Option<PathBuf>,
 }
 
 impl SystemSettings {
@@ -79,4 +105,27 @@ impl SystemSettings {
   pub fn block_time(&self) -> std::time::Duration {
     self.block_time.into()
   }
+
+  pub fn rules(&self) -> Rules {
+    Rules {
+      chain_id: self.network_id.clone(),
+      hierarchy: self.hierarchy,
+    }
+  }
+
+  pub fn snapshot(&self) -> Option<&Path> {
+    self.snapshot.as_deref()
+  }
+
+  pub fn snapshot_root(&self) -> Option<&str> {
+    self.snapshot_root.as_deref()
+  }
+
+  pub fn dump_snapshot(&self) -> Option<&Path> {
+    self.dump_snapshot.as_deref()
+  }
+
+  pub fn validator_key(&self) -> Option<&Path> {
+    self.validator_key.as_deref()
+  }
 }
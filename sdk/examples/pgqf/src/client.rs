@@ -1,15 +1,22 @@
 use {
   crate::settings::SystemSettings,
-  anoma_client_sdk::{BlockchainWatcher, InMemoryStateStore},
+  anoma_client_sdk::{BlockchainWatcher, InMemoryStateStore, Rules},
   anoma_network as network,
-  anoma_predicates_sdk::{Address, Predicate},
+  anoma_predicates_sdk::Address,
   anoma_primitives::{
+    all,
+    builder::{
+      code,
+      inline,
+      inline_bytes,
+      predicate,
+      proposal,
+      stdpred,
+      IntentBuilder,
+    },
+    encoding,
     Block,
-    Code,
-    Exact,
     Intent,
-    Param,
-    PredicateTree,
     Transaction,
   },
   clap::Parser,
@@ -22,7 +29,6 @@ use {
     Network,
   },
   rand::{seq::SliceRandom, Rng},
-  rmp_serde::{from_slice, to_vec},
   std::{
     collections::HashMap,
     future::ready,
@@ -67,7 +73,7 @@ async fn main() -> anyhow::Result<()> {
 
   let (blocks, intents) = start_network(&opts)?;
   let mut blocks = blocks
-    .filter_map(|bytes| ready(from_slice::<Block>(&bytes).ok()))
+    .filter_map(|bytes| ready(encoding::decode::<Block>(&bytes).ok()))
     .boxed();
 
   // Wait for some block on p2p to base our state off it
@@ -76,13 +82,14 @@ async fn main() -> anyhow::Result<()> {
 
   // the funding campaign will be open to public
   // donations in 10 blocks and will last for 100 blocks.
-  let campaign_start = recent_block.height + 10;
+  let campaign_start = recent_block.header.height + 10;
   let campaign_end = campaign_start + 100;
   info!("Campain lifetime [{campaign_start}, {campaign_end}]");
 
   #[allow(clippy::box_default)]
   let mut watcher = BlockchainWatcher::new(
     NonZeroUsize::new(64).unwrap(),
+    Rules::new(opts.network_id()),
     Box::leak(Box::new(InMemoryStateStore::default())),
     Box::leak(Box::new(InMemoryStateStore::default())),
     std::iter::once(recent_block),
@@ -92,6 +99,7 @@ async fn main() -> anyhow::Result<()> {
   // first create a campaign:
   let tx = send_and_confirm_intents(
     std::iter::once(create_campaign_intent(
+      opts.network_id(),
       *watcher.most_recent_block().await.hash(),
       campaign_start,
       campaign_end,
@@ -105,6 +113,7 @@ async fn main() -> anyhow::Result<()> {
   // then add one project
   let tx = send_and_confirm_intents(
     create_project_intents(
+      opts.network_id(),
       "project1",
       *watcher.most_recent_block().await.hash(),
     ),
@@ -118,14 +127,17 @@ async fn main() -> anyhow::Result<()> {
   let tx = send_and_confirm_intents(
     [
       create_project_intents(
+        opts.network_id(),
         "project2",
         *watcher.most_recent_block().await.hash(),
       ),
       create_project_intents(
+        opts.network_id(),
         "project3",
         *watcher.most_recent_block().await.hash(),
       ),
       create_project_intents(
+        opts.network_id(),
         "project4",
         *watcher.most_recent_block().await.hash(),
       ),
@@ -144,6 +156,7 @@ async fn main() -> anyhow::Result<()> {
   // first donation to the matching pool
   let tx = send_and_confirm_intents(
     create_matching_pool_donation_intents(
+      opts.network_id(),
       1200,
       *watcher.most_recent_block().await.hash(),
     ),
@@ -158,10 +171,12 @@ async fn main() -> anyhow::Result<()> {
   let tx = send_and_confirm_intents(
     [
       create_matching_pool_donation_intents(
+        opts.network_id(),
         1800,
         *watcher.most_recent_block().await.hash(),
       ),
       create_matching_pool_donation_intents(
+        opts.network_id(),
         1200,
         *watcher.most_recent_block().await.hash(),
       ),
@@ -193,11 +208,12 @@ async fn main() -> anyhow::Result<()> {
     let project = *projects.choose(&mut rand::thread_rng()).unwrap();
     let amount = rand::thread_rng().gen_range(100..10000);
     let donation_intent = create_project_donation_intent(
+      opts.network_id(),
       project,
       amount,
       *watcher.most_recent_block().await.hash(),
     );
-    if intents.gossip(to_vec(&donation_intent).unwrap()).is_ok() {
+    if intents.gossip(encoding::encode(&donation_intent)).is_ok() {
       info!("Donated {amount} to {project}");
       donations_intents.push(*donation_intent.hash());
 
@@ -231,6 +247,7 @@ async fn main() -> anyhow::Result<()> {
   // funds to projects.
   let tx = send_and_confirm_intents(
     std::iter::once(create_funding_redistribution_intent(
+      opts.network_id(),
       matching_pool_amount,
       donation_amounts,
       *watcher.most_recent_block().await.hash(),
@@ -260,7 +277,7 @@ async fn send_and_confirm_intents<'s>(
   for intent in intents {
     loop {
       info!("sending intent {intent:?}..");
-      match intents_topic.gossip(to_vec(&intent)?) {
+      match intents_topic.gossip(encoding::encode(&intent)) {
         Ok(_) => {
           info!("done");
           break;
@@ -286,67 +303,61 @@ async fn send_and_confirm_intents<'s>(
 }
 
 fn create_campaign_intent(
+  chain_id: &str,
   blockhash: Multihash,
   start_height: u64,
   end_height: u64,
 ) -> anyhow::Result<Intent> {
-  Ok(Intent::new(
-    blockhash,
-    PredicateTree::And(
-      Box::new(PredicateTree::Id(Predicate {
-        code: Code::AccountRef("/stdpred/v1".parse()?, "state_equal".into()),
-        params: vec![
-          Param::Inline(b"serialized-value-of-campaign-account-state".to_vec()),
-          Param::ProposalRef("/pgqf/spring-2023".parse()?),
-        ],
-      })),
-      Box::new(PredicateTree::Id(Predicate {
-        code: Code::AccountRef(
-          "/stdpred/v1".parse()?,
-          "predicates_equal".into(),
-        ),
-        params: vec![
-          Param::Inline(to_vec(&PredicateTree::<Exact>::Id(Predicate {
-            code: Code::AccountRef("/pgqf".parse()?, "predicate".into()),
-            params: vec![
-              Param::Inline(to_vec(&start_height)?),
-              Param::Inline(to_vec(&end_height)?),
-              Param::Inline(to_vec(&Address::new(
-                "/token/usdx/spring-2023.eth",
-              )?)?),
-              Param::Inline(to_vec(&Vec::<String>::default())?),
-            ],
-          }))?),
-          Param::ProposalRef("/pgqf/spring-2023".parse()?),
-        ],
-      })),
-    ),
-  ))
+  let campaign: Address = "/pgqf/spring-2023".parse()?;
+  let campaign_predicate = predicate(code("/pgqf", "predicate"), [
+    inline(start_height),
+    inline(end_height),
+    inline(Address::new("/token/usdx/spring-2023.eth")?),
+    inline(Vec::<String>::default()),
+  ]);
+
+  Ok(
+    IntentBuilder::new(chain_id, blockhash, all![
+      predicate(code(stdpred::ADDRESS, "state_equal"), [
+        inline_bytes(&b"serialized-value-of-campaign-account-state"[..]),
+        proposal(&campaign),
+      ]),
+      predicate(code(stdpred::ADDRESS, "predicates_equal"), [
+        inline(campaign_predicate),
+        proposal(&campaign),
+      ]),
+    ])
+    .build(),
+  )
 }
 
 fn create_project_intents(
+  chain_id: &str,
   _name: &str,
   blockhash: Multihash,
 ) -> impl Iterator<Item = Intent> {
-  std::iter::once(Intent::new(blockhash, todo!()))
+  std::iter::once(Intent::new(chain_id.into(), blockhash, todo!()))
 }
 
 fn create_matching_pool_donation_intents(
+  chain_id: &str,
   _amount: u64,
   blockhash: Multihash,
 ) -> impl Iterator<Item = Intent> {
-  std::iter::once(Intent::new(blockhash, todo!()))
+  std::iter::once(Intent::new(chain_id.into(), blockhash, todo!()))
 }
 
 fn create_project_donation_intent(
+  chain_id: &str,
   _name: &str,
   _amount: u64,
   blockhash: Multihash,
 ) -> Intent {
-  Intent::new(blockhash, todo!())
+  Intent::new(chain_id.into(), blockhash, todo!())
 }
 
 fn create_funding_redistribution_intent(
+  chain_id: &str,
   matching_pool_amount: u64,
   donation_amounts: HashMap<&str, u64>,
   blockhash: Multihash,
@@ -1,7 +1,7 @@
 use {
   crate::settings::SystemSettings,
   anoma_network as network,
-  anoma_primitives::{Block, Intent},
+  anoma_primitives::{encoding, Block, Intent},
   clap::Parser,
   futures::StreamExt,
   network::{
@@ -10,7 +10,6 @@ use {
     Keypair,
     Network,
   },
-  rmp_serde::from_slice,
   tracing::info,
 };
 
@@ -56,16 +55,41 @@ async fn main() -> anyhow::Result<()> {
 
   let (_, blocks, intents) = start_network(&opts)?;
 
-  let mut blocks = blocks.map(|bytes| from_slice::<Block>(&bytes));
-  let mut intents = intents.map(|bytes| from_slice::<Intent>(&bytes));
+  let mut blocks = blocks.map(|bytes| encoding::decode::<Block>(&bytes));
+  let mut intents = intents.map(|bytes| encoding::decode::<Intent>(&bytes));
+
+  // intents that are waiting to be solved
+  let mut pool: Vec<Intent> = vec![];
 
   loop {
     tokio::select! {
       Some(Ok(intent)) = intents.next() => {
         info!("received an intent: {intent:?}");
+        if let Some(tip) = &intent.terms.tip {
+          info!("intent offers a tip of {} {}", tip.amount, tip.token);
+        }
+
+        // declared trades are matched without simulating the intents
+        if let Some(trade) = &intent.terms.trade {
+          let matching = pool.iter().filter(|other| {
+            other.terms.trade.as_ref().map_or(false, |t| t.matches(trade))
+          });
+          for other in matching {
+            info!(
+              "intent {} matches the trade of {}",
+              bs58::encode(intent.hash().to_bytes()).into_string(),
+              bs58::encode(other.hash().to_bytes()).into_string()
+            );
+          }
+        }
+        pool.push(intent);
       }
       Some(Ok(block)) = blocks.next() => {
         info!("received a block: {block:#?}");
+
+        // intents that expire before the next block can't be solved anymore
+        let height = block.header.height + 1;
+        pool.retain(|intent| !intent.is_expired_at(height));
       }
     }
   }
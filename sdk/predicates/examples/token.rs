@@ -2,10 +2,9 @@
 //!
 //! A token consists of:
 //!   1. one top-level account that governs the token behaviour
-//!   2. many sub-accounts of the top-level account that contain
-//!      balances of individual wallets. Wallet balance accounts
-//!      also are responsible for the spending authorization logic
-//!      of those accounts tokens.
+//!   2. many sub-accounts of the top-level account that contain balances of
+//!      individual wallets. Wallet balance accounts also are responsible for
+//!      the spending authorization logic of those accounts tokens.
 //!
 //! If we were to build a USDX token then the logic would look as following:
 //!
@@ -91,7 +90,7 @@ use {
     ExpandedParam,
     PredicateContext,
   },
-  ed25519_dalek::{PublicKey, Signature, Verifier},
+  ed25519_dalek::PublicKey,
 };
 
 // those can be structs as the contract grows
@@ -108,13 +107,17 @@ fn predicate(params: &Vec<ExpandedParam>, context: &PredicateContext) -> bool {
 
   let mut argit = params.iter();
 
-  let self_addr: Address =
-    rmp_serde::from_slice(argit.next().expect("asserted").data())
-      .expect("invalid self address param format");
+  let self_addr: Address = argit
+    .next()
+    .expect("asserted")
+    .decode()
+    .expect("invalid self address param format");
 
-  let mint_auth: PublicKey =
-    rmp_serde::from_slice(argit.next().expect("asserted").data())
-      .expect("invalid public key param");
+  let mint_auth: PublicKey = argit
+    .next()
+    .expect("asserted")
+    .decode()
+    .expect("invalid public key param");
 
   let current_total_supply = match argit.next().expect("asserted") {
     ExpandedParam::AccountRef(addr, state) => {
@@ -168,7 +171,8 @@ fn predicate(params: &Vec<ExpandedParam>, context: &PredicateContext) -> bool {
 
         // verify that the new total supply is equal to the increase in
         // balances sum in this transaction
-        ExpandedAccountChange::ReplaceState { proposed, .. } => {
+        ExpandedAccountChange::ReplaceState { proposed, .. }
+        | ExpandedAccountChange::PatchState { proposed, .. } => {
           read_total_supply(proposed)
         }
 
@@ -203,7 +207,8 @@ fn predicate(params: &Vec<ExpandedParam>, context: &PredicateContext) -> bool {
         }
         // verify that the new total supply is equal to the increase in
         // balances sum in this transaction
-        ExpandedAccountChange::ReplaceState { proposed, .. } => {
+        ExpandedAccountChange::ReplaceState { proposed, .. }
+        | ExpandedAccountChange::PatchState { proposed, .. } => {
           let proposed_supply = read_total_supply(proposed);
           if proposed_supply != expected_new_supply {
             log!(
@@ -258,7 +263,8 @@ fn sum_balances(
     .fold(0, |acc, (_, change)| {
       acc
         + (match change {
-          ExpandedAccountChange::ReplaceState { current, .. } => {
+          ExpandedAccountChange::ReplaceState { current, .. }
+          | ExpandedAccountChange::PatchState { current, .. } => {
             balance(current)
           }
           ExpandedAccountChange::DeleteAccount { current } => {
@@ -276,7 +282,8 @@ fn sum_balances(
       acc
         + (match change {
           ExpandedAccountChange::CreateAccount(acc) => balance(&acc.state),
-          ExpandedAccountChange::ReplaceState { proposed, .. } => {
+          ExpandedAccountChange::ReplaceState { proposed, .. }
+          | ExpandedAccountChange::PatchState { proposed, .. } => {
             balance(proposed)
           }
           _ => 0,
@@ -291,17 +298,7 @@ fn is_signed_by_mint_auth(
   mint_auth: &PublicKey,
   context: &PredicateContext,
 ) -> bool {
-  let calldata_key = bs58::encode(mint_auth.as_bytes()).into_string();
-  for (hash, calldata) in &context.calldata {
-    if let Some(signature) = calldata.get(&calldata_key) {
-      if let Ok(signature) = Signature::from_bytes(signature) {
-        if mint_auth.verify(&hash.to_bytes(), &signature).is_ok() {
-          return true;
-        }
-      }
-    }
-  }
-  false
+  context.is_signed_by(mint_auth)
 }
 
 fn read_total_supply(state: &[u8]) -> TokenSupply {
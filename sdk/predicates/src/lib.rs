@@ -13,8 +13,14 @@ pub use {
     Expanded,
     ExpandedAccountChange,
     ExpandedParam,
+    ParamError,
+    PatchError,
     Predicate,
     PredicateContext,
+    StatePatch,
+    Tip,
+    Trade,
+    TypedParam,
   },
 };
 
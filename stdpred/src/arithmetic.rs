@@ -10,8 +10,8 @@ fn uint_equal(params: &Vec<ExpandedParam>, _: &PredicateContext) -> bool {
   assert_eq!(params.len(), 2);
 
   let mut it = params.iter();
-  let first = slice_to_u64(it.next().expect("asserted").data());
-  let second = slice_to_u64(it.next().expect("asserted").data());
+  let first = uint(it.next().expect("asserted"));
+  let second = uint(it.next().expect("asserted"));
 
   first == second
 }
@@ -26,8 +26,8 @@ fn uint_greater_than(
   assert_eq!(params.len(), 2);
 
   let mut it = params.iter();
-  let first = slice_to_u64(it.next().expect("asserted").data());
-  let second = slice_to_u64(it.next().expect("asserted").data());
+  let first = uint(it.next().expect("asserted"));
+  let second = uint(it.next().expect("asserted"));
 
   first > second
 }
@@ -42,8 +42,8 @@ fn uint_greater_than_equal(
   assert_eq!(params.len(), 2);
 
   let mut it = params.iter();
-  let first = slice_to_u64(it.next().expect("asserted").data());
-  let second = slice_to_u64(it.next().expect("asserted").data());
+  let first = uint(it.next().expect("asserted"));
+  let second = uint(it.next().expect("asserted"));
 
   first >= second
 }
@@ -55,8 +55,8 @@ fn uint_less_than(params: &Vec<ExpandedParam>, _: &PredicateContext) -> bool {
   assert_eq!(params.len(), 2);
 
   let mut it = params.iter();
-  let first = slice_to_u64(it.next().expect("asserted").data());
-  let second = slice_to_u64(it.next().expect("asserted").data());
+  let first = uint(it.next().expect("asserted"));
+  let second = uint(it.next().expect("asserted"));
 
   first < second
 }
@@ -71,8 +71,8 @@ fn uint_less_than_equal(
   assert_eq!(params.len(), 2);
 
   let mut it = params.iter();
-  let first = slice_to_u64(it.next().expect("asserted").data());
-  let second = slice_to_u64(it.next().expect("asserted").data());
+  let first = uint(it.next().expect("asserted"));
+  let second = uint(it.next().expect("asserted"));
 
   first <= second
 }
@@ -87,9 +87,9 @@ fn uint_greater_than_by(
   assert_eq!(params.len(), 3);
 
   let mut it = params.iter();
-  let first = slice_to_u64(it.next().expect("asserted").data());
-  let second = slice_to_u64(it.next().expect("asserted").data());
-  let by = slice_to_u64(it.next().expect("asserted").data());
+  let first = uint(it.next().expect("asserted"));
+  let second = uint(it.next().expect("asserted"));
+  let by = uint(it.next().expect("asserted"));
 
   first.saturating_sub(second) == by
 }
@@ -104,13 +104,13 @@ fn uint_less_than_by(
   assert_eq!(params.len(), 3);
 
   let mut it = params.iter();
-  let first = slice_to_u64(it.next().expect("asserted").data());
-  let second = slice_to_u64(it.next().expect("asserted").data());
-  let by = slice_to_u64(it.next().expect("asserted").data());
+  let first = uint(it.next().expect("asserted"));
+  let second = uint(it.next().expect("asserted"));
+  let by = uint(it.next().expect("asserted"));
 
   second.saturating_sub(first) == by
 }
 
-fn slice_to_u64(bytes: &[u8]) -> u64 {
-  rmp_serde::from_slice(bytes).expect("invalid argument format")
+fn uint(param: &ExpandedParam) -> u64 {
+  param.decode().expect("invalid argument format")
 }
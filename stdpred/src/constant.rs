@@ -16,7 +16,10 @@ use {
 #[predicate]
 fn constant(params: &Vec<ExpandedParam>, _: &PredicateContext) -> bool {
   assert_eq!(params.len(), 1);
-  rmp_serde::from_slice(params.iter().next().expect("asserted").data())
+  params
+    .first()
+    .expect("asserted")
+    .decode()
     .expect("invalid argument format")
 }
 
@@ -34,12 +37,18 @@ fn immutable_state(
 
   // make sure that the change is targetting this account not any of its
   // children
-  let target: Address =
-    rmp_serde::from_slice(params.first().expect("asserted").data())
-      .expect("invalid predicate param");
+  let target: Address = params
+    .first()
+    .expect("asserted")
+    .decode()
+    .expect("invalid predicate param");
 
   if let Some(change) = context.proposals.get(&target) {
-    if matches!(change, ExpandedAccountChange::ReplaceState { .. }) {
+    if matches!(
+      change,
+      ExpandedAccountChange::ReplaceState { .. }
+        | ExpandedAccountChange::PatchState { .. }
+    ) {
       return false;
     }
   }
@@ -61,9 +70,11 @@ fn immutable_predicates(
 
   // make sure that the change is targetting this account not any of its
   // children
-  let target: Address =
-    rmp_serde::from_slice(params.first().expect("asserted").data())
-      .expect("invalid predicate param");
+  let target: Address = params
+    .first()
+    .expect("asserted")
+    .decode()
+    .expect("invalid predicate param");
 
   if let Some(change) = context.proposals.get(&target) {
     if matches!(change, ExpandedAccountChange::ReplacePredicates { .. }) {
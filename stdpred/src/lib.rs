@@ -4,8 +4,18 @@ mod arithmetic;
 mod bytes;
 mod constant;
 mod signature;
+mod tip;
+mod trade;
+mod wallet;
 
 use anoma_predicates_sdk::initialize_library;
-pub use {arithmetic::*, bytes::*, constant::*, signature::*};
+pub use {
+  arithmetic::*,
+  bytes::*,
+  constant::*,
+  signature::*,
+  tip::*,
+  trade::*,
+};
 
 initialize_library!();
@@ -0,0 +1,47 @@
+use {
+  crate::wallet::received,
+  alloc::vec::Vec,
+  anoma_predicates_sdk::{predicate, ExpandedParam, PredicateContext, Tip},
+};
+
+/// Verifies that the solver named in the transaction receives a tip.
+///
+/// The tip is paid to the solver's wallet of the token. All intents
+/// of the transaction that tip in the same token are paid to the same
+/// wallet, so its balance must grow by at least the sum of their tips.
+/// Wallet balances are 64bit unsigned integers. Transactions that
+/// don't name a solver can't satisfy this predicate.
+///
+/// Parameters:
+///   0. Address of the token
+///   1. Tip amount, 64bit unsigned integer
+#[predicate]
+fn tip_paid(params: &Vec<ExpandedParam>, context: &PredicateContext) -> bool {
+  assert_eq!(params.len(), 2);
+
+  let mut it = params.iter();
+  let tip = Tip {
+    token: it
+      .next()
+      .expect("asserted")
+      .decode()
+      .expect("invalid token"),
+    amount: it
+      .next()
+      .expect("asserted")
+      .decode()
+      .expect("invalid amount"),
+  };
+
+  // intents may expect a tip without declaring it in their terms,
+  // then it is not part of the total of declared tips.
+  let owed = context
+    .tips
+    .get(&tip.token)
+    .map_or(tip.amount, |total| tip.amount.max(*total));
+
+  match &context.solver {
+    Some(solver) => received(context, &tip.payee(solver)) >= owed,
+    None => false,
+  }
+}
@@ -0,0 +1,46 @@
+use {
+  crate::wallet::{received, sent},
+  alloc::vec::Vec,
+  anoma_predicates_sdk::{predicate, ExpandedParam, PredicateContext, Trade},
+};
+
+/// Verifies that a transaction carries out the trade declared by an
+/// intent.
+///
+/// The owner's wallet of every offered token sends at most the offered
+/// amount and the owner's wallet of every wanted token receives at least
+/// the wanted amount. If a resource names a counterparty, then it is the
+/// counterparty's wallet that receives the offered tokens or sends the
+/// wanted tokens. Wallet balances are 64bit unsigned integers.
+///
+/// Parameters:
+///   0. The trade
+#[predicate]
+fn trade_satisfied(
+  params: &Vec<ExpandedParam>,
+  context: &PredicateContext,
+) -> bool {
+  assert_eq!(params.len(), 1);
+
+  let trade: Trade = params
+    .first()
+    .expect("asserted")
+    .decode()
+    .expect("invalid trade");
+
+  let offers = trade.offers.iter().all(|offer| {
+    sent(context, &offer.wallet(&trade.owner)) <= offer.amount
+      && offer.counterparty.as_ref().map_or(true, |counterparty| {
+        received(context, &offer.wallet(counterparty)) >= offer.amount
+      })
+  });
+
+  let wants = trade.wants.iter().all(|want| {
+    received(context, &want.wallet(&trade.owner)) >= want.amount
+      && want.counterparty.as_ref().map_or(true, |counterparty| {
+        sent(context, &want.wallet(counterparty)) >= want.amount
+      })
+  });
+
+  offers && wants
+}
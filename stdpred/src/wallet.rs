@@ -0,0 +1,48 @@
+use anoma_predicates_sdk::{
+  Address,
+  ExpandedAccountChange,
+  PredicateContext,
+  TypedParam,
+};
+
+/// Balance of a token wallet before and after the transaction,
+/// or `None` if the transaction does not change the wallet.
+///
+/// Wallet balances are 64bit unsigned integers, wallets that are
+/// created or deleted have a zero balance before or after.
+pub(crate) fn balances(
+  context: &PredicateContext,
+  wallet: &Address,
+) -> Option<(u64, u64)> {
+  match context.proposals.get(wallet)? {
+    ExpandedAccountChange::CreateAccount(account) => {
+      Some((0, balance(&account.state)))
+    }
+    ExpandedAccountChange::ReplaceState { current, proposed }
+    | ExpandedAccountChange::PatchState {
+      current, proposed, ..
+    } => Some((balance(current), balance(proposed))),
+    ExpandedAccountChange::DeleteAccount { current } => {
+      Some((balance(&current.state), 0))
+    }
+    ExpandedAccountChange::ReplacePredicates { .. } => None,
+  }
+}
+
+/// Number of tokens that a wallet receives in the transaction.
+pub(crate) fn received(context: &PredicateContext, wallet: &Address) -> u64 {
+  balances(context, wallet)
+    .map(|(before, after)| after.saturating_sub(before))
+    .unwrap_or(0)
+}
+
+/// Number of tokens that a wallet sends in the transaction.
+pub(crate) fn sent(context: &PredicateContext, wallet: &Address) -> u64 {
+  balances(context, wallet)
+    .map(|(before, after)| before.saturating_sub(after))
+    .unwrap_or(0)
+}
+
+fn balance(state: &[u8]) -> u64 {
+  TypedParam::<u64>::from_bytes(state).decode().unwrap_or(0)
+}
@@ -1,5 +1,7 @@
+#![allow(clippy::result_large_err)]
+
 use {
-  crate::{State, StateDiff},
+  crate::{Rules, State, StateDiff},
   anoma_primitives::{
     Account,
     AccountChange,
@@ -10,12 +12,18 @@ use {
     ExpandedAccountChange,
     ExpandedCode,
     ExpandedParam,
+    Intent,
+    IntentRef,
     Param,
+    PatchError,
     Predicate,
     PredicateContext,
     PredicateTree,
     Transaction,
+    CODE_REGISTRY,
+    ROOT_ACCOUNT,
   },
+  multihash::Multihash,
   std::collections::{BTreeMap, HashMap},
   thiserror::Error,
 };
@@ -28,12 +36,46 @@ pub enum Error {
   #[error("Trying to mutate an account ({0}) that does not exist.")]
   AccountDoesNotExist(Address),
 
+  #[error(
+    "Code registry entries ({0}) can only be created with bytecode that \
+     matches their hash and can't be changed."
+  )]
+  InvalidCodeRegistration(Address),
+
+  #[error("Invalid state patch for account {0}: {1}")]
+  InvalidStatePatch(Address, PatchError),
+
+  #[error("Account {0} is changed more than once by the transaction.")]
+  ConflictingChanges(Address),
+
+  #[error("Can't move a subtree ({0}) into itself ({1}).")]
+  InvalidSubtreeMove(Address, Address),
+
+  #[error("Trying to create an account ({0}) whose parent does not exist.")]
+  ParentDoesNotExist(Address),
+
+  #[error(
+    "Trying to delete an account ({0}) without deleting its descendants."
+  )]
+  OrphanedDescendants(Address),
+
+  #[error(
+    "The root account ({0}) can't be deleted on chains with hierarchy rules."
+  )]
+  RootAccountDeleted(Address),
+
   #[error(
     "Predicate is referencing code from an account ({0}) that does not exist \
      in {1:?}"
   )]
   CodeDoesNotExist(Address, Predicate),
 
+  #[error(
+    "Predicate is referencing registered code with hash {} that does not \
+     exist in {1:?}", bs58::encode(.0.to_bytes()).into_string()
+  )]
+  RegisteredCodeDoesNotExist(Multihash, Predicate),
+
   #[error(
     "Predicate is referencing an account ({0}) that does not exist in {1:?}"
   )]
@@ -50,6 +92,24 @@ pub enum Error {
      the transaction in {1:?}"
   )]
   CalldataNotFound(String, Predicate),
+
+  #[error(
+    "Predicate is referencing calldata with key '{0}' that has different \
+     values in intents of the transaction in {1:?}"
+  )]
+  CalldataConflict(String, Predicate),
+
+  #[error(
+    "Intents with the same signing hash {} carry different calldata.",
+    bs58::encode(.0.to_bytes()).into_string()
+  )]
+  IntentCalldataConflict(Multihash),
+
+  #[error(
+    "Predicate is referencing an intent ({0:?}) that is not in the \
+     transaction in {1:?}"
+  )]
+  IntentNotFound(IntentRef, Predicate),
 }
 
 /// in case all predicates evaluate successfully on mutated
@@ -59,21 +119,37 @@ pub enum Error {
 pub fn outputs(
   state: &dyn State,
   transaction: &Transaction,
+  rules: &Rules,
 ) -> Result<StateDiff, Error> {
+  let changes = account_changes(state, transaction)?;
+  if rules.hierarchy {
+    check_hierarchy(state, &changes)?;
+  }
+
   let mut output = StateDiff::default();
-  for (addr, change) in &transaction.proposals {
-    let addr = addr.clone();
+  let registry: Address = CODE_REGISTRY.parse().expect("valid address");
+  for (addr, change) in changes {
+    if registry.is_parent_of(&addr) {
+      let matches_hash = |acc: &Account| {
+        Code::registry_address(&Code::hash_bytecode(&acc.state)) == addr
+      };
+      if !matches!(&change, AccountChange::CreateAccount(acc) if matches_hash(acc))
+      {
+        return Err(Error::InvalidCodeRegistration(addr));
+      }
+    }
+
     match change {
       AccountChange::CreateAccount(acc) => {
         if state.get(&addr).is_some() {
-          return Err(Error::AccountAlreadyExists(addr.clone()));
+          return Err(Error::AccountAlreadyExists(addr));
         }
-        output.set(addr, acc.clone());
+        output.set(addr, acc);
       }
       AccountChange::ReplaceState(s) => {
         if let Some(acc) = state.get(&addr) {
           output.set(addr, Account {
-            state: s.clone(),
+            state: s,
             predicates: acc.predicates,
           });
         } else {
@@ -84,7 +160,7 @@ pub fn outputs(
         if let Some(acc) = state.get(&addr) {
           output.set(addr, Account {
             state: acc.state,
-            predicates: p.clone(),
+            predicates: p,
           });
         } else {
           return Err(Error::AccountDoesNotExist(addr));
@@ -97,12 +173,129 @@ pub fn outputs(
           output.remove(&addr);
         }
       }
+      AccountChange::PatchState(patch) => {
+        if let Some(acc) = state.get(&addr) {
+          output.set(addr.clone(), Account {
+            state: patch
+              .apply(&acc.state)
+              .map_err(|e| Error::InvalidStatePatch(addr, e))?,
+            predicates: acc.predicates,
+          });
+        } else {
+          return Err(Error::AccountDoesNotExist(addr));
+        }
+      }
+      AccountChange::DeleteSubtree | AccountChange::MoveSubtree(_) => {
+        unreachable!("subtree changes are flattened")
+      }
     }
   }
 
   Ok(output)
 }
 
+/// Changes of all accounts affected by a transaction.
+///
+/// Subtree changes are replaced by changes of every account in the
+/// affected subtrees, so the rest of the VM only deals with changes
+/// of individual accounts. An account may be changed only once.
+fn account_changes(
+  state: &dyn State,
+  transaction: &Transaction,
+) -> Result<BTreeMap<Address, AccountChange>, Error> {
+  let mut output = BTreeMap::new();
+  let mut insert =
+    |addr: Address, change| match output.insert(addr.clone(), change) {
+      Some(_) => Err(Error::ConflictingChanges(addr)),
+      None => Ok(()),
+    };
+
+  for (addr, change) in &transaction.proposals {
+    match change {
+      AccountChange::DeleteSubtree => {
+        for (addr, _) in subtree(state, addr)? {
+          insert(addr, AccountChange::DeleteAccount)?;
+        }
+      }
+      AccountChange::MoveSubtree(to) => {
+        if addr == to || addr.is_parent_of(to) {
+          return Err(Error::InvalidSubtreeMove(addr.clone(), to.clone()));
+        }
+        for (from, acc) in subtree(state, addr)? {
+          let moved = from.rebase(addr, to).expect("address in subtree");
+          insert(from, AccountChange::DeleteAccount)?;
+          insert(moved, AccountChange::CreateAccount(acc))?;
+        }
+      }
+      change => insert(addr.clone(), change.clone())?,
+    }
+  }
+
+  Ok(output)
+}
+
+/// Verifies that changes keep every account that is not top-level
+/// under an existing parent, and all top-level accounts under the
+/// root account, see [`ROOT_ACCOUNT`].
+fn check_hierarchy(
+  state: &dyn State,
+  changes: &BTreeMap<Address, AccountChange>,
+) -> Result<(), Error> {
+  let exists_after = |addr: &Address| match changes.get(addr) {
+    Some(AccountChange::CreateAccount(_)) => true,
+    Some(AccountChange::DeleteAccount) => false,
+    _ => state.get(addr).is_some(),
+  };
+
+  let root = root_account();
+  for (addr, change) in changes {
+    match change {
+      AccountChange::CreateAccount(_) if matches!(addr.parent(), Some(p) if !exists_after(&p)) =>
+      {
+        return Err(Error::ParentDoesNotExist(addr.clone()));
+      }
+      AccountChange::CreateAccount(_)
+        if addr.parent().is_none() && addr != &root && !exists_after(&root) =>
+      {
+        return Err(Error::ParentDoesNotExist(addr.clone()));
+      }
+      AccountChange::DeleteAccount if addr == &root => {
+        return Err(Error::RootAccountDeleted(addr.clone()));
+      }
+      AccountChange::DeleteAccount
+        if state.descendants(addr).iter().any(exists_after) =>
+      {
+        return Err(Error::OrphanedDescendants(addr.clone()));
+      }
+      _ => {}
+    }
+  }
+
+  Ok(())
+}
+
+fn root_account() -> Address {
+  ROOT_ACCOUNT.parse().expect("valid address")
+}
+
+/// An existing account and all its descendants.
+fn subtree(
+  state: &dyn State,
+  root: &Address,
+) -> Result<Vec<(Address, Account)>, Error> {
+  let account = state
+    .get(root)
+    .ok_or_else(|| Error::AccountDoesNotExist(root.clone()))?;
+
+  let mut output = vec![(root.clone(), account)];
+  for addr in state.descendants(root) {
+    if let Some(acc) = state.get(&addr) {
+      output.push((addr, acc));
+    }
+  }
+  Ok(output)
+}
+
 /// Prepares the transaction context that is passed as an
 /// argument to every predicate triggered by a transaction.
 pub fn predicate_context(
@@ -110,21 +303,44 @@ pub fn predicate_context(
   transaction: &Transaction,
 ) -> Result<PredicateContext, Error> {
   Ok(PredicateContext {
-    calldata: transaction
-      .intents
-      .iter()
-      .map(|intent| (*intent.hash(), intent.calldata.clone()))
-      .collect(),
+    // calldata is grouped by the signing hash, so signatures in it can
+    // be verified. Intents that are signed the same way but carry
+    // different calldata would overwrite each other's entries.
+    calldata: {
+      let mut calldata = BTreeMap::new();
+      for intent in &transaction.intents {
+        let hash = *intent.signing_hash();
+        if let Some(previous) = calldata.insert(hash, intent.calldata.clone()) {
+          if previous != intent.calldata {
+            return Err(Error::IntentCalldataConflict(hash));
+          }
+        }
+      }
+      calldata
+    },
     proposals: {
       let mut proposals = BTreeMap::new();
-      for (addr, change) in &transaction.proposals {
+      for (addr, change) in account_changes(state, transaction)? {
         proposals.insert(
           addr.clone(), //
-          expand_account_change(addr.clone(), state, change)?,
+          expand_account_change(addr, state, &change)?,
         );
       }
       proposals
     },
+    tips: {
+      let mut tips = BTreeMap::new();
+      for tip in transaction
+        .intents
+        .iter()
+        .filter_map(|i| i.terms.tip.as_ref())
+      {
+        let total = tips.entry(tip.token.clone()).or_insert(0u64);
+        *total = total.saturating_add(tip.amount);
+      }
+      tips
+    },
+    solver: transaction.solver.clone(),
   })
 }
 
@@ -134,33 +350,27 @@ pub fn accounts_predicates(
   state: &dyn State,
   context: &PredicateContext,
   transaction: &Transaction,
+  rules: &Rules,
 ) -> Result<Vec<PredicateTree<Expanded>>, Error> {
   let mut output = HashMap::new();
 
   // when predicates on accounts reference calldata entries,
-  // the reference calldata entries stored in intents. If intents
-  // have overlapping calldata entries, then one of them will be
-  // bound to the CalldataRef parameter. The one that gets bound is
-  // undetermined as we don't want to make any promises for VM users.
-  //
-  // Its rare that account predicates reference calldata entries from
-  // intents, but when they do, its most likely a very specific value
-  // identified by things like a public key.
+  // the referenced calldata entries are stored in intents. If more
+  // than one intent has an entry with the same key, then they all
+  // must agree on its value, otherwise the transaction is rejected.
   //
   // If account predicates care about which specific intent has a given
-  // calldata entry, then they have access to the context object that groups
-  // those entries by the containing intent hash.
-  let calldata = context
-    .calldata
-    .values()
-    .cloned()
-    .reduce(|mut prev, current| {
-      prev.extend(current);
-      prev
-    })
-    .unwrap_or_default();
+  // calldata entry, then they can reference it by the intent position
+  // or signing hash, or use the context object that groups those
+  // entries by the containing intent signing hash.
+  let calldata = CalldataScope {
+    intents: &transaction.intents,
+    owner: None,
+  };
 
-  for addr in transaction.proposals.keys() {
+  // proposals in the context include every account
+  // affected by subtree changes.
+  for addr in context.proposals.keys() {
     if !output.contains_key(addr) {
       if let Some(acc) = state.get(addr) {
         output.insert(
@@ -183,6 +393,22 @@ pub fn accounts_predicates(
     }
   }
 
+  // top-level accounts have no ancestors, so on chains with hierarchy
+  // rules their creation is guarded by the root account predicates.
+  let creates_top_level = context.proposals.iter().any(|(addr, change)| {
+    addr.parent().is_none()
+      && matches!(change, ExpandedAccountChange::CreateAccount(_))
+  });
+  let root = root_account();
+  if rules.hierarchy && creates_top_level && !output.contains_key(&root) {
+    if let Some(acc) = state.get(&root) {
+      output.insert(
+        root,
+        expand_predicate_tree(state, acc.predicates, context, &calldata)?,
+      );
+    }
+  }
+
   Ok(output.into_values().collect())
 }
 
@@ -192,28 +418,88 @@ pub fn intents_predicates(
   tx: Transaction,
 ) -> Result<Vec<PredicateTree<Expanded>>, Error> {
   let mut output = Vec::with_capacity(tx.intents.len());
-  for intent in tx.intents {
+  for intent in &tx.intents {
     output.push(expand_predicate_tree(
       state,
-      intent.expectations,
+      intent.expectations.clone(),
       context,
-      &intent.calldata,
+      &CalldataScope {
+        intents: &tx.intents,
+        owner: Some(&intent.calldata),
+      },
     )?);
   }
   Ok(output)
 }
 
+/// Calldata entries that are visible to calldata references
+/// in a predicate tree.
+struct CalldataScope<'a> {
+  /// All intents in the transaction in their order.
+  intents: &'a [Intent],
+
+  /// Calldata of the intent that owns the predicate tree,
+  /// or none for account predicates.
+  owner: Option<&'a Calldata>,
+}
+
+impl CalldataScope<'_> {
+  /// Resolves a calldata entry of a given intent, or if no intent
+  /// is named then of the owning intent or of all intents for
+  /// account predicates. All intents that have the entry must
+  /// agree on its value.
+  fn resolve(
+    &self,
+    intent: Option<&IntentRef>,
+    key: &str,
+    pred: &Predicate,
+  ) -> Result<Vec<u8>, Error> {
+    let candidates: Vec<&Calldata> = match (intent, self.owner) {
+      (None, Some(owner)) => vec![owner],
+      (None, None) => self.intents.iter().map(|i| &i.calldata).collect(),
+      (Some(IntentRef::Index(ix)), _) => self
+        .intents
+        .get(*ix)
+        .map(|i| &i.calldata)
+        .into_iter()
+        .collect(),
+      (Some(IntentRef::SigningHash(hash)), _) => self
+        .intents
+        .iter()
+        .filter(|i| i.signing_hash() == hash)
+        .map(|i| &i.calldata)
+        .collect(),
+    };
+
+    if let (Some(intent), true) = (intent, candidates.is_empty()) {
+      return Err(Error::IntentNotFound(intent.clone(), pred.clone()));
+    }
+
+    let mut values = candidates.into_iter().filter_map(|c| c.get(key));
+    match values.next() {
+      Some(first) => match values.all(|v| v == first) {
+        true => Ok(first.clone()),
+        false => Err(Error::CalldataConflict(key.into(), pred.clone())),
+      },
+      None => Err(Error::CalldataNotFound(key.into(), pred.clone())),
+    }
+  }
+}
+
 /// Gathers a predicate tree into a self contained object with
 /// all external references to accounts, proposals or calldata
 /// resolved and embedded in the expanded representation of
 /// predicate tree.
+///
+/// The tree is normalized first, so redundant predicates are
+/// neither expanded nor evaluated.
 fn expand_predicate_tree(
   state: &dyn State,
   tree: PredicateTree,
   context: &PredicateContext,
-  calldata: &Calldata,
+  calldata: &CalldataScope,
 ) -> Result<PredicateTree<Expanded>, Error> {
-  tree.try_map(|pred| {
+  tree.normalize().try_map(|pred| {
     let pred_e = pred.clone();
     Ok(Predicate::<Expanded> {
       code: match pred.code {
@@ -231,6 +517,19 @@ fn expand_predicate_tree(
             return Err(Error::CodeDoesNotExist(addr, pred_e));
           }
         }
+        Code::Hash(hash, entrypoint) => {
+          // registry entries are checked when created,
+          // this guards against a corrupted state.
+          match state.get(&Code::registry_address(&hash)) {
+            Some(acc) if Code::hash_bytecode(&acc.state) == hash => {
+              ExpandedCode {
+                code: acc.state,
+                entrypoint,
+              }
+            }
+            _ => return Err(Error::RegisteredCodeDoesNotExist(hash, pred_e)),
+          }
+        }
       },
       params: {
         let mut params = Vec::with_capacity(pred.params.len());
@@ -248,10 +547,14 @@ fn expand_predicate_tree(
                 None => return Err(Error::ProposalDoesNotExist(addr, pred_e)),
               },
             ),
-            Param::CalldataRef(key) => match calldata.get(&key) {
-              Some(val) => ExpandedParam::CalldataRef(key, val.clone()),
-              None => return Err(Error::CalldataNotFound(key, pred_e)),
-            },
+            Param::CalldataRef(key) => {
+              let value = calldata.resolve(None, &key, &pred_e)?;
+              ExpandedParam::CalldataRef(key, value)
+            }
+            Param::IntentCalldataRef(intent, key) => {
+              let value = calldata.resolve(Some(&intent), &key, &pred_e)?;
+              ExpandedParam::IntentCalldataRef(intent, key, value)
+            }
           });
         }
         params
@@ -290,5 +593,531 @@ fn expand_account_change(
       Some(acc) => ExpandedAccountChange::DeleteAccount { current: acc },
       None => return Err(Error::AccountDoesNotExist(addr)),
     },
+    AccountChange::PatchState(patch) => match state.get(&addr) {
+      Some(acc) => ExpandedAccountChange::PatchState {
+        patch: patch.clone(),
+        proposed: patch
+          .apply(&acc.state)
+          .map_err(|e| Error::InvalidStatePatch(addr, e))?,
+        current: acc.state,
+      },
+      None => return Err(Error::AccountDoesNotExist(addr)),
+    },
+    AccountChange::DeleteSubtree | AccountChange::MoveSubtree(_) => {
+      unreachable!("subtree changes are flattened")
+    }
   })
 }
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{
+      accounts_predicates,
+      intents_predicates,
+      outputs,
+      predicate_context,
+      Error,
+    },
+    crate::{InMemoryStateStore, Rules, State, StateDiff},
+    anoma_primitives::{
+      builder::{self, IntentBuilder, TransactionBuilder},
+      Account,
+      Address,
+      Code,
+      Expanded,
+      ExpandedAccountChange,
+      ExpandedParam,
+      ExpressionTree,
+      Intent,
+      IntentRef,
+      Param,
+      PredicateTree,
+      StatePatch,
+      Transaction,
+      ROOT_ACCOUNT,
+    },
+    multihash::Multihash,
+  };
+
+  /// State with the standard predicates library account,
+  /// its bytecode is never invoked by these tests.
+  fn store() -> InMemoryStateStore {
+    let mut store = InMemoryStateStore::default();
+    let mut diff = StateDiff::default();
+    diff.set(builder::stdpred::ADDRESS.parse().unwrap(), Account {
+      state: vec![],
+      predicates: builder::stdpred::constant(false),
+    });
+    store.apply(diff);
+    store
+  }
+
+  fn intent(key: &str, value: &[u8]) -> Intent {
+    IntentBuilder::new(
+      "testnet",
+      Multihash::default(),
+      builder::stdpred::bytes_equal(
+        builder::calldata(key),
+        builder::inline_bytes(value),
+      ),
+    )
+    .calldata(key, value.to_vec())
+    .build()
+  }
+
+  /// A transaction that changes an account that has a predicate
+  /// with a given param, and the expanded value of that param.
+  fn expand(param: Param, intents: Vec<Intent>) -> Result<Vec<u8>, Error> {
+    let mut store = store();
+    let mut diff = StateDiff::default();
+    diff.set("/a".parse().unwrap(), Account {
+      state: vec![],
+      predicates: builder::stdpred::bytes_equal(
+        param,
+        builder::inline_bytes(vec![]),
+      ),
+    });
+    store.apply(diff);
+
+    let mut tx = TransactionBuilder::new().replace_state("/a", vec![1]);
+    for intent in intents {
+      tx = tx.intent(intent);
+    }
+    let tx = tx.build();
+
+    let context = predicate_context(&store, &tx)?;
+    let mut trees =
+      accounts_predicates(&store, &context, &tx, &Rules::new("testnet"))?;
+    Ok(first_param(trees.remove(0)).data().to_vec())
+  }
+
+  fn first_param(tree: PredicateTree<Expanded>) -> ExpandedParam {
+    match tree {
+      ExpressionTree::Id(pred) => pred.params[0].clone(),
+      _ => panic!("expected a single predicate"),
+    }
+  }
+
+  #[test]
+  fn account_calldata_refs() {
+    let key = || builder::calldata("k");
+
+    assert_eq!(
+      expand(key(), vec![intent("k", b"1"), intent("other", b"2")]).unwrap(),
+      b"1"
+    );
+    assert_eq!(
+      expand(key(), vec![intent("k", b"1"), intent("k", b"1")]).unwrap(),
+      b"1"
+    );
+    assert!(matches!(
+      expand(key(), vec![intent("k", b"1"), intent("k", b"2")]),
+      Err(Error::CalldataConflict(..))
+    ));
+    assert!(matches!(
+      expand(key(), vec![intent("other", b"1")]),
+      Err(Error::CalldataNotFound(..))
+    ));
+  }
+
+  #[test]
+  fn intent_calldata_refs() {
+    let intents = || vec![intent("k", b"1"), intent("k", b"2")];
+    let by_index = |ix| builder::intent_calldata(IntentRef::Index(ix), "k");
+
+    assert_eq!(expand(by_index(0), intents()).unwrap(), b"1");
+    assert_eq!(expand(by_index(1), intents()).unwrap(), b"2");
+    assert!(matches!(
+      expand(by_index(2), intents()),
+      Err(Error::IntentNotFound(IntentRef::Index(2), _))
+    ));
+    assert!(matches!(
+      expand(
+        builder::intent_calldata(IntentRef::Index(0), "x"),
+        intents()
+      ),
+      Err(Error::CalldataNotFound(..))
+    ));
+
+    let hash = *intents()[1].signing_hash();
+    assert_eq!(
+      expand(
+        builder::intent_calldata(IntentRef::SigningHash(hash), "k"),
+        intents()
+      )
+      .unwrap(),
+      b"2"
+    );
+    assert!(matches!(
+      expand(
+        builder::intent_calldata(
+          IntentRef::SigningHash(Multihash::default()),
+          "k"
+        ),
+        intents()
+      ),
+      Err(Error::IntentNotFound(..))
+    ));
+  }
+
+  #[test]
+  fn context_calldata_conflicts() {
+    let store = store();
+    let signed = |value: &[u8]| {
+      IntentBuilder::new(
+        "testnet",
+        Multihash::default(),
+        builder::stdpred::constant(true),
+      )
+      .calldata("k", value.to_vec())
+      .build()
+    };
+
+    let tx: Transaction = TransactionBuilder::new()
+      .intent(signed(b"1"))
+      .intent(signed(b"1"))
+      .build();
+    let context = predicate_context(&store, &tx).unwrap();
+    assert_eq!(context.calldata.len(), 1);
+
+    let tx: Transaction = TransactionBuilder::new()
+      .intent(signed(b"1"))
+      .intent(signed(b"2"))
+      .build();
+    assert!(matches!(
+      predicate_context(&store, &tx),
+      Err(Error::IntentCalldataConflict(hash))
+        if hash == *tx.intents[0].signing_hash()
+    ));
+  }
+
+  #[test]
+  fn intents_see_own_calldata() {
+    let store = store();
+    let tx: Transaction = TransactionBuilder::new()
+      .intent(intent("k", b"1"))
+      .intent(intent("k", b"2"))
+      .build();
+
+    let context = predicate_context(&store, &tx).unwrap();
+    let trees = intents_predicates(&store, &context, tx).unwrap();
+    let values: Vec<_> = trees
+      .into_iter()
+      .map(|tree| first_param(tree).data().to_vec())
+      .collect();
+    assert_eq!(values, vec![b"1".to_vec(), b"2".to_vec()]);
+  }
+
+  #[test]
+  fn registered_code() {
+    let bytecode = b"wasm bytecode".to_vec();
+    let hash = Code::hash_bytecode(&bytecode);
+    let address = Code::registry_address(&hash);
+
+    let mut store = store();
+    let registration = TransactionBuilder::new()
+      .register_code(bytecode.clone())
+      .build();
+    store
+      .apply(outputs(&store, &registration, &Rules::new("testnet")).unwrap());
+
+    let intent = IntentBuilder::new(
+      "testnet",
+      Multihash::default(),
+      builder::predicate(builder::registered_code(hash, "run"), []),
+    )
+    .build();
+    let tx = TransactionBuilder::new().intent(intent).build();
+    let context = predicate_context(&store, &tx).unwrap();
+    match intents_predicates(&store, &context, tx).unwrap().remove(0) {
+      ExpressionTree::Id(pred) => {
+        assert_eq!(pred.code.code, bytecode);
+        assert_eq!(pred.code.entrypoint, "run");
+      }
+      _ => panic!("expected a single predicate"),
+    }
+
+    // unregistered code can't be referenced
+    let intent = IntentBuilder::new(
+      "testnet",
+      Multihash::default(),
+      builder::predicate(
+        builder::registered_code(Code::hash_bytecode(b"other"), "run"),
+        [],
+      ),
+    )
+    .build();
+    let tx = TransactionBuilder::new().intent(intent).build();
+    let context = predicate_context(&store, &tx).unwrap();
+    assert!(matches!(
+      intents_predicates(&store, &context, tx),
+      Err(Error::RegisteredCodeDoesNotExist(..))
+    ));
+
+    // registry entries must match their hash and are immutable
+    let mismatched = TransactionBuilder::new()
+      .create(
+        Code::registry_address(&Code::hash_bytecode(b"other")),
+        Account {
+          state: bytecode.clone(),
+          predicates: ExpressionTree::Any(vec![]),
+        },
+      )
+      .build();
+    assert!(matches!(
+      outputs(&store, &mismatched, &Rules::new("testnet")),
+      Err(Error::InvalidCodeRegistration(_))
+    ));
+    let replaced = TransactionBuilder::new()
+      .replace_state(address, b"other".to_vec())
+      .build();
+    assert!(matches!(
+      outputs(&store, &replaced, &Rules::new("testnet")),
+      Err(Error::InvalidCodeRegistration(_))
+    ));
+  }
+
+  #[test]
+  fn patched_state() {
+    let mut store = store();
+    let mut diff = StateDiff::default();
+    diff.set("/a".parse().unwrap(), Account {
+      state: b"abcd".to_vec(),
+      predicates: builder::stdpred::constant(true),
+    });
+    store.apply(diff);
+
+    let patch = StatePatch::Range {
+      offset: 2,
+      bytes: b"xyz".to_vec(),
+    };
+    let tx = TransactionBuilder::new()
+      .patch_state("/a", patch.clone())
+      .build();
+    let output = outputs(&store, &tx, &Rules::new("testnet")).unwrap();
+    let account = output.get(&"/a".parse().unwrap()).unwrap();
+    assert_eq!(account.state, b"abxyz");
+    assert_eq!(account.predicates, builder::stdpred::constant(true));
+
+    let context = predicate_context(&store, &tx).unwrap();
+    assert_eq!(
+      context.proposals.values().next().unwrap(),
+      &ExpandedAccountChange::PatchState {
+        patch,
+        current: b"abcd".to_vec(),
+        proposed: b"abxyz".to_vec(),
+      }
+    );
+
+    let tx = TransactionBuilder::new()
+      .patch_state("/a", StatePatch::Range {
+        offset: 5,
+        bytes: vec![],
+      })
+      .build();
+    assert!(matches!(
+      outputs(&store, &tx, &Rules::new("testnet")),
+      Err(Error::InvalidStatePatch(..))
+    ));
+    assert!(matches!(
+      predicate_context(&store, &tx),
+      Err(Error::InvalidStatePatch(..))
+    ));
+
+    let tx = TransactionBuilder::new()
+      .patch_state("/b", StatePatch::Append(vec![1]))
+      .build();
+    assert!(matches!(
+      outputs(&store, &tx, &Rules::new("testnet")),
+      Err(Error::AccountDoesNotExist(_))
+    ));
+  }
+
+  #[test]
+  fn subtree_changes() {
+    let addr = |a: &str| -> Address { a.parse().unwrap() };
+    let mut store = store();
+    let mut diff = StateDiff::default();
+    for a in ["/pgqf/1", "/pgqf/1/project/1", "/pgqf/10", "/archive"] {
+      diff.set(addr(a), Account {
+        state: a.as_bytes().to_vec(),
+        predicates: builder::stdpred::constant(true),
+      });
+    }
+    store.apply(diff);
+
+    let tx = TransactionBuilder::new().delete_subtree("/pgqf/1").build();
+    let output = outputs(&store, &tx, &Rules::new("testnet")).unwrap();
+    assert_eq!(output.change(&addr("/pgqf/1")), Some(None));
+    assert_eq!(output.change(&addr("/pgqf/1/project/1")), Some(None));
+    assert_eq!(output.change(&addr("/pgqf/10")), None);
+
+    let context = predicate_context(&store, &tx).unwrap();
+    assert!(context
+      .proposals
+      .values()
+      .all(|c| matches!(c, ExpandedAccountChange::DeleteAccount { .. })));
+    let trees =
+      accounts_predicates(&store, &context, &tx, &Rules::new("testnet"))
+        .unwrap();
+    assert_eq!(trees.len(), 2);
+
+    let tx = TransactionBuilder::new()
+      .move_subtree("/pgqf/1", "/archive/1")
+      .build();
+    let output = outputs(&store, &tx, &Rules::new("testnet")).unwrap();
+    assert_eq!(output.change(&addr("/pgqf/1")), Some(None));
+    assert_eq!(output.change(&addr("/pgqf/1/project/1")), Some(None));
+    assert_eq!(
+      output.get(&addr("/archive/1/project/1")),
+      store.get(&addr("/pgqf/1/project/1"))
+    );
+    assert_eq!(output.get(&addr("/archive/1")), store.get(&addr("/pgqf/1")));
+
+    // predicates of the moved accounts and the new parent
+    let context = predicate_context(&store, &tx).unwrap();
+    assert_eq!(context.proposals.len(), 4);
+    let trees =
+      accounts_predicates(&store, &context, &tx, &Rules::new("testnet"))
+        .unwrap();
+    assert_eq!(trees.len(), 3);
+
+    let fails = |tx: TransactionBuilder| {
+      outputs(&store, &tx.build(), &Rules::new("testnet"))
+    };
+    assert!(matches!(
+      fails(TransactionBuilder::new().move_subtree("/pgqf/1", "/pgqf/1/a")),
+      Err(Error::InvalidSubtreeMove(..))
+    ));
+    assert!(matches!(
+      fails(TransactionBuilder::new().move_subtree("/pgqf/1", "/pgqf/10")),
+      Err(Error::AccountAlreadyExists(_))
+    ));
+    assert!(matches!(
+      fails(
+        TransactionBuilder::new()
+          .delete_subtree("/pgqf/1")
+          .replace_state("/pgqf/1/project/1", vec![])
+      ),
+      Err(Error::ConflictingChanges(_))
+    ));
+    assert!(matches!(
+      fails(TransactionBuilder::new().delete_subtree("/pgqf/2")),
+      Err(Error::AccountDoesNotExist(_))
+    ));
+  }
+
+  #[test]
+  fn hierarchy_rules() {
+    let account = || Account {
+      state: vec![],
+      predicates: builder::stdpred::constant(true),
+    };
+    let rules = Rules {
+      hierarchy: true,
+      ..Rules::new("testnet")
+    };
+    let mut store = store();
+    let mut diff = StateDiff::default();
+    diff.set(ROOT_ACCOUNT.parse().unwrap(), Account {
+      state: vec![],
+      predicates: builder::stdpred::constant(false),
+    });
+    diff.set("/a".parse().unwrap(), account());
+    diff.set("/a/b".parse().unwrap(), account());
+    store.apply(diff);
+
+    // hierarchy rules are enabled explicitly, not by the root account
+    let orphan = TransactionBuilder::new()
+      .create("/a/x/y", account())
+      .build();
+    assert!(outputs(&store, &orphan, &Rules::new("testnet")).is_ok());
+
+    assert!(matches!(
+      outputs(&store, &orphan, &rules),
+      Err(Error::ParentDoesNotExist(_))
+    ));
+    let with_parent = TransactionBuilder::new()
+      .create("/a/x", account())
+      .create("/a/x/y", account())
+      .build();
+    assert!(outputs(&store, &with_parent, &rules).is_ok());
+
+    let delete = TransactionBuilder::new().delete("/a").build();
+    assert!(matches!(
+      outputs(&store, &delete, &rules),
+      Err(Error::OrphanedDescendants(_))
+    ));
+    let delete_all = TransactionBuilder::new().delete("/a").delete("/a/b");
+    assert!(outputs(&store, &delete_all.build(), &rules).is_ok());
+    let subtree = TransactionBuilder::new().delete_subtree("/a").build();
+    assert!(outputs(&store, &subtree, &rules).is_ok());
+    let moved = TransactionBuilder::new()
+      .move_subtree("/a/b", "/c/b")
+      .build();
+    assert!(matches!(
+      outputs(&store, &moved, &rules),
+      Err(Error::ParentDoesNotExist(_))
+    ));
+
+    // top-level accounts are guarded by the root account predicates
+    let top_level = TransactionBuilder::new().create("/x", account()).build();
+    let context = predicate_context(&store, &top_level).unwrap();
+    let trees =
+      accounts_predicates(&store, &context, &top_level, &rules).unwrap();
+    assert_eq!(trees.len(), 1);
+    assert!(!first_param(trees[0].clone()).decode::<bool>().unwrap());
+
+    let unguarded =
+      accounts_predicates(&store, &context, &top_level, &Rules::new("testnet"));
+    assert!(unguarded.unwrap().is_empty());
+  }
+
+  #[test]
+  fn root_account_lifecycle() {
+    let account = || Account {
+      state: vec![],
+      predicates: builder::stdpred::constant(true),
+    };
+    let rules = Rules {
+      hierarchy: true,
+      ..Rules::new("testnet")
+    };
+    let mut store = store();
+
+    // before the root account exists no top-level account can be
+    // created, except for the root account itself, which is unguarded.
+    let top_level = TransactionBuilder::new().create("/x", account()).build();
+    assert!(matches!(
+      outputs(&store, &top_level, &rules),
+      Err(Error::ParentDoesNotExist(a)) if a == "/x".parse().unwrap()
+    ));
+
+    let root = TransactionBuilder::new()
+      .create(ROOT_ACCOUNT, account())
+      .build();
+    let context = predicate_context(&store, &root).unwrap();
+    let trees = accounts_predicates(&store, &context, &root, &rules).unwrap();
+    assert!(trees.is_empty());
+
+    let genesis = TransactionBuilder::new()
+      .create(ROOT_ACCOUNT, account())
+      .create("/x", account())
+      .build();
+    store.apply(outputs(&store, &genesis, &rules).unwrap());
+
+    // once created, the root account can't be deleted
+    let delete = TransactionBuilder::new().delete(ROOT_ACCOUNT).build();
+    assert!(matches!(
+      outputs(&store, &delete, &rules),
+      Err(Error::RootAccountDeleted(_))
+    ));
+    let subtree = TransactionBuilder::new().delete_subtree(ROOT_ACCOUNT);
+    assert!(matches!(
+      outputs(&store, &subtree.build(), &rules),
+      Err(Error::RootAccountDeleted(_))
+    ));
+    assert!(outputs(&store, &delete, &Rules::new("testnet")).is_ok());
+  }
+}
@@ -1,16 +1,20 @@
 #![allow(clippy::result_large_err)]
 
 use {
-  crate::{collect, State, StateDiff},
+  crate::{collect, witness, RecordingState, Rules, State, StateDiff, Witness},
   anoma_primitives::{
+    AccountChange,
     Address,
+    Code,
     Expanded,
+    ExpressionTree,
     Predicate,
     PredicateContext,
     PredicateTree,
     Transaction,
+    ValidationError,
   },
-  multihash::{Code, MultihashDigest},
+  multihash::Multihash,
   rayon::prelude::*,
   rmp_serde::{encode, to_vec},
   std::sync::{
@@ -46,9 +50,30 @@ pub enum Error {
   #[error("State access error: {0}")]
   State(#[from] collect::Error),
 
+  #[error("Witness error: {0}")]
+  Witness(#[from] witness::Error),
+
+  #[error("Invalid transaction: {0}")]
+  Invalid(#[from] ValidationError),
+
+  #[error(
+    "Intent {} is not valid at height {1}",
+    bs58::encode(.0.to_bytes()).into_string()
+  )]
+  IntentNotValid(Multihash, u64),
+
+  #[error(
+    "Intent {} is signed for chain {1:?}",
+    bs58::encode(.0.to_bytes()).into_string()
+  )]
+  WrongChain(Multihash, String),
+
   #[error("Rejected by predicate {0:?}")]
   Rejected(Predicate<Expanded>),
 
+  #[error("Rejected by an expression without enough subexpressions")]
+  RejectedEmpty,
+
   #[error("Predicate evaluation cancelled by other failed predicates")]
   Cancelled,
 
@@ -84,17 +109,71 @@ pub enum Error {
 /// current blockchain state and the proposed values and returns
 /// a StateDiff object that can be applied to global blockchain
 /// state if all predicates evaluate to true.
+///
+/// The height is the height of the block that includes the transaction,
+/// all intents of the transaction must be valid at that height.
+/// The transaction is executed under the rules of the chain, all its
+/// intents must be signed for that chain.
 pub fn execute(
   tx: Transaction,
+  height: u64,
+  rules: &Rules,
   state: &dyn State,
   cache: &dyn State,
 ) -> Result<StateDiff, Error> {
+  if let Some(intent) = tx.intents.iter().find(|i| i.chain_id != rules.chain_id)
+  {
+    return Err(Error::WrongChain(*intent.hash(), intent.chain_id.clone()));
+  }
+
+  if let Some(intent) = tx.intents.iter().find(|i| !i.is_valid_at(height)) {
+    return Err(Error::IntentNotValid(*intent.hash(), height));
+  }
+
+  let expansion = expand(tx, rules, state)?;
+
+  // on success return the resulting state diff of this tx
+  match parallel_invoke_predicates(
+    &expansion.context,
+    expansion.predicates.into_par_iter(),
+    cache,
+  ) {
+    Ok(()) => Ok(expansion.diff),
+    Err(e) => Err(e),
+  }
+}
+
+/// Predicates triggered by a transaction, with all their references
+/// to accounts, proposals and calldata resolved, see [`expand`].
+#[derive(Debug)]
+pub struct Expansion {
+  /// Changes applied to the state if all predicates are satisfied.
+  pub diff: StateDiff,
+
+  /// The context object passed to every predicate.
+  pub context: PredicateContext,
+
+  /// Predicates of mutated accounts and their ancestors,
+  /// followed by the expectations of all intents.
+  pub predicates: Vec<PredicateTree<Expanded>>,
+}
+
+/// Resolves all predicates that a transaction triggers
+/// without invoking them.
+///
+/// This is the stage of [`execute`] that interprets the transaction
+/// against the current state, before any predicate code runs.
+pub fn expand(
+  tx: Transaction,
+  rules: &Rules,
+  state: &dyn State,
+) -> Result<Expansion, Error> {
   // those changes will be applied if all predicates
   // evaluate to true in intents and mutated accounts.
   // the resulting type is a StateDiff that is ready
   // to be applied to global replicated blockchain
   // state.
-  let state_diff = collect::outputs(state, &tx)?;
+  let state_diff = collect::outputs(state, &tx, rules)?;
 
   // This context object is passed to every account and intent predicate
   // during evaluation stage. It contains all account mutations proposed
@@ -106,23 +185,62 @@ pub fn execute(
   // accounts and all their parent accounts. For each mutated account
   // all its and its ancestor accounts predicates must evaluate to
   // true before a mutation is accepted into the global blockchain state.
-  let account_preds = collect::accounts_predicates(state, &context, &tx)?;
+  let account_preds =
+    collect::accounts_predicates(state, &context, &tx, rules)?;
 
   // Those are predicates of all intents in the transaction. They all must
   // evaluate to true for a transaction before any account mutations are
   // allowed.
   let intent_preds = collect::intents_predicates(state, &context, tx)?;
 
-  // merge both sets of predicates into one parallel iterator
-  let combined = account_preds
-    .into_par_iter()
-    .chain(intent_preds.into_par_iter());
+  Ok(Expansion {
+    diff: state_diff,
+    context,
+    predicates: account_preds.into_iter().chain(intent_preds).collect(),
+  })
+}
 
-  // on success return the resulting state diff of this tx
-  match parallel_invoke_predicates(&context, combined, cache) {
-    Ok(()) => Ok(state_diff),
-    Err(e) => Err(e),
+/// Executes a transaction against a witness instead of the full state.
+///
+/// This allows validating a transaction without access to the full
+/// blockchain state. The witness must contain every account read during
+/// execution, otherwise the transaction is rejected because its outcome
+/// can't be determined. Merkle proofs in the witness are not checked here,
+/// use [`Witness::verify`] against a trusted state root for that.
+///
+/// Proofs show that accounts exist or don't exist, but not that an account
+/// has no descendants other than those in the witness. Transactions that
+/// delete or move subtrees depend on all descendants of an account, so
+/// they are rejected. So are deletions of single accounts on chains with
+/// hierarchy rules, where they must not leave descendants orphaned.
+pub fn execute_with_witness(
+  tx: Transaction,
+  height: u64,
+  rules: &Rules,
+  witness: &Witness,
+  cache: &dyn State,
+) -> Result<StateDiff, Error> {
+  let unprovable = tx.proposals.iter().find(|(_, change)| match change {
+    AccountChange::DeleteSubtree | AccountChange::MoveSubtree(_) => true,
+    AccountChange::DeleteAccount => rules.hierarchy,
+    _ => false,
+  });
+  if let Some((address, _)) = unprovable {
+    return Err(witness::Error::UnprovableChange(address.clone()).into());
   }
+
+  let state = RecordingState::new(witness);
+  let result = execute(tx, height, rules, &state, cache);
+
+  // an account outside of the witness was treated as nonexistent,
+  // so the result can't be trusted regardless of its outcome.
+  if let Some(missing) =
+    state.reads().into_iter().find(|a| !witness.contains(a))
+  {
+    return Err(witness::Error::Incomplete(missing).into());
+  }
+
+  result
 }
 
 /// Runs a set of predicates in parallel and returns Ok(()) if all of
@@ -145,26 +263,104 @@ fn parallel_invoke_predicates(
         return Err(Error::Cancelled);
       }
 
-      tree.map(|pred| {
+      let verdict = evaluate(tree, &|pred| {
         if cancelled.load(Ordering::Acquire) {
           return Err(Error::Cancelled);
         }
 
         match invoke(&context, &pred, cache) {
-          Ok(true) => Ok(pred),
-          Ok(false) => Err(Error::Rejected(pred)),
+          Ok(true) => Ok(Verdict::Satisfied(Some(pred))),
+          Ok(false) => Ok(Verdict::Rejected(Some(pred))),
           Err(e) => {
             // on predicate crash, cancel everything
             cancelled.store(true, Ordering::Release);
             Err(e)
-          },
+          }
         }
-      }).reduce(|p| p, not, and, or).map(|_| ())
+      })?;
+
+      match verdict {
+        Verdict::Satisfied(_) => Ok(()),
+        Verdict::Rejected(Some(pred)) => Err(Error::Rejected(pred)),
+        Verdict::Rejected(None) => Err(Error::RejectedEmpty),
+      }
     })
     .reduce_with(and) // top-level preds
     .unwrap_or(Ok(()))
 }
 
+/// Outcome of evaluating a predicate tree along with the predicate
+/// that decided it. There is no such predicate if the outcome was
+/// decided by an expression with too few subexpressions, like an
+/// empty `Any`.
+enum Verdict {
+  Satisfied(Option<Predicate<Expanded>>),
+  Rejected(Option<Predicate<Expanded>>),
+}
+
+/// Evaluates a predicate tree, skipping subexpressions that can't
+/// change the outcome.
+///
+/// Subexpressions are evaluated in order, so for example in `And`
+/// the right side is not evaluated if the left side is rejected,
+/// and `Threshold` stops as soon as enough subexpressions are
+/// satisfied or too many are rejected.
+fn evaluate<F>(
+  tree: PredicateTree<Expanded>,
+  leaf: &F,
+) -> Result<Verdict, Error>
+where
+  F: Fn(Predicate<Expanded>) -> Result<Verdict, Error>,
+{
+  Ok(match tree {
+    ExpressionTree::Id(pred) => leaf(pred)?,
+    ExpressionTree::Not(tree) => match evaluate(*tree, leaf)? {
+      Verdict::Satisfied(pred) => Verdict::Rejected(pred.map(negated)),
+      Verdict::Rejected(pred) => Verdict::Satisfied(pred.map(negated)),
+    },
+    ExpressionTree::And(l, r) => evaluate_threshold(2, vec![*l, *r], leaf)?,
+    ExpressionTree::Or(l, r) => evaluate_threshold(1, vec![*l, *r], leaf)?,
+    ExpressionTree::All(trees) => evaluate_threshold(trees.len(), trees, leaf)?,
+    ExpressionTree::Any(trees) => evaluate_threshold(1, trees, leaf)?,
+    ExpressionTree::Threshold(k, trees) => evaluate_threshold(k, trees, leaf)?,
+  })
+}
+
+/// Evaluates subexpressions until at least `k` of them are satisfied or
+/// too many of them are rejected for `k` to be reached.
+fn evaluate_threshold<F>(
+  k: usize,
+  trees: Vec<PredicateTree<Expanded>>,
+  leaf: &F,
+) -> Result<Verdict, Error>
+where
+  F: Fn(Predicate<Expanded>) -> Result<Verdict, Error>,
+{
+  let mut remaining = trees.len();
+  let mut trees = trees.into_iter();
+  let mut satisfied = 0;
+  let mut satisfied_by = None;
+  let mut rejected_by = None;
+
+  while satisfied < k {
+    if satisfied + remaining < k {
+      return Ok(Verdict::Rejected(rejected_by));
+    }
+
+    remaining -= 1;
+    let tree = trees.next().expect("at least one remaining");
+    match evaluate(tree, leaf)? {
+      Verdict::Satisfied(pred) => {
+        satisfied += 1;
+        satisfied_by = satisfied_by.or(pred);
+      }
+      Verdict::Rejected(pred) => rejected_by = rejected_by.or(pred),
+    }
+  }
+
+  Ok(Verdict::Satisfied(satisfied_by))
+}
+
 fn invoke(
   context: &[u8],
   predicate: &Predicate<Expanded>,
@@ -173,7 +369,7 @@ fn invoke(
   let compiler = Cranelift::default();
   let mut store = Store::new(compiler);
 
-  let codehash = Code::Sha3_256.digest(&predicate.code.code);
+  let codehash = Code::hash_bytecode(&predicate.code.code);
   let cachekey = Address::new(format!(
     "/predcache/{}",
     bs58::encode(codehash.to_bytes()).into_string()
@@ -240,26 +436,12 @@ fn invoke(
   }
 }
 
-fn not(
-  val: Result<Predicate<Expanded>, Error>,
-) -> Result<Predicate<Expanded>, Error> {
-  match val {
-    Ok(mut p) => {
-      let mut not: String = "not(".into();
-      not.push_str(&p.code.entrypoint);
-      not.push(')');
-      p.code.entrypoint = not;
-      Err(Error::Rejected(p))
-    }
-    Err(Error::Rejected(mut p)) => {
-      let mut not: String = "not(".into();
-      not.push_str(&p.code.entrypoint);
-      not.push(')');
-      p.code.entrypoint = not;
-      Ok(p)
-    }
-    Err(e) => Err(e),
-  }
+fn negated(mut pred: Predicate<Expanded>) -> Predicate<Expanded> {
+  let mut not: String = "not(".into();
+  not.push_str(&pred.code.entrypoint);
+  not.push(')');
+  pred.code.entrypoint = not;
+  pred
 }
 
 fn and<T>(a: Result<T, Error>, b: Result<T, Error>) -> Result<T, Error> {
@@ -274,18 +456,6 @@ fn and<T>(a: Result<T, Error>, b: Result<T, Error>) -> Result<T, Error> {
   }
 }
 
-fn or<T>(a: Result<T, Error>, b: Result<T, Error>) -> Result<T, Error> {
-  match (a, b) {
-    (Ok(p), Ok(_)) => Ok(p),
-    (Ok(p), Err(Error::Rejected(_))) => Ok(p),
-    (Err(Error::Rejected(_)), Ok(p)) => Ok(p),
-    (Err(Error::Cancelled), Err(e)) => Err(e),
-    (Err(e), Err(Error::Cancelled)) => Err(e),
-    (Err(e), _) => Err(e),
-    (_, Err(e)) => Err(e),
-  }
-}
-
 fn syscalls(store: &mut Store, memory: &Memory) -> Imports {
   let env = FunctionEnv::new(store, memory.clone());
 
@@ -0,0 +1,282 @@
+use {
+  crate::{State, StateDiff},
+  anoma_primitives::{Account, Address},
+  std::{
+    collections::{BTreeMap, HashMap},
+    num::NonZeroU64,
+  },
+  thiserror::Error,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Error {
+  #[error(
+    "State at height {0} is no longer retained. Earliest available height is \
+     {1}"
+  )]
+  Pruned(u64, u64),
+
+  #[error("State at height {0} is not known yet. Latest height is {1}")]
+  FutureHeight(u64, u64),
+
+  #[error("Block height {0} must be greater than the current height {1}")]
+  NonMonotonicHeight(u64, u64),
+}
+
+/// Specifies how many past versions of accounts are kept
+/// by a [`VersionedStateStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pruning {
+  /// Never discard any historical versions of accounts.
+  Archive,
+
+  /// Retain enough versions to answer queries for the
+  /// last N heights, including the current height.
+  KeepRecent(NonZeroU64),
+}
+
+/// A state store that keeps per-height versions of accounts.
+///
+/// Every account change committed at some block height creates a new
+/// version of that account, so the contents of any account can be
+/// queried as they were after any block within the retained history
+/// window.
+///
+/// Implements the [`State`] trait over the most recent version of all
+/// accounts. Diffs applied through [`State::apply`] amend the versions
+/// at the current height, which is useful for seeding genesis state.
+/// Diffs produced by blocks should be recorded using `commit`.
+#[derive(Debug)]
+pub struct VersionedStateStore {
+  height: u64,
+  earliest: u64,
+  pruning: Pruning,
+  versions: HashMap<Address, BTreeMap<u64, Option<Account>>>,
+}
+
+impl VersionedStateStore {
+  /// Creates an empty store that starts at a given block height.
+  pub fn new(height: u64, pruning: Pruning) -> Self {
+    Self {
+      height,
+      earliest: height,
+      pruning,
+      versions: HashMap::new(),
+    }
+  }
+
+  /// Height of the most recently committed block.
+  pub fn height(&self) -> u64 {
+    self.height
+  }
+
+  /// The oldest height that can still be queried using
+  /// [`VersionedStateStore::get_at`].
+  pub fn earliest_height(&self) -> u64 {
+    self.earliest
+  }
+
+  /// Iterate over the most recent version of all existing accounts.
+  pub fn iter(&self) -> impl Iterator<Item = (&Address, &Account)> {
+    self.versions.iter().filter_map(|(addr, versions)| {
+      versions
+        .values()
+        .next_back()
+        .and_then(|acc| acc.as_ref())
+        .map(|acc| (addr, acc))
+    })
+  }
+
+  /// Retreive an account as it was after applying the block at a
+  /// given height.
+  ///
+  /// Returns `Ok(None)` if the account did not exist at that height.
+  pub fn get_at(
+    &self,
+    address: &Address,
+    height: u64,
+  ) -> Result<Option<Account>, Error> {
+    if height > self.height {
+      return Err(Error::FutureHeight(height, self.height));
+    }
+
+    if height < self.earliest {
+      return Err(Error::Pruned(height, self.earliest));
+    }
+
+    Ok(
+      self
+        .versions
+        .get(address)
+        .and_then(|versions| versions.range(..=height).next_back())
+        .and_then(|(_, acc)| acc.clone()),
+    )
+  }
+
+  /// Records all account changes produced by a block at a given height
+  /// as new versions of those accounts, then discards versions that
+  /// fall outside of the retention window.
+  pub fn commit(&mut self, height: u64, diff: StateDiff) -> Result<(), Error> {
+    if height <= self.height {
+      return Err(Error::NonMonotonicHeight(height, self.height));
+    }
+
+    self.height = height;
+    self.record(diff);
+    self.prune();
+    Ok(())
+  }
+
+  fn record(&mut self, diff: StateDiff) {
+    let height = self.height;
+    for (addr, acc) in diff.iter() {
+      self
+        .versions
+        .entry(addr.clone())
+        .or_default()
+        .insert(height, acc.cloned());
+    }
+  }
+
+  /// Drops all account versions that are not needed to answer
+  /// queries within the retained history window.
+  fn prune(&mut self) {
+    let retain = match self.pruning {
+      Pruning::Archive => return,
+      Pruning::KeepRecent(n) => n.get(),
+    };
+
+    let earliest = self.height.saturating_sub(retain - 1);
+    if earliest <= self.earliest {
+      return;
+    }
+    self.earliest = earliest;
+
+    self.versions.retain(|_, versions| {
+      // the newest version at or before the earliest retained
+      // height is the baseline for all queries in the window,
+      // everything older than it is unreachable.
+      if let Some(&baseline) =
+        versions.range(..=earliest).next_back().map(|(h, _)| h)
+      {
+        *versions = versions.split_off(&baseline);
+      }
+
+      // a deleted account with no newer versions is indistinguishable
+      // from an account that never existed.
+      !matches!(
+        versions.iter().next(),
+        Some((_, None)) if versions.len() == 1
+      )
+    });
+  }
+}
+
+impl State for VersionedStateStore {
+  fn get(&self, address: &Address) -> Option<Account> {
+    self
+      .versions
+      .get(address)
+      .and_then(|versions| versions.values().next_back())
+      .and_then(|acc| acc.clone())
+  }
+
+  fn descendants(&self, address: &Address) -> Vec<Address> {
+    let mut output: Vec<_> = self
+      .versions
+      .iter()
+      .filter(|(addr, versions)| {
+        address.is_parent_of(addr)
+          && matches!(versions.values().next_back(), Some(Some(_)))
+      })
+      .map(|(addr, _)| addr.clone())
+      .collect();
+    output.sort();
+    output
+  }
+
+  fn apply(&mut self, diff: StateDiff) {
+    self.record(diff);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{Error, Pruning, VersionedStateStore},
+    crate::{State, StateDiff},
+    anoma_primitives::{Account, Address, Code, Predicate, PredicateTree},
+    std::num::NonZeroU64,
+  };
+
+  fn account_with_state(state: Vec<u8>) -> Account {
+    Account {
+      state,
+      predicates: PredicateTree::Id(Predicate {
+        code: Code::Inline(b"some-code".to_vec()),
+        params: vec![],
+      }),
+    }
+  }
+
+  fn set(addr: &Address, state: Vec<u8>) -> StateDiff {
+    let mut diff = StateDiff::default();
+    diff.set(addr.clone(), account_with_state(state));
+    diff
+  }
+
+  #[test]
+  fn query_past_heights() -> anyhow::Result<()> {
+    let addr: Address = "/token/usdx/alice.eth".parse()?;
+    let mut store = VersionedStateStore::new(0, Pruning::Archive);
+
+    store.commit(1, set(&addr, vec![1]))?;
+    store.commit(2, StateDiff::default())?;
+    store.commit(3, set(&addr, vec![3]))?;
+
+    let mut delete = StateDiff::default();
+    delete.remove(&addr);
+    store.commit(4, delete)?;
+
+    assert_eq!(store.get_at(&addr, 0)?, None);
+    assert_eq!(store.get_at(&addr, 1)?.unwrap().state, vec![1]);
+    assert_eq!(store.get_at(&addr, 2)?.unwrap().state, vec![1]);
+    assert_eq!(store.get_at(&addr, 3)?.unwrap().state, vec![3]);
+    assert_eq!(store.get_at(&addr, 4)?, None);
+    assert_eq!(store.get(&addr), None);
+    assert_eq!(store.get_at(&addr, 5), Err(Error::FutureHeight(5, 4)));
+    assert_eq!(
+      store.commit(4, StateDiff::default()),
+      Err(Error::NonMonotonicHeight(4, 4))
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn pruning() -> anyhow::Result<()> {
+    let addr1: Address = "/addr1".parse()?;
+    let addr2: Address = "/addr2".parse()?;
+    let mut store = VersionedStateStore::new(
+      0,
+      Pruning::KeepRecent(NonZeroU64::new(2).unwrap()),
+    );
+
+    store.apply(set(&addr2, vec![0]));
+    store.commit(1, set(&addr1, vec![1]))?;
+    store.commit(2, set(&addr1, vec![2]))?;
+    store.commit(3, set(&addr1, vec![3]))?;
+
+    assert_eq!(store.earliest_height(), 2);
+    assert_eq!(store.get_at(&addr1, 1), Err(Error::Pruned(1, 2)));
+    assert_eq!(store.get_at(&addr1, 2)?.unwrap().state, vec![2]);
+    assert_eq!(store.get_at(&addr1, 3)?.unwrap().state, vec![3]);
+
+    // unchanged accounts keep their baseline version
+    assert_eq!(store.get_at(&addr2, 2)?.unwrap().state, vec![0]);
+    assert_eq!(store.versions[&addr1].len(), 2);
+    assert_eq!(store.iter().count(), 2);
+
+    Ok(())
+  }
+}
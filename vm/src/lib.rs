@@ -1,11 +1,33 @@
 mod collect;
 mod execution;
+mod history;
+mod rules;
 mod schedule;
 mod state;
 mod syncell;
+mod witness;
+
+pub mod snapshot;
 
 pub use {
-  execution::{execute, Error as RuntimeError},
+  execution::{
+    execute,
+    execute_with_witness,
+    expand,
+    Error as RuntimeError,
+    Expansion,
+  },
+  history::{Error as HistoryError, Pruning, VersionedStateStore},
+  rules::Rules,
   schedule::execute_many,
-  state::{InMemoryStateStore, State, StateDiff},
+  state::{InMemoryStateStore, State, StateDiff, StateView},
+  witness::{
+    AccountProof,
+    Error as WitnessError,
+    MerkleState,
+    Neighbour,
+    RecordingState,
+    StateTree,
+    Witness,
+  },
 };
@@ -0,0 +1,25 @@
+/// Chain-wide rules that transactions are executed under.
+///
+/// They are part of the configuration of a chain, every node of the
+/// chain must execute transactions under the same rules to arrive at
+/// the same state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+  /// Identifier of the chain, transactions with intents signed
+  /// for other chains are rejected.
+  pub chain_id: String,
+
+  /// Enforces the account hierarchy when accounts are created or
+  /// deleted, see [`anoma_primitives::ROOT_ACCOUNT`].
+  pub hierarchy: bool,
+}
+
+impl Rules {
+  /// Rules of a chain with the given id and without hierarchy rules.
+  pub fn new(chain_id: impl Into<String>) -> Self {
+    Self {
+      chain_id: chain_id.into(),
+      hierarchy: false,
+    }
+  }
+}
@@ -2,12 +2,22 @@ use {
   crate::{
     execute,
     execution,
-    state::Overlayed,
     syncell::SynCell,
+    Rules,
     State,
     StateDiff,
+    StateView,
+  },
+  anoma_primitives::{
+    AccountChange,
+    Address,
+    Code,
+    Limits,
+    Param,
+    Predicate,
+    Transaction,
+    ROOT_ACCOUNT,
   },
-  anoma_primitives::{Address, Code, Param, Transaction},
   petgraph::{
     dot,
     prelude::DiGraph,
@@ -29,17 +39,45 @@ use {
 
 /// Runs multiple transactions in parallel, while preserving read/write
 /// dependency ordering. This function is usually called on all transactions
-/// within one block in the blockchain.
+/// within one block in the blockchain, with the height of that block and
+/// under the rules of the chain.
 ///
 /// Produces a list of results that contain either a state diff on successfull
 /// transaction execution or an error explaining why a tx failed. The resulting
 /// collection of results is in the same order as the input txs.
+///
+/// Transactions that fail [`Transaction::validate`] with default limits
+/// are rejected before scheduling, without reading any state.
 pub fn execute_many(
+  height: u64,
+  rules: &Rules,
   state: &dyn State,
   cache: &dyn State,
   txs: impl Iterator<Item = Transaction>,
 ) -> Vec<Result<StateDiff, execution::Error>> {
-  Schedule::new(state, txs).run(state, cache).collect()
+  let limits = Limits::default();
+  let mut valid = vec![];
+  let rejections: Vec<_> = txs
+    .map(|tx| match tx.validate(&limits) {
+      Ok(()) => {
+        valid.push(tx);
+        None
+      }
+      Err(e) => Some(execution::Error::Invalid(e)),
+    })
+    .collect();
+
+  // results of scheduled transactions are in the order of
+  // valid transactions, fill the gaps between rejected ones.
+  let mut executed =
+    Schedule::new(state, valid.into_iter()).run(height, rules, state, cache);
+  rejections
+    .into_iter()
+    .map(|rejection| match rejection {
+      Some(error) => Err(error),
+      None => executed.next().expect("one result per valid transaction"),
+    })
+    .collect()
 }
 
 type NodeType = SynCell<Option<(Transaction, usize)>>;
@@ -66,11 +104,13 @@ impl<'s> std::fmt::Debug for Tree<'s> {
 impl<'s> Tree<'s> {
   pub fn run(
     self,
+    height: u64,
+    rules: &Rules,
     state: &dyn State,
     cache: &dyn State,
   ) -> impl Iterator<Item = (Result<StateDiff, execution::Error>, usize)> {
     let mut txs = vec![];
-    let mut acc_state = StateDiff::default();
+    let mut acc_state = StateView::new(state);
     let mut iter = BfsRows::new(&self.schedule.graph, self.root);
     while let Some(row) = iter.next(&self.schedule.graph) {
       // gather all txs belonging to the same deptree row,
@@ -94,10 +134,9 @@ impl<'s> Tree<'s> {
       // appear in the block.
       //
       // Run all txs on the same level in parallel:
-      let state = Overlayed::new(state, &acc_state);
       let results: Vec<_> = row_txs
         .into_par_iter()
-        .map(|(tx, ix)| (execute(tx, &state, cache), ix))
+        .map(|(tx, ix)| (execute(tx, height, rules, &acc_state, cache), ix))
         .collect();
 
       // accumulate state changes within one tx dependency tree row,
@@ -150,13 +189,17 @@ impl Schedule {
 
   pub fn run(
     self,
+    height: u64,
+    rules: &Rules,
     state: &dyn State,
     cache: &dyn State,
   ) -> impl Iterator<Item = Result<StateDiff, execution::Error>> {
     let mut trees: Vec<(Result<StateDiff, execution::Error>, usize)> = self
       .trees()
       .into_par_iter()
-      .map(|tree: Tree| tree.run(state, cache).collect::<Vec<_>>())
+      .map(|tree: Tree| {
+        tree.run(height, rules, state, cache).collect::<Vec<_>>()
+      })
       .flatten()
       .collect();
 
@@ -229,98 +272,151 @@ impl std::fmt::Debug for Schedule {
 
 /// Specifies the list of all accounts that a transaction will read or write to.
 /// This is used when scheduling transactions for execution in parallel.
+///
+/// Subtree changes write to every account under their roots, including
+/// accounts created by other transactions before they run, so they are
+/// tracked as writes to whole subtrees rather than individual accounts.
 #[derive(Debug, PartialEq, Eq)]
 struct TransactionRefs {
   reads: HashSet<Address>,
   writes: HashSet<Address>,
+  subtrees: HashSet<Address>,
 }
 
 impl TransactionRefs {
   pub fn depends_on(&self, other: &Self) -> bool {
+    let in_subtree = |roots: &HashSet<Address>, addr: &Address| {
+      roots
+        .iter()
+        .any(|root| root == addr || root.is_parent_of(addr))
+    };
+
     self.reads.iter().any(|addr| other.writes.contains(addr))
       || self.writes.iter().any(|addr| other.writes.contains(addr))
+      || self
+        .reads
+        .iter()
+        .chain(&self.writes)
+        .chain(&self.subtrees)
+        .any(|addr| in_subtree(&other.subtrees, addr))
+      || other
+        .writes
+        .iter()
+        .chain(&other.subtrees)
+        .any(|addr| in_subtree(&self.subtrees, addr))
   }
 
   pub fn new(tx: &Transaction, state: &dyn State) -> Self {
     let mut reads = HashSet::new();
     let mut writes = HashSet::new();
+    let mut subtrees = HashSet::new();
 
-    // collect all writes
-    for addr in tx.proposals.keys() {
+    // collect all writes, along with all accounts whose
+    // predicates and ancestors predicates will be evaluated.
+    let mut affected = Vec::new();
+    for (addr, change) in &tx.proposals {
       // add the account that we want to mutate
       writes.insert(addr.clone());
+      affected.push(addr.clone());
+
+      match change {
+        // deleting an account depends on whether it has descendants
+        AccountChange::DeleteAccount => {
+          subtrees.insert(addr.clone());
+        }
+        AccountChange::DeleteSubtree => {
+          subtrees.insert(addr.clone());
+          affected.extend(state.descendants(addr));
+        }
+        AccountChange::MoveSubtree(to) => {
+          subtrees.insert(addr.clone());
+          subtrees.insert(to.clone());
+          affected.extend(state.descendants(addr));
+          affected.push(to.clone());
+        }
+        _ => {}
+      }
+    }
+
+    // on chains with hierarchy rules creating accounts depends on the
+    // existence of their parents, and creating top-level accounts on
+    // the root account, see [`ROOT_ACCOUNT`]. They are tracked under
+    // any rules, a superfluous read only limits parallelism.
+    let root: Address = ROOT_ACCOUNT.parse().expect("valid address");
+    let mut top_level = false;
+    for addr in &affected {
+      let parent = addr.parent().unwrap_or_else(|| {
+        top_level = true;
+        root.clone()
+      });
+      if !writes.contains(&parent) {
+        reads.insert(parent);
+      }
     }
 
     // if an account is both read and write, then
     // it belongs to the "write" subset, because
     // it is what matters when locking state and
     // scheduling concurrent executions of transactions.
+    let mut read_refs = |pred: &Predicate| {
+      for param in &pred.params {
+        if let Param::AccountRef(addr) = param {
+          if !writes.contains(addr) {
+            reads.insert(addr.clone());
+          }
+        };
+      }
+
+      if let Some(addr) = code_account(&pred.code) {
+        if !writes.contains(&addr) {
+          reads.insert(addr);
+        }
+      }
+    };
 
     // collect all reads that will occur when evaluating
     // the validity predicates of the mutatated account and
     // all its ancestors.
-    for addr in tx.proposals.keys() {
+    for addr in &affected {
       // and all references used by its predicates
       if let Some(acc) = state.get(addr) {
-        acc.predicates.for_each(&mut |pred| {
-          for param in &pred.params {
-            if let Param::AccountRef(addr) = param {
-              if !writes.contains(addr) {
-                reads.insert(addr.clone());
-              }
-            };
-          }
+        acc.predicates.for_each(&mut read_refs);
+      }
 
-          if let Code::AccountRef(ref addr, _) = pred.code {
-            if !writes.contains(addr) {
-              reads.insert(addr.clone());
-            }
-          }
-        });
-
-        // then all references used by predicates of all its ancestors
-        for ancestor in addr.ancestors() {
-          if let Some(acc) = state.get(&ancestor) {
-            acc.predicates.for_each(&mut |pred| {
-              for param in &pred.params {
-                if let Param::AccountRef(addr) = param {
-                  if !writes.contains(addr) {
-                    reads.insert(addr.clone());
-                  }
-                };
-              }
-              if let Code::AccountRef(ref addr, _) = pred.code {
-                if !writes.contains(addr) {
-                  reads.insert(addr.clone());
-                }
-              }
-            });
-          }
+      // then all references used by predicates of all its ancestors
+      for ancestor in addr.ancestors() {
+        if let Some(acc) = state.get(&ancestor) {
+          acc.predicates.for_each(&mut read_refs);
         }
       }
     }
 
+    if top_level {
+      if let Some(acc) = state.get(&root) {
+        acc.predicates.for_each(&mut read_refs);
+      }
+    }
+
     // collect all reads that will occur when evaluating
     // intent predicates.
     for intent in &tx.intents {
-      intent.expectations.for_each(&mut |pred| {
-        for param in &pred.params {
-          if let Param::AccountRef(addr) = param {
-            if !writes.contains(addr) {
-              reads.insert(addr.clone());
-            }
-          };
-        }
+      intent.expectations.for_each(&mut read_refs);
+    }
 
-        if let Code::AccountRef(ref addr, _) = pred.code {
-          if !writes.contains(addr) {
-            reads.insert(addr.clone());
-          }
-        }
-      })
+    Self {
+      reads,
+      writes,
+      subtrees,
     }
+  }
+}
 
-    Self { reads, writes }
+/// The account that stores the bytecode of a predicate, if any.
+fn code_account(code: &Code) -> Option<Address> {
+  match code {
+    Code::Inline(_) => None,
+    Code::AccountRef(addr, _) => Some(addr.clone()),
+    Code::Hash(hash, _) => Some(Code::registry_address(hash)),
   }
 }
 
@@ -0,0 +1,562 @@
+//! Defines the binary format of state snapshots.
+//!
+//! A snapshot captures all accounts of the chain state as of some block,
+//! along with that block and the root of the captured state. It is used to seed
+//! new nodes and test environments from a known chain state without replaying
+//! all blocks since genesis.
+//!
+//! Snapshot layout:
+//!
+//! ```text
+//! magic: b"ANOMASNP"
+//! version: u8
+//! frame(Header)
+//! frame(Chunk) * N
+//! frame(Trailer)
+//! ```
+//!
+//! Each frame is a big-endian u32 length of the MessagePack-encoded section,
+//! followed by the section bytes and a 32-byte Sha3-256 checksum of those
+//! bytes. Chunks carry a bounded number of accounts, so both exporting and
+//! importing can be done in a streaming fashion without holding the entire
+//! state in memory. The trailer carries the total number of chunks and
+//! accounts and a digest of all chunks checksums, so a truncated or
+//! reordered snapshot is detected at import time.
+//!
+//! Checksums only detect corruption, anyone can recompute them. The header
+//! carries the root of the state after applying the snapshot block, which
+//! is the state root that the next block of the chain must carry, see
+//! [`anoma_primitives::BlockHeader::state_root`]. Imported accounts are
+//! verified against it, and it should be checked against a trusted source,
+//! such as the next block signed by a validator, before the imported state
+//! is trusted.
+
+use {
+  crate::{
+    witness::{account_hash, StateTree},
+    State,
+    StateDiff,
+  },
+  anoma_primitives::{Account, Address, Block, Digest},
+  multihash::{Hasher, Multihash, MultihashDigest, Sha3_256},
+  serde::{Deserialize, Serialize},
+  std::{
+    borrow::Borrow,
+    collections::BTreeMap,
+    io::{Read, Write},
+    num::NonZeroUsize,
+  },
+  thiserror::Error,
+};
+
+/// Identifies a byte stream as an Anoma state snapshot.
+pub const MAGIC: &[u8; 8] = b"ANOMASNP";
+
+/// Version of the snapshot format produced by this module.
+pub const VERSION: u8 = 3;
+
+/// Number of accounts per chunk used by [`export`].
+pub const DEFAULT_CHUNK_SIZE: usize = 1024;
+
+/// Upper bound on the size of a single frame. Protects readers from
+/// allocating arbitrary amounts of memory on corrupted length prefixes.
+const MAX_FRAME_LEN: usize = 256 * 1024 * 1024;
+
+const CHECKSUM_LEN: usize = 32;
+
+#[derive(Debug, Error)]
+pub enum Error {
+  #[error("Snapshot I/O error: {0}")]
+  Io(#[from] std::io::Error),
+
+  #[error("Failed to encode snapshot section: {0}")]
+  Encoding(#[from] rmp_serde::encode::Error),
+
+  #[error("Failed to decode snapshot section: {0}")]
+  Decoding(#[from] rmp_serde::decode::Error),
+
+  #[error("Not an Anoma state snapshot")]
+  InvalidMagic,
+
+  #[error("Unsupported snapshot format version {0}")]
+  UnsupportedVersion(u8),
+
+  #[error("Snapshot frame of {0} bytes exceeds the maximum frame size")]
+  FrameTooLarge(usize),
+
+  #[error("Checksum mismatch in snapshot frame #{0}")]
+  ChecksumMismatch(u64),
+
+  #[error("Unexpected snapshot section, expected {0}")]
+  UnexpectedSection(&'static str),
+
+  #[error(
+    "Snapshot trailer declares {0} chunks and {1} accounts, but {2} chunks \
+     and {3} accounts were read"
+  )]
+  CountMismatch(u64, u64, u64, u64),
+
+  #[error("Snapshot chunks digest does not match the trailer")]
+  DigestMismatch,
+
+  #[error("Snapshot accounts do not match the declared state root {0:?}")]
+  StateRootMismatch(Multihash),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Section {
+  /// The snapshot block and the root of the state after it.
+  Header(Box<Block>, Multihash),
+  Chunk(Vec<(Address, Account)>),
+  Trailer(Summary),
+}
+
+/// Describes the contents of a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+  /// Number of account chunks in the snapshot.
+  pub chunks: u64,
+
+  /// Total number of accounts in the snapshot.
+  pub accounts: u64,
+
+  /// Sha3-256 hash of the concatenated checksums of all chunks.
+  pub digest: Multihash,
+}
+
+/// Streams accounts into a snapshot.
+///
+/// Accounts are buffered until a full chunk is accumulated, then the
+/// chunk is written to the underlying writer. Call [`Writer::finish`]
+/// after all accounts are pushed to write the remaining accounts and
+/// the snapshot trailer.
+pub struct Writer<W: Write> {
+  writer: W,
+  chunk_size: usize,
+  pending: Vec<(Address, Account)>,
+  chunks: u64,
+  accounts: u64,
+  digest: Sha3_256,
+}
+
+impl<W: Write> Writer<W> {
+  /// Starts a new snapshot of the state as of the given block.
+  ///
+  /// The state root is the root of all accounts that will be
+  /// pushed to the snapshot, see [`StateTree::root`].
+  pub fn new(
+    mut writer: W,
+    block: &Block,
+    state_root: Multihash,
+    chunk_size: NonZeroUsize,
+  ) -> Result<Self, Error> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    write_frame(
+      &mut writer,
+      &Section::Header(Box::new(block.clone()), state_root),
+    )?;
+
+    Ok(Self {
+      writer,
+      chunk_size: chunk_size.get(),
+      pending: Vec::with_capacity(chunk_size.get()),
+      chunks: 0,
+      accounts: 0,
+      digest: Sha3_256::default(),
+    })
+  }
+
+  /// Adds an account to the snapshot.
+  pub fn push(
+    &mut self,
+    address: Address,
+    account: Account,
+  ) -> Result<(), Error> {
+    self.pending.push((address, account));
+    if self.pending.len() == self.chunk_size {
+      self.flush_chunk()?;
+    }
+    Ok(())
+  }
+
+  /// Writes all remaining accounts and the snapshot trailer.
+  pub fn finish(mut self) -> Result<Summary, Error> {
+    if !self.pending.is_empty() {
+      self.flush_chunk()?;
+    }
+
+    let summary = Summary {
+      chunks: self.chunks,
+      accounts: self.accounts,
+      digest: finalize(&mut self.digest),
+    };
+
+    write_frame(&mut self.writer, &Section::Trailer(summary.clone()))?;
+    self.writer.flush()?;
+    Ok(summary)
+  }
+
+  fn flush_chunk(&mut self) -> Result<(), Error> {
+    let accounts =
+      std::mem::replace(&mut self.pending, Vec::with_capacity(self.chunk_size));
+    self.accounts += accounts.len() as u64;
+    let checksum = write_frame(&mut self.writer, &Section::Chunk(accounts))?;
+    self.digest.update(&checksum);
+    self.chunks += 1;
+    Ok(())
+  }
+}
+
+/// Reads and verifies a snapshot chunk by chunk.
+pub struct Reader<R: Read> {
+  reader: R,
+  block: Block,
+  state_root: Multihash,
+  hashes: BTreeMap<Address, Digest>,
+  frames: u64,
+  chunks: u64,
+  accounts: u64,
+  digest: Sha3_256,
+  summary: Option<Summary>,
+}
+
+impl<R: Read> Reader<R> {
+  /// Reads the snapshot preamble and the block that the
+  /// snapshotted state corresponds to.
+  pub fn new(mut reader: R) -> Result<Self, Error> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(Error::InvalidMagic);
+    }
+
+    let mut version = [0u8; 1];
+    reader.read_exact(&mut version)?;
+    if version[0] != VERSION {
+      return Err(Error::UnsupportedVersion(version[0]));
+    }
+
+    let (block, state_root) = match read_frame(&mut reader, 0)?.0 {
+      Section::Header(block, state_root) => (*block, state_root),
+      _ => return Err(Error::UnexpectedSection("header")),
+    };
+
+    Ok(Self {
+      reader,
+      block,
+      state_root,
+      hashes: BTreeMap::new(),
+      frames: 1,
+      chunks: 0,
+      accounts: 0,
+      digest: Sha3_256::default(),
+      summary: None,
+    })
+  }
+
+  /// The block that the snapshotted state corresponds to.
+  pub fn block(&self) -> &Block {
+    &self.block
+  }
+
+  /// Root of the state after applying the snapshot block. It is
+  /// the state root that the next block of the chain must carry.
+  pub fn state_root(&self) -> &Multihash {
+    &self.state_root
+  }
+
+  /// Reads the next chunk of accounts.
+  ///
+  /// Returns `None` once the trailer is reached and all chunks were
+  /// verified against it and against the declared state root.
+  pub fn next_chunk(&mut self) -> Result<Option<StateDiff>, Error> {
+    if self.summary.is_some() {
+      return Ok(None);
+    }
+
+    let (section, checksum) = read_frame(&mut self.reader, self.frames)?;
+    self.frames += 1;
+
+    match section {
+      Section::Chunk(accounts) => {
+        self.digest.update(&checksum);
+        self.chunks += 1;
+        self.accounts += accounts.len() as u64;
+
+        let mut diff = StateDiff::default();
+        for (addr, acc) in accounts {
+          self.hashes.insert(addr.clone(), account_hash(&acc));
+          diff.set(addr, acc);
+        }
+        Ok(Some(diff))
+      }
+      Section::Trailer(summary) => {
+        if summary.chunks != self.chunks || summary.accounts != self.accounts {
+          return Err(Error::CountMismatch(
+            summary.chunks,
+            summary.accounts,
+            self.chunks,
+            self.accounts,
+          ));
+        }
+
+        if summary.digest != finalize(&mut self.digest) {
+          return Err(Error::DigestMismatch);
+        }
+
+        let hashes = std::mem::take(&mut self.hashes);
+        if StateTree::from_hashes(hashes).root() != self.state_root {
+          return Err(Error::StateRootMismatch(self.state_root));
+        }
+
+        self.summary = Some(summary);
+        Ok(None)
+      }
+      Section::Header(..) => Err(Error::UnexpectedSection("chunk or trailer")),
+    }
+  }
+
+  /// Available after all chunks were read.
+  pub fn summary(&self) -> Option<&Summary> {
+    self.summary.as_ref()
+  }
+}
+
+/// Writes a snapshot of all given accounts as of some block.
+///
+/// Accepts any iterable collection of accounts, for example the
+/// output of [`crate::InMemoryStateStore::iter`], along with the
+/// root of the state that they form.
+pub fn export<A, C>(
+  block: &Block,
+  state_root: Multihash,
+  accounts: impl IntoIterator<Item = (A, C)>,
+  writer: impl Write,
+) -> Result<Summary, Error>
+where
+  A: Borrow<Address>,
+  C: Borrow<Account>,
+{
+  let mut writer = Writer::new(
+    writer,
+    block,
+    state_root,
+    NonZeroUsize::new(DEFAULT_CHUNK_SIZE).expect("non-zero constant"),
+  )?;
+
+  for (addr, acc) in accounts {
+    writer.push(addr.borrow().clone(), acc.borrow().clone())?;
+  }
+
+  writer.finish()
+}
+
+/// Reads a snapshot and applies all its accounts to a state store.
+///
+/// Every chunk is verified before it is applied, and all accounts are
+/// verified against the state root once all chunks are read. If the
+/// snapshot is corrupted, the chunks preceding the failure are already
+/// applied, so snapshots should be imported into empty stores that are
+/// discarded on failure.
+///
+/// Returns the block that the imported state corresponds to and the
+/// root of the imported state.
+pub fn import(
+  reader: impl Read,
+  target: &mut dyn State,
+) -> Result<(Block, Multihash), Error> {
+  let mut reader = Reader::new(reader)?;
+  while let Some(chunk) = reader.next_chunk()? {
+    target.apply(chunk);
+  }
+  Ok((reader.block, reader.state_root))
+}
+
+/// Writes a section frame and returns the checksum of its contents.
+fn write_frame(
+  writer: &mut impl Write,
+  section: &Section,
+) -> Result<[u8; CHECKSUM_LEN], Error> {
+  let bytes = rmp_serde::to_vec(section)?;
+  if bytes.len() > MAX_FRAME_LEN {
+    return Err(Error::FrameTooLarge(bytes.len()));
+  }
+  let checksum = checksum(&bytes);
+
+  writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+  writer.write_all(&bytes)?;
+  writer.write_all(&checksum)?;
+  Ok(checksum)
+}
+
+/// Reads a section frame and verifies its checksum.
+fn read_frame(
+  reader: &mut impl Read,
+  index: u64,
+) -> Result<(Section, [u8; CHECKSUM_LEN]), Error> {
+  let mut len = [0u8; 4];
+  reader.read_exact(&mut len)?;
+  let len = u32::from_be_bytes(len) as usize;
+  if len > MAX_FRAME_LEN {
+    return Err(Error::FrameTooLarge(len));
+  }
+
+  let mut bytes = vec![0u8; len];
+  reader.read_exact(&mut bytes)?;
+
+  let mut expected = [0u8; CHECKSUM_LEN];
+  reader.read_exact(&mut expected)?;
+  if checksum(&bytes) != expected {
+    return Err(Error::ChecksumMismatch(index));
+  }
+
+  Ok((rmp_serde::from_slice(&bytes)?, expected))
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+  let mut hasher = Sha3_256::default();
+  hasher.update(bytes);
+  let mut output = [0u8; CHECKSUM_LEN];
+  output.copy_from_slice(hasher.finalize());
+  output
+}
+
+fn finalize(hasher: &mut Sha3_256) -> Multihash {
+  multihash::Code::Sha3_256
+    .wrap(hasher.finalize())
+    .expect("hash length matches hashcode")
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{export, import, Error, Reader, Writer},
+    crate::{InMemoryStateStore, State, StateDiff, StateTree},
+    anoma_primitives::{
+      Account,
+      Address,
+      Block,
+      Code,
+      Predicate,
+      PredicateTree,
+    },
+    ed25519_dalek::Keypair,
+    rand::rngs::OsRng,
+    std::num::NonZeroUsize,
+  };
+
+  fn account_with_state(state: Vec<u8>) -> Account {
+    Account {
+      state,
+      predicates: PredicateTree::Id(Predicate {
+        code: Code::Inline(b"some-code".to_vec()),
+        params: vec![],
+      }),
+    }
+  }
+
+  fn populated_store(count: usize) -> InMemoryStateStore {
+    let mut diff = StateDiff::default();
+    for i in 0..count {
+      diff.set(
+        Address::new(format!("/test/addr{i}")).unwrap(),
+        account_with_state(vec![i as u8]),
+      );
+    }
+    let mut store = InMemoryStateStore::default();
+    store.apply(diff);
+    store
+  }
+
+  #[test]
+  fn export_import_roundtrip() -> anyhow::Result<()> {
+    let source = populated_store(10);
+    let proposer = Keypair::generate(&mut OsRng);
+    let genesis = Block::genesis(vec![proposer.public]);
+    let block = Block::new(
+      &genesis.header,
+      1,
+      genesis.header.state_root,
+      &proposer,
+      vec![],
+    );
+
+    let root = StateTree::new(source.iter()).root();
+    let mut bytes = vec![];
+    let mut writer =
+      Writer::new(&mut bytes, &block, root, NonZeroUsize::new(3).unwrap())?;
+    for (addr, acc) in source.iter() {
+      writer.push(addr.clone(), acc.clone())?;
+    }
+    let summary = writer.finish()?;
+
+    assert_eq!(summary.chunks, 4);
+    assert_eq!(summary.accounts, 10);
+
+    let mut target = InMemoryStateStore::default();
+    let (imported, imported_root) = import(bytes.as_slice(), &mut target)?;
+
+    assert_eq!(imported.hash(), block.hash());
+    assert_eq!(imported_root, root);
+    assert_eq!(target.iter().count(), 10);
+    for (addr, acc) in source.iter() {
+      assert_eq!(target.get(addr).as_ref(), Some(acc));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn corrupted_snapshots() -> anyhow::Result<()> {
+    let source = populated_store(5);
+    let root = StateTree::new(source.iter()).root();
+    let mut bytes = vec![];
+    export(&Block::zero(), root, source.iter(), &mut bytes)?;
+
+    // flipped bit in the first chunk, right after the header frame
+    let header_len = u32::from_be_bytes(bytes[9..13].try_into()?) as usize;
+    let chunk_offset = 9 + 4 + header_len + 32;
+    let mut corrupted = bytes.clone();
+    corrupted[chunk_offset + 4 + 10] ^= 1;
+    assert!(matches!(
+      import(corrupted.as_slice(), &mut InMemoryStateStore::default()),
+      Err(Error::ChecksumMismatch(1))
+    ));
+
+    // truncated stream
+    let truncated = &bytes[..bytes.len() - 10];
+    assert!(matches!(
+      import(truncated, &mut InMemoryStateStore::default()),
+      Err(Error::Io(_))
+    ));
+
+    // unknown version
+    let mut versioned = bytes.clone();
+    versioned[8] = 42;
+    assert!(matches!(
+      Reader::new(versioned.as_slice()),
+      Err(Error::UnsupportedVersion(42))
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn rejects_accounts_not_matching_state_root() -> anyhow::Result<()> {
+    let source = populated_store(5);
+
+    // the snapshot is well formed and all checksums match,
+    // but its accounts are not the declared state.
+    let declared = StateTree::new(populated_store(4).iter()).root();
+    let mut bytes = vec![];
+    export(&Block::zero(), declared, source.iter(), &mut bytes)?;
+
+    assert!(matches!(
+      import(bytes.as_slice(), &mut InMemoryStateStore::default()),
+      Err(Error::StateRootMismatch(root)) if root == declared
+    ));
+
+    Ok(())
+  }
+}
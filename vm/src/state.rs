@@ -1,7 +1,11 @@
 use {
   anoma_primitives::{Account, Address},
+  im::{ordmap::DiffItem, OrdMap},
   serde::{Deserialize, Serialize},
-  std::collections::{BTreeMap, BTreeSet, HashMap},
+  std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound::{Excluded, Unbounded},
+  },
 };
 
 /// Represents a change in Blockchain Accounts state.
@@ -60,6 +64,33 @@ impl StateDiff {
     StateDiff { upserts, deletes }
   }
 
+  /// Retreives the change to an account under a given address.
+  ///
+  /// Returns `None` if the diff does not change this account,
+  /// `Some(None)` if the account is removed by this diff, or
+  /// `Some(Some(account))` if the account is created or updated.
+  pub fn change(&self, address: &Address) -> Option<Option<&Account>> {
+    match self.upserts.get(address) {
+      Some(acc) => Some(Some(acc)),
+      None => self.deletes.contains(address).then_some(None),
+    }
+  }
+
+  /// Descendants of an address in a state with descendants `base`
+  /// after this diff is applied to it.
+  pub(crate) fn descendants_over(
+    &self,
+    base: Vec<Address>,
+    address: &Address,
+  ) -> Vec<Address> {
+    let mut output: BTreeSet<_> = base
+      .into_iter()
+      .filter(|addr| !self.deletes.contains(addr))
+      .collect();
+    output.extend(self.descendants(address));
+    output.into_iter().collect()
+  }
+
   /// Iterate over all account changes in a state diff.
   ///
   /// There are two variants of changes:
@@ -81,6 +112,15 @@ impl State for StateDiff {
     self.upserts.get(address).cloned()
   }
 
+  fn descendants(&self, address: &Address) -> Vec<Address> {
+    self
+      .upserts
+      .keys()
+      .filter(|addr| address.is_parent_of(addr))
+      .cloned()
+      .collect()
+  }
+
   fn apply(&mut self, diff: StateDiff) {
     *self = std::mem::take(self).merge(diff);
   }
@@ -91,53 +131,176 @@ pub trait State: Sync + Send {
   /// Retreive an account by its address.
   fn get(&self, address: &Address) -> Option<Account>;
 
+  /// Addresses of all existing accounts in the subtree under an address,
+  /// not including the address itself, in ascending order.
+  fn descendants(&self, address: &Address) -> Vec<Address>;
+
   /// Apply changes from a statediff to the accounts data store.
   fn apply(&mut self, diff: StateDiff);
 }
 
-/// Represents a view of two overlayed states without modifying any of them.
+/// A copy-on-write view over some base state.
+///
+/// Changes applied to a view are recorded in a stack of layers on top of
+/// the base state, the base state itself is never modified. This is useful
+/// for speculative execution, for example when a solver wants to try
+/// candidate transactions on top of pending ones and throw them away.
 ///
-/// The entire state of the chain can be represented as a chain
-/// of combined partial states produced by all transactions or blocks
-/// executed in order.
-pub struct Overlayed<'s1, 's2> {
-  base: &'s1 dyn State,
-  overlay: &'s2 dyn State,
+/// Nested layers are started with [`StateView::nest`] and are either merged
+/// into their enclosing layer using [`StateView::commit`] or thrown away
+/// using [`StateView::discard`]. All accumulated changes can be collected
+/// as one state diff and applied to the base state once the view is
+/// released. Views can also be stacked by creating a new view over an
+/// existing one.
+pub struct StateView<'s> {
+  base: &'s dyn State,
+  layers: Vec<StateDiff>,
 }
 
-impl<'s1, 's2> Overlayed<'s1, 's2> {
-  /// Creates a new combines state view
-  pub fn new(base: &'s1 dyn State, overlay: &'s2 dyn State) -> Self {
-    Self { base, overlay }
+impl<'s> StateView<'s> {
+  /// Creates a new view with no changes over a base state.
+  pub fn new(base: &'s dyn State) -> Self {
+    Self {
+      base,
+      layers: vec![StateDiff::default()],
+    }
+  }
+
+  /// Starts a new nested layer of changes.
+  ///
+  /// All changes applied from now on are recorded in the new layer until
+  /// it is committed or discarded.
+  pub fn nest(&mut self) {
+    self.layers.push(StateDiff::default());
+  }
+
+  /// Number of nested layers on top of the outermost one.
+  pub fn depth(&self) -> usize {
+    self.layers.len() - 1
+  }
+
+  /// Merges the innermost nested layer into its enclosing layer.
+  ///
+  /// Has no effect if there are no nested layers.
+  pub fn commit(&mut self) {
+    if self.layers.len() > 1 {
+      let top = self.layers.pop().expect("more than one layer");
+      self.top().apply(top);
+    }
+  }
+
+  /// Throws away all changes in the innermost layer.
+  ///
+  /// If there are no nested layers, then all changes in the view are
+  /// thrown away and the view is equivalent to its base state again.
+  pub fn discard(&mut self) {
+    if self.layers.len() > 1 {
+      self.layers.pop();
+    } else {
+      *self.top() = StateDiff::default();
+    }
+  }
+
+  /// Returns all changes from all layers of this view merged together.
+  pub fn diff(&self) -> StateDiff {
+    self
+      .layers
+      .iter()
+      .cloned()
+      .reduce(StateDiff::merge)
+      .unwrap_or_default()
+  }
+
+  /// Releases the base state and returns all changes
+  /// from all layers of this view merged together.
+  pub fn into_diff(self) -> StateDiff {
+    self
+      .layers
+      .into_iter()
+      .reduce(StateDiff::merge)
+      .unwrap_or_default()
+  }
+
+  fn top(&mut self) -> &mut StateDiff {
+    self.layers.last_mut().expect("at least one layer")
   }
 }
 
-impl<'s1, 's2> State for Overlayed<'s1, 's2> {
-  /// Retreives a value at a given key, first tries to get it from
-  /// the overlay and then the base state.
+impl<'s> State for StateView<'s> {
+  /// Retreives an account from the innermost layer that changes it,
+  /// otherwise from the base state.
   fn get(&self, address: &Address) -> Option<Account> {
-    match self.overlay.get(address) {
-      None => self.base.get(address),
-      Some(value) => Some(value),
+    for layer in self.layers.iter().rev() {
+      if let Some(change) = layer.change(address) {
+        return change.cloned();
+      }
     }
+    self.base.get(address)
   }
 
-  fn apply(&mut self, _: StateDiff) {
-    unimplemented!("this state type is read only");
+  fn descendants(&self, address: &Address) -> Vec<Address> {
+    self
+      .layers
+      .iter()
+      .fold(self.base.descendants(address), |base, layer| {
+        layer.descendants_over(base, address)
+      })
+  }
+
+  /// Records changes in the innermost layer of the view.
+  fn apply(&mut self, diff: StateDiff) {
+    self.top().apply(diff);
   }
 }
 
 /// This store is used in testing and other short-lived
 /// scenarios such as simulators or SDK examples.
-#[derive(Debug, Default)]
+///
+/// Accounts are kept in a persistent immutable map, so cloning the
+/// store is O(1) and the clone shares all its structure with the
+/// original. This makes it cheap to snapshot and fork the chain state
+/// and to compute diffs between any two snapshots.
+#[derive(Debug, Clone, Default)]
 pub struct InMemoryStateStore {
-  data: HashMap<Address, Account>,
+  data: OrdMap<Address, Account>,
 }
 
 impl InMemoryStateStore {
   pub fn iter(&self) -> impl Iterator<Item = (&Address, &Account)> {
     self.data.iter()
   }
+
+  /// Takes an O(1) snapshot of the store.
+  ///
+  /// The snapshot is an independent store, changes applied to either
+  /// of them are not visible in the other, so it may also be used as
+  /// a fork of the current state.
+  pub fn snapshot(&self) -> Self {
+    self.clone()
+  }
+
+  /// Produces a state diff that turns this store into a newer store.
+  ///
+  /// Parts of the state that are shared between the two stores, such as
+  /// an older snapshot and its descendants, are never visited, so the
+  /// cost is proportional to the number of changed accounts.
+  pub fn diff(&self, newer: &InMemoryStateStore) -> StateDiff {
+    let mut diff = StateDiff::default();
+    for item in self.data.diff(&newer.data) {
+      match item {
+        DiffItem::Add(addr, acc)
+        | DiffItem::Update {
+          new: (addr, acc), ..
+        } => {
+          diff.set(addr.clone(), acc.clone());
+        }
+        DiffItem::Remove(addr, _) => {
+          diff.remove(addr);
+        }
+      }
+    }
+    diff
+  }
 }
 
 impl State for InMemoryStateStore {
@@ -145,6 +308,20 @@ impl State for InMemoryStateStore {
     self.data.get(address).cloned()
   }
 
+  fn descendants(&self, address: &Address) -> Vec<Address> {
+    // all descendants share the address as a string prefix, and
+    // all addresses with this prefix are next to each other.
+    let prefix = address.to_string();
+    self
+      .data
+      .range((Excluded(address), Unbounded))
+      .map(|(addr, _)| addr)
+      .take_while(|addr| addr.to_string().starts_with(&prefix))
+      .filter(|addr| address.is_parent_of(addr))
+      .cloned()
+      .collect()
+  }
+
   fn apply(&mut self, diff: StateDiff) {
     for (k, v) in diff.upserts {
       self.data.insert(k, v);
@@ -159,7 +336,7 @@ impl State for InMemoryStateStore {
 #[cfg(test)]
 mod tests {
   use {
-    crate::{state::StateDiff, InMemoryStateStore, State},
+    crate::{state::StateDiff, InMemoryStateStore, State, StateView},
     anoma_primitives::{
       Account,
       Address,
@@ -168,6 +345,7 @@ mod tests {
       Predicate,
       PredicateTree,
     },
+    proptest::prelude::*,
   };
 
   fn account_with_state(state: Vec<u8>) -> Account {
@@ -286,4 +464,198 @@ mod tests {
 
     Ok(())
   }
+
+  #[test]
+  fn nested_views() -> Result<(), AddressError> {
+    let mut store = InMemoryStateStore::default();
+    let mut genesis = StateDiff::default();
+    genesis.set("/addr1".parse()?, account_with_state(vec![1]));
+    genesis.set("/addr2".parse()?, account_with_state(vec![2]));
+    store.apply(genesis);
+
+    let mut view = StateView::new(&store);
+    let mut pending = StateDiff::default();
+    pending.set("/addr1".parse()?, account_with_state(vec![10]));
+    view.apply(pending);
+
+    // speculative layer that gets thrown away
+    view.nest();
+    let mut attempt = StateDiff::default();
+    attempt.remove(&"/addr2".parse()?);
+    attempt.set("/addr3".parse()?, account_with_state(vec![3]));
+    view.apply(attempt);
+
+    assert_eq!(view.depth(), 1);
+    assert!(view.get(&"/addr2".parse()?).is_none());
+    assert!(view.get(&"/addr3".parse()?).is_some());
+
+    view.discard();
+    assert_eq!(view.depth(), 0);
+    assert_eq!(view.get(&"/addr2".parse()?).unwrap().state, vec![2]);
+    assert!(view.get(&"/addr3".parse()?).is_none());
+
+    // speculative layer that gets committed
+    view.nest();
+    let mut attempt = StateDiff::default();
+    attempt.remove(&"/addr2".parse()?);
+    view.apply(attempt);
+    view.commit();
+
+    assert_eq!(view.depth(), 0);
+    assert!(view.get(&"/addr2".parse()?).is_none());
+    assert_eq!(view.get(&"/addr1".parse()?).unwrap().state, vec![10]);
+
+    // the base state is untouched until the diff is applied
+    assert_eq!(store.get(&"/addr1".parse()?).unwrap().state, vec![1]);
+    assert!(store.get(&"/addr2".parse()?).is_some());
+
+    let diff = view.into_diff();
+    store.apply(diff);
+
+    assert_eq!(store.get(&"/addr1".parse()?).unwrap().state, vec![10]);
+    assert!(store.get(&"/addr2".parse()?).is_none());
+
+    Ok(())
+  }
+
+  #[test]
+  fn snapshots_and_forks() -> Result<(), AddressError> {
+    let mut store = InMemoryStateStore::default();
+    let mut genesis = StateDiff::default();
+    for ix in 0..100u8 {
+      genesis.set(format!("/addr{ix}").parse()?, account_with_state(vec![ix]));
+    }
+    store.apply(genesis);
+
+    let snapshot = store.snapshot();
+    let mut fork = store.snapshot();
+
+    let mut change = StateDiff::default();
+    change.set("/addr1".parse()?, account_with_state(vec![42]));
+    change.set("/new".parse()?, account_with_state(vec![1]));
+    change.remove(&"/addr2".parse()?);
+    fork.apply(change);
+
+    // forks are independent of the original and its snapshots
+    assert_eq!(store.get(&"/addr1".parse()?).unwrap().state, vec![1]);
+    assert_eq!(snapshot.get(&"/addr1".parse()?).unwrap().state, vec![1]);
+    assert_eq!(fork.get(&"/addr1".parse()?).unwrap().state, vec![42]);
+    assert!(store.get(&"/addr2".parse()?).is_some());
+    assert!(fork.get(&"/addr2".parse()?).is_none());
+
+    let diff = snapshot.diff(&fork);
+    let changes: Vec<_> = diff
+      .iter()
+      .map(|(a, acc)| (a.clone(), acc.is_some()))
+      .collect();
+    assert_eq!(changes, vec![
+      ("/addr1".parse()?, true),
+      ("/new".parse()?, true),
+      ("/addr2".parse()?, false),
+    ]);
+    assert_eq!(snapshot.diff(&store).iter().count(), 0);
+
+    // replaying the diff on the snapshot yields the fork
+    let mut replayed = snapshot.snapshot();
+    replayed.apply(diff);
+    assert_eq!(replayed.diff(&fork).iter().count(), 0);
+    assert_eq!(fork.diff(&snapshot).iter().count(), 3);
+
+    Ok(())
+  }
+
+  #[test]
+  fn descendants() -> Result<(), AddressError> {
+    let addrs = |list: &[&str]| -> Result<Vec<Address>, AddressError> {
+      list.iter().map(|a| a.parse()).collect()
+    };
+
+    let mut store = InMemoryStateStore::default();
+    let mut diff = StateDiff::default();
+    for addr in ["/a", "/a-b", "/a/x", "/a/x/y", "/ab", "/a/z", "/b"] {
+      diff.set(addr.parse()?, account_with_state(vec![]));
+    }
+    store.apply(diff);
+
+    assert_eq!(
+      store.descendants(&"/a".parse()?),
+      addrs(&["/a/x", "/a/x/y", "/a/z"])?
+    );
+    assert_eq!(store.descendants(&"/a/x".parse()?), addrs(&["/a/x/y"])?);
+    assert!(store.descendants(&"/a/z".parse()?).is_empty());
+    assert!(store.descendants(&"/c".parse()?).is_empty());
+
+    let mut view = StateView::new(&store);
+    let mut diff = StateDiff::default();
+    diff.remove(&"/a/x/y".parse()?);
+    diff.set("/a/w".parse()?, account_with_state(vec![]));
+    view.apply(diff);
+    view.nest();
+    let mut diff = StateDiff::default();
+    diff.set("/a/x/y".parse()?, account_with_state(vec![]));
+    diff.remove(&"/a/z".parse()?);
+    view.apply(diff);
+
+    assert_eq!(
+      view.descendants(&"/a".parse()?),
+      addrs(&["/a/w", "/a/x", "/a/x/y"])?
+    );
+    view.discard();
+    assert_eq!(
+      view.descendants(&"/a".parse()?),
+      addrs(&["/a/w", "/a/x", "/a/z"])?
+    );
+
+    Ok(())
+  }
+
+  /// Diffs over a small set of addresses, so that
+  /// merged diffs often touch the same accounts.
+  fn arbitrary_diff() -> impl Strategy<Value = StateDiff> {
+    const ADDRESSES: [&str; 4] = ["/a", "/a/b", "/a/b/c", "/d"];
+    prop::collection::vec((0..ADDRESSES.len(), any::<Option<u8>>()), 0..8)
+      .prop_map(|changes| {
+        let mut diff = StateDiff::default();
+        for (ix, change) in changes {
+          let address = ADDRESSES[ix].parse().unwrap();
+          match change {
+            Some(state) => diff.set(address, account_with_state(vec![state])),
+            None => diff.remove(&address),
+          };
+        }
+        diff
+      })
+  }
+
+  fn changes(diff: &StateDiff) -> Vec<(&Address, Option<&Account>)> {
+    diff.iter().collect()
+  }
+
+  proptest! {
+    #[test]
+    fn statediff_merge_is_associative(
+      a in arbitrary_diff(),
+      b in arbitrary_diff(),
+      c in arbitrary_diff(),
+    ) {
+      let left = a.clone().merge(b.clone()).merge(c.clone());
+      let right = a.merge(b.merge(c));
+      prop_assert_eq!(changes(&left), changes(&right));
+    }
+
+    #[test]
+    fn statediff_merge_equals_consecutive_apply(
+      a in arbitrary_diff(),
+      b in arbitrary_diff(),
+    ) {
+      let mut consecutive = InMemoryStateStore::default();
+      consecutive.apply(a.clone());
+      consecutive.apply(b.clone());
+
+      let mut merged = InMemoryStateStore::default();
+      merged.apply(a.merge(b));
+
+      prop_assert!(consecutive.iter().eq(merged.iter()));
+    }
+  }
 }
@@ -0,0 +1,541 @@
+use {
+  crate::{InMemoryStateStore, State, StateDiff, VersionedStateStore},
+  anoma_primitives::{
+    encoding,
+    leaf_hash,
+    Account,
+    Address,
+    Digest,
+    MerkleProof,
+    MerkleTree,
+  },
+  multihash::Multihash,
+  serde::{Deserialize, Serialize},
+  std::{
+    borrow::Borrow,
+    collections::{BTreeMap, BTreeSet},
+    sync::Mutex,
+  },
+  thiserror::Error,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Error {
+  #[error("Account {0} was accessed but is not part of the witness")]
+  Incomplete(Address),
+
+  #[error("Witness does not carry merkle proofs")]
+  MissingProofs,
+
+  #[error("Missing merkle proof for account {0}")]
+  MissingProof(Address),
+
+  #[error("Invalid merkle proof for account {0}")]
+  InvalidProof(Address),
+
+  #[error(
+    "Change of account {0} depends on all its descendants, which can't be \
+     proven by a witness"
+  )]
+  UnprovableChange(Address),
+}
+
+/// Wraps a state store and logs every account read from it.
+///
+/// This is used to find out which accounts a transaction needs for its
+/// validation. Run [`crate::execute`] against a recording state, then
+/// turn the log into a [`Witness`] that can be shipped to validators
+/// that do not have access to the full state.
+///
+/// Changes applied to a recording state are kept on top of the wrapped
+/// state and are not recorded, only reads of the wrapped state end up
+/// in the witness.
+pub struct RecordingState<'s> {
+  base: &'s dyn State,
+  changes: StateDiff,
+  reads: Mutex<BTreeMap<Address, Option<Account>>>,
+}
+
+impl<'s> RecordingState<'s> {
+  pub fn new(base: &'s dyn State) -> Self {
+    Self {
+      base,
+      changes: StateDiff::default(),
+      reads: Mutex::new(BTreeMap::new()),
+    }
+  }
+
+  /// Addresses of all accounts read so far, including accounts
+  /// that were looked up but do not exist.
+  pub fn reads(&self) -> BTreeSet<Address> {
+    self.log().keys().cloned().collect()
+  }
+
+  /// Builds a witness of all accounts read so far.
+  pub fn witness(&self) -> Witness {
+    Witness {
+      accounts: self.log().clone(),
+      proofs: None,
+    }
+  }
+
+  /// Builds a witness of all accounts read so far, along with merkle
+  /// proofs of their contents or absence.
+  ///
+  /// The tree must be built over the same state that is wrapped by
+  /// this recording state, otherwise the proofs will not verify.
+  pub fn witness_with_proofs(&self, tree: &StateTree) -> Witness {
+    let accounts = self.log().clone();
+    let proofs = accounts
+      .keys()
+      .map(|addr| (addr.clone(), tree.prove(addr)))
+      .collect();
+    Witness {
+      accounts,
+      proofs: Some(proofs),
+    }
+  }
+
+  fn log(
+    &self,
+  ) -> std::sync::MutexGuard<'_, BTreeMap<Address, Option<Account>>> {
+    self.reads.lock().expect("reads log poisoned")
+  }
+}
+
+impl<'s> State for RecordingState<'s> {
+  fn get(&self, address: &Address) -> Option<Account> {
+    if let Some(change) = self.changes.change(address) {
+      return change.cloned();
+    }
+
+    let account = self.base.get(address);
+    self.log().insert(address.clone(), account.clone());
+    account
+  }
+
+  /// All listed descendants of the base state are recorded, so a witness
+  /// built from the recording lists the same descendants.
+  fn descendants(&self, address: &Address) -> Vec<Address> {
+    let base = self.base.descendants(address);
+    for addr in &base {
+      let account = self.base.get(addr);
+      self.log().insert(addr.clone(), account);
+    }
+    self.changes.descendants_over(base, address)
+  }
+
+  fn apply(&mut self, diff: StateDiff) {
+    self.changes.apply(diff);
+  }
+}
+
+/// The subset of state that is needed to validate a transaction.
+///
+/// Contains all accounts read during validation. Accounts that were
+/// looked up but did not exist are stored as `None`, so a validator can
+/// tell them apart from accounts that are missing from the witness.
+///
+/// Implements the [`State`] trait, so it can be used in place of the full
+/// state by [`crate::execute_with_witness`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Witness {
+  pub accounts: BTreeMap<Address, Option<Account>>,
+  pub proofs: Option<BTreeMap<Address, AccountProof>>,
+}
+
+impl Witness {
+  /// Checks if the witness knows anything about an account.
+  pub fn contains(&self, address: &Address) -> bool {
+    self.accounts.contains_key(address)
+  }
+
+  /// Verifies the contents of the witness against a trusted state root.
+  pub fn verify(&self, root: &Multihash) -> Result<(), Error> {
+    let proofs = self.proofs.as_ref().ok_or(Error::MissingProofs)?;
+    for (addr, account) in &self.accounts {
+      let proof = proofs
+        .get(addr)
+        .ok_or_else(|| Error::MissingProof(addr.clone()))?;
+      if !proof.verify(addr, account.as_ref(), root) {
+        return Err(Error::InvalidProof(addr.clone()));
+      }
+    }
+    Ok(())
+  }
+}
+
+impl State for Witness {
+  fn get(&self, address: &Address) -> Option<Account> {
+    self.accounts.get(address).cloned().flatten()
+  }
+
+  /// Only descendants recorded in the witness are known, proofs
+  /// do not guarantee that no other descendants exist. Changes that
+  /// depend on all descendants are rejected by
+  /// [`crate::execute_with_witness`] for this reason.
+  fn descendants(&self, address: &Address) -> Vec<Address> {
+    self
+      .accounts
+      .iter()
+      .filter(|(addr, acc)| acc.is_some() && address.is_parent_of(addr))
+      .map(|(addr, _)| addr.clone())
+      .collect()
+  }
+
+  /// Applied changes are no longer covered by the original proofs,
+  /// so their proofs are dropped.
+  fn apply(&mut self, diff: StateDiff) {
+    for (addr, account) in diff.iter() {
+      self.accounts.insert(addr.clone(), account.cloned());
+      if let Some(ref mut proofs) = self.proofs {
+        proofs.remove(addr);
+      }
+    }
+  }
+}
+
+/// A merkle tree over all accounts in the state, ordered by address.
+///
+/// Each leaf commits to an account address and the hash of its contents.
+#[derive(Debug, Clone)]
+pub struct StateTree {
+  leaves: Vec<(Address, Digest)>,
+  tree: MerkleTree,
+}
+
+impl StateTree {
+  pub fn new<A, C>(accounts: impl IntoIterator<Item = (A, C)>) -> Self
+  where
+    A: Borrow<Address>,
+    C: Borrow<Account>,
+  {
+    Self::from_hashes(
+      accounts
+        .into_iter()
+        .map(|(addr, acc)| (addr.borrow().clone(), account_hash(acc.borrow())))
+        .collect(),
+    )
+  }
+
+  /// Builds the tree from hashes of accounts, see [`account_hash`].
+  pub(crate) fn from_hashes(hashes: BTreeMap<Address, Digest>) -> Self {
+    let leaves: Vec<_> = hashes.into_iter().collect();
+    let tree = MerkleTree::new(
+      leaves
+        .iter()
+        .map(|(addr, hash)| account_leaf(addr, hash))
+        .collect(),
+    );
+
+    Self { leaves, tree }
+  }
+
+  /// Applies account changes to the tree, so it doesn't have to be
+  /// rebuilt from all accounts after every change.
+  ///
+  /// Only changed accounts are hashed. If no accounts are created or
+  /// deleted, then only the paths from their leaves to the root are
+  /// rehashed, otherwise the nodes above the leaves are rebuilt.
+  pub fn apply(&mut self, diff: &StateDiff) {
+    let mut rebuild = false;
+    for (address, change) in diff.iter() {
+      let position =
+        self.leaves.binary_search_by(|(addr, _)| addr.cmp(address));
+      match (position, change) {
+        (Ok(index), Some(account)) => {
+          let hash = account_hash(account);
+          self.leaves[index].1 = hash;
+          if !rebuild {
+            self.tree.update(index, account_leaf(address, &hash));
+          }
+        }
+        (Ok(index), None) => {
+          self.leaves.remove(index);
+          rebuild = true;
+        }
+        (Err(index), Some(account)) => {
+          let hash = account_hash(account);
+          self.leaves.insert(index, (address.clone(), hash));
+          rebuild = true;
+        }
+        (Err(_), None) => {}
+      }
+    }
+
+    if rebuild {
+      self.tree = MerkleTree::new(
+        self
+          .leaves
+          .iter()
+          .map(|(addr, hash)| account_leaf(addr, hash))
+          .collect(),
+      );
+    }
+  }
+
+  /// The state root that commits to all accounts in the tree.
+  pub fn root(&self) -> Multihash {
+    self.tree.root()
+  }
+
+  /// Proves either the contents or the absence of an account.
+  pub fn prove(&self, address: &Address) -> AccountProof {
+    let neighbour = |index: usize| {
+      let (address, account) = self.leaves[index].clone();
+      Neighbour {
+        address,
+        account,
+        proof: self.tree.proof(index).expect("index within tree"),
+      }
+    };
+
+    match self.leaves.binary_search_by(|(addr, _)| addr.cmp(address)) {
+      Ok(index) => AccountProof::Inclusion(
+        self.tree.proof(index).expect("index within tree"),
+      ),
+      Err(index) => AccountProof::Exclusion {
+        left: index.checked_sub(1).map(neighbour),
+        right: (index < self.leaves.len()).then(|| neighbour(index)),
+      },
+    }
+  }
+}
+
+/// A state that can build a [`StateTree`] over all its accounts.
+pub trait MerkleState: State {
+  fn state_tree(&self) -> StateTree;
+}
+
+impl MerkleState for InMemoryStateStore {
+  fn state_tree(&self) -> StateTree {
+    StateTree::new(self.iter())
+  }
+}
+
+impl MerkleState for VersionedStateStore {
+  fn state_tree(&self) -> StateTree {
+    StateTree::new(self.iter())
+  }
+}
+
+/// Proves the presence or absence of an account in a [`StateTree`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountProof {
+  /// The account exists and its leaf is at the proven position.
+  Inclusion(MerkleProof),
+
+  /// The account does not exist. Proven by showing the two accounts
+  /// that surround its address and occupy adjacent leaves. A missing
+  /// neighbour means that the address sorts before the first or after
+  /// the last account in the tree.
+  Exclusion {
+    left: Option<Neighbour>,
+    right: Option<Neighbour>,
+  },
+}
+
+/// An existing account adjacent to the position of a non-existing one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Neighbour {
+  pub address: Address,
+  pub account: Digest,
+  pub proof: MerkleProof,
+}
+
+impl Neighbour {
+  fn verify(&self, root: &Multihash) -> bool {
+    self
+      .proof
+      .verify(&account_leaf(&self.address, &self.account), root)
+  }
+}
+
+impl AccountProof {
+  /// Verifies that an account under the given address has the given
+  /// contents, or does not exist if `account` is `None`.
+  pub fn verify(
+    &self,
+    address: &Address,
+    account: Option<&Account>,
+    root: &Multihash,
+  ) -> bool {
+    match (self, account) {
+      (AccountProof::Inclusion(proof), Some(account)) => {
+        proof.verify(&account_leaf(address, &account_hash(account)), root)
+      }
+      (AccountProof::Exclusion { left, right }, None) => match (left, right) {
+        (None, None) => MerkleTree::new(vec![]).root() == *root,
+        (Some(left), None) => {
+          &left.address < address
+            && left.proof.index + 1 == left.proof.size
+            && left.verify(root)
+        }
+        (None, Some(right)) => {
+          &right.address > address
+            && right.proof.index == 0
+            && right.verify(root)
+        }
+        (Some(left), Some(right)) => {
+          &left.address < address
+            && &right.address > address
+            && left.proof.size == right.proof.size
+            && left.proof.index + 1 == right.proof.index
+            && left.verify(root)
+            && right.verify(root)
+        }
+      },
+      _ => false,
+    }
+  }
+}
+
+pub(crate) fn account_hash(account: &Account) -> Digest {
+  let mut output = Digest::default();
+  output.copy_from_slice(encoding::hash(account).digest());
+  output
+}
+
+fn account_leaf(address: &Address, account: &Digest) -> Digest {
+  leaf_hash(&encoding::encode(&(address, account)))
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::{AccountProof, Error, RecordingState, StateTree},
+    crate::{InMemoryStateStore, State, StateDiff},
+    anoma_primitives::{Account, Address, Code, Predicate, PredicateTree},
+    std::collections::BTreeSet,
+  };
+
+  fn account_with_state(state: Vec<u8>) -> Account {
+    Account {
+      state,
+      predicates: PredicateTree::Id(Predicate {
+        code: Code::Inline(b"some-code".to_vec()),
+        params: vec![],
+      }),
+    }
+  }
+
+  fn store(addrs: &[&str]) -> anyhow::Result<InMemoryStateStore> {
+    let mut diff = StateDiff::default();
+    for (ix, addr) in addrs.iter().enumerate() {
+      diff.set(addr.parse()?, account_with_state(vec![ix as u8]));
+    }
+    let mut store = InMemoryStateStore::default();
+    store.apply(diff);
+    Ok(store)
+  }
+
+  #[test]
+  fn records_reads() -> anyhow::Result<()> {
+    let store = store(&["/a", "/b", "/c"])?;
+    let mut recording = RecordingState::new(&store);
+
+    let a: Address = "/a".parse()?;
+    let b: Address = "/b".parse()?;
+    let x: Address = "/x".parse()?;
+
+    assert!(recording.get(&a).is_some());
+    assert!(recording.get(&x).is_none());
+
+    // reads of locally applied changes are not part of the witness
+    let mut diff = StateDiff::default();
+    diff.remove(&b);
+    recording.apply(diff);
+    assert!(recording.get(&b).is_none());
+    assert!(store.get(&b).is_some());
+
+    let witness = recording.witness();
+    assert_eq!(recording.reads(), BTreeSet::from([a.clone(), x.clone()]));
+    assert_eq!(witness.get(&a), store.get(&a));
+    assert!(witness.contains(&x));
+    assert!(witness.get(&x).is_none());
+    assert!(!witness.contains(&b));
+    assert_eq!(
+      witness.verify(&StateTree::new(store.iter()).root()),
+      Err(Error::MissingProofs)
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn merkle_proofs() -> anyhow::Result<()> {
+    let store = store(&["/b", "/d", "/f"])?;
+    let tree = StateTree::new(store.iter());
+    let root = tree.root();
+
+    // absent before, between and after existing accounts
+    let recording = RecordingState::new(&store);
+    for addr in ["/a", "/b", "/c", "/d", "/e", "/f", "/g"] {
+      recording.get(&addr.parse()?);
+    }
+
+    let witness = recording.witness_with_proofs(&tree);
+    assert_eq!(witness.verify(&root), Ok(()));
+
+    // tampered contents
+    let mut tampered = witness.clone();
+    let d: Address = "/d".parse()?;
+    tampered
+      .accounts
+      .insert(d.clone(), Some(account_with_state(vec![9])));
+    assert_eq!(tampered.verify(&root), Err(Error::InvalidProof(d.clone())));
+
+    // hidden account
+    let mut hidden = witness.clone();
+    hidden.accounts.insert(d.clone(), None);
+    assert_eq!(hidden.verify(&root), Err(Error::InvalidProof(d.clone())));
+
+    // non-adjacent neighbours
+    let c: Address = "/c".parse()?;
+    let mut skipped = witness.clone();
+    if let Some(AccountProof::Exclusion { right, .. }) =
+      skipped.proofs.as_mut().unwrap().get_mut(&c)
+    {
+      *right = match tree.prove(&"/e".parse()?) {
+        AccountProof::Exclusion { right, .. } => right,
+        _ => unreachable!(),
+      };
+    }
+    assert_eq!(skipped.verify(&root), Err(Error::InvalidProof(c)));
+
+    // empty state
+    let empty = InMemoryStateStore::default();
+    let tree = StateTree::new(empty.iter());
+    let recording = RecordingState::new(&empty);
+    recording.get(&d);
+    assert_eq!(
+      recording.witness_with_proofs(&tree).verify(&tree.root()),
+      Ok(())
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn incremental_updates() -> anyhow::Result<()> {
+    let mut store = store(&["/b", "/d", "/f"])?;
+    let mut tree = StateTree::new(store.iter());
+
+    let mut modified = StateDiff::default();
+    modified.set("/d".parse()?, account_with_state(vec![9]));
+    modified.remove(&"/x".parse()?);
+
+    let mut resized = StateDiff::default();
+    resized.set("/a".parse()?, account_with_state(vec![1]));
+    resized.set("/f".parse()?, account_with_state(vec![2]));
+    resized.remove(&"/b".parse()?);
+
+    for diff in [modified, resized] {
+      tree.apply(&diff);
+      store.apply(diff);
+      assert_eq!(tree.root(), StateTree::new(store.iter()).root());
+    }
+
+    Ok(())
+  }
+}
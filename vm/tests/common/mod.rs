@@ -5,6 +5,9 @@ use {
 
 pub mod token_ops;
 
+/// Chain id that all test intents are signed for.
+pub const CHAIN_ID: &str = "testnet";
+
 use {
   anoma_primitives::{Account, Address, Code, Param, Predicate, PredicateTree},
   anoma_vm::{State, StateDiff},
@@ -38,11 +41,11 @@ pub fn install_standard_library() -> StateDiff {
     predicates: PredicateTree::And(
       Box::new(PredicateTree::Id(Predicate {
         code: Code::AccountRef(stdaddr.clone(), "immutable_state".into()),
-        params: vec![Param::Inline(to_vec(&stdaddr).unwrap())],
+        params: vec![Param::typed(&stdaddr)],
       })),
       Box::new(PredicateTree::Id(Predicate {
         code: Code::AccountRef(stdaddr.clone(), "immutable_predicates".into()),
-        params: vec![Param::Inline(to_vec(&stdaddr).unwrap())],
+        params: vec![Param::typed(&stdaddr)],
       })),
     ),
   });
@@ -79,11 +82,11 @@ pub fn install_token_bytecode() -> StateDiff {
       // immutable predicates & state
       Box::new(PredicateTree::Id(Predicate {
         code: Code::AccountRef(stdaddr.clone(), "immutable_state".into()),
-        params: vec![Param::Inline(to_vec(&tokenaddr).unwrap())],
+        params: vec![Param::typed(&tokenaddr)],
       })),
       Box::new(PredicateTree::Id(Predicate {
         code: Code::AccountRef(stdaddr, "immutable_predicates".into()),
-        params: vec![Param::Inline(to_vec(&tokenaddr).unwrap())],
+        params: vec![Param::typed(&tokenaddr)],
       })),
     ),
   });
@@ -103,10 +106,10 @@ pub fn create_usdx_token(mint_authority: PublicKey) -> StateDiff {
           // input params as per documentation:
 
           // self address, used to identity child wallet balances accounts
-          Param::Inline(to_vec(&tokenaddr).unwrap()),
+          Param::typed(&tokenaddr),
           // mint authority, signature to authorize minting and burning
           // tokens
-          Param::Inline(to_vec(&mint_authority).unwrap()),
+          Param::typed(&mint_authority),
           // reference to an account where the total supply value is stored.
           // we're going to store it in the top-level account itself
           Param::AccountRef(tokenaddr.clone()),
@@ -117,7 +120,7 @@ pub fn create_usdx_token(mint_authority: PublicKey) -> StateDiff {
           "/stdpred/v1".parse().unwrap(),
           "constant".into(),
         ),
-        params: vec![Param::Inline(to_vec(&false).unwrap())],
+        params: vec![Param::typed(&false)],
       })),
     ),
   });
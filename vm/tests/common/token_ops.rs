@@ -2,19 +2,23 @@
 
 use {
   anoma_primitives::{
+    all,
+    any,
+    builder::{
+      account,
+      inline,
+      proposal,
+      stdpred,
+      IntentBuilder,
+      TransactionBuilder,
+    },
     Account,
-    AccountChange,
     Address,
-    Code,
-    Exact,
-    Intent,
-    Param,
-    Predicate,
     PredicateTree,
     Transaction,
   },
   anoma_vm::State,
-  ed25519_dalek::{Keypair, PublicKey, Signer},
+  ed25519_dalek::{Keypair, PublicKey},
   multihash::Multihash,
   rmp_serde::{from_slice, to_vec},
 };
@@ -45,84 +49,32 @@ pub fn mint(
     .unwrap_or(0)
     + amount;
 
-  let mut mint_intent = Intent::new(
-    recent_blockhash,
-    PredicateTree::<Exact>::And(
-      Box::new(PredicateTree::Id(Predicate {
-        // expect that the total supply is updated by the mint amount
-        code: Code::AccountRef("/stdpred/v1".parse()?, "uint_equal".into()),
-        params: vec![
-          Param::ProposalRef("/token/usdx".parse()?),
-          Param::Inline(to_vec(&new_supply)?),
-        ],
-      })),
-      Box::new(PredicateTree::Id(Predicate {
-        // expect that the minted amount is credited to a wallet
-        code: Code::AccountRef("/stdpred/v1".parse()?, "uint_equal".into()),
-        params: vec![
-          Param::ProposalRef(recipient.clone()),
-          Param::Inline(to_vec(&new_balance)?),
-        ],
-      })),
-    ),
-  );
-
-  let recipient_acc_change = match state.get(recipient) {
-    Some(_) => AccountChange::ReplaceState(to_vec(&new_balance)?),
-    None => AccountChange::CreateAccount(Account {
-      // wallet does not exist, create it
-      state: to_vec(&new_balance)?,
-      predicates: PredicateTree::Or(
-        Box::new(PredicateTree::Id(Predicate {
-          // The newly created account will requre a
-          // signature if the balance is deducted,
-          // otherwise its happy to receive tokens
-          // without any authorization.
-          code: Code::AccountRef(
-            "/stdpred/v1".parse()?,
-            "uint_greater_than_equal".into(),
-          ),
-          params: vec![
-            Param::ProposalRef(recipient.clone()),
-            Param::AccountRef(recipient.clone()),
-          ],
-        })),
-        Box::new(PredicateTree::Id(Predicate {
-          // If proposed balance is not greater that current balance
-          // then require a signature to authorize spending
-          code: Code::AccountRef(
-            "/stdpred/v1".parse()?,
-            "require_ed25519_signature".into(),
-          ),
-          params: vec![Param::Inline(recipient_pubkey.to_bytes().to_vec())],
-        })),
-      ),
-    }),
-  };
-
-  // add mint authority signature to the intent
-  let mint_pk_b58 = bs58::encode(auth_keypair.public.as_bytes()).into_string();
-
-  mint_intent.calldata.insert(
-    mint_pk_b58,
-    auth_keypair
-      .sign(mint_intent.signing_hash().to_bytes().as_slice())
-      .to_bytes()
-      .to_vec(),
-  );
-
-  Ok(Transaction::new(
-    vec![mint_intent],
-    [
-      (recipient.clone(), recipient_acc_change),
-      (
-        "/token/usdx".parse()?, // update total supply
-        AccountChange::ReplaceState(to_vec(&new_supply)?),
-      ),
-    ]
-    .into_iter()
-    .collect(),
-  ))
+  // expect that the total supply is updated by the mint amount
+  // and that the minted amount is credited to a wallet, signed
+  // by the mint authority.
+  let mint_intent =
+    IntentBuilder::new(super::CHAIN_ID, recent_blockhash, all![
+      stdpred::uint_equal(proposal("/token/usdx"), inline(new_supply)),
+      stdpred::uint_equal(proposal(recipient), inline(new_balance)),
+    ])
+    .signed_by(auth_keypair)
+    .build();
+
+  let builder = TransactionBuilder::new()
+    .intent(mint_intent)
+    .replace_state("/token/usdx", to_vec(&new_supply)?); // total supply
+
+  Ok(
+    match state.get(recipient) {
+      Some(_) => builder.replace_state(recipient, to_vec(&new_balance)?),
+      None => builder.create(recipient, Account {
+        // wallet does not exist, create it
+        state: to_vec(&new_balance)?,
+        predicates: wallet_predicates(recipient, recipient_pubkey),
+      }),
+    }
+    .build(),
+  )
 }
 
 /// Creates a transaction that transfers a given number of USDX tokens between
@@ -142,41 +94,20 @@ pub fn transfer(
 ) -> anyhow::Result<Transaction> {
   assert!(state.get(sender).is_some());
 
-  let mut transfer_intent = Intent::new(
+  let transfer_intent = IntentBuilder::new(
+    super::CHAIN_ID,
     recent_blockhash,
-    PredicateTree::<Exact>::And(
-      Box::new(PredicateTree::Id(Predicate {
-        code: Code::AccountRef(
-          "/stdpred/v1".parse()?,
-          "uint_less_than_by".into(),
-        ),
-        params: vec![
-          Param::ProposalRef(sender.clone()),
-          Param::AccountRef(sender.clone()),
-          Param::Inline(to_vec(&amount)?),
-        ],
-      })),
-      Box::new(PredicateTree::Id(Predicate {
-        code: Code::AccountRef(
-          "/stdpred/v1".parse()?,
-          "uint_greater_than_equal".into(),
-        ),
-        params: vec![
-          Param::ProposalRef(recipient.clone()),
-          Param::Inline(to_vec(&amount)?),
-        ],
-      })),
-    ),
-  );
-
-  // sign intent by sender
-  transfer_intent.calldata.insert(
-    bs58::encode(sender_keypair.public.as_bytes()).into_string(),
-    sender_keypair
-      .sign(transfer_intent.signing_hash().to_bytes().as_slice())
-      .to_bytes()
-      .to_vec(),
-  );
+    all![
+      stdpred::uint_less_than_by(
+        proposal(sender),
+        account(sender),
+        inline(amount)
+      ),
+      stdpred::uint_greater_than_equal(proposal(recipient), inline(amount)),
+    ],
+  )
+  .signed_by(sender_keypair) // sign intent by sender
+  .build();
 
   let new_sender_balance =
     from_slice::<u64>(&state.get(sender).expect("asserted").state)?
@@ -193,45 +124,22 @@ pub fn transfer(
     })
     .unwrap_or(amount);
 
-  let sender_acc_change =
-    AccountChange::ReplaceState(to_vec(&new_sender_balance)?);
-  let recipient_acc_change = match state.get(recipient) {
-    Some(_) => AccountChange::ReplaceState(to_vec(&new_recipient_balance)?),
-    None => AccountChange::CreateAccount(Account {
-      state: to_vec(&new_recipient_balance)?,
-      predicates: PredicateTree::Or(
-        Box::new(PredicateTree::Id(Predicate {
-          code: Code::AccountRef(
-            "/stdpred/v1".parse()?,
-            "uint_greater_than_equal".into(),
-          ),
-          params: vec![
-            Param::ProposalRef(recipient.clone()),
-            Param::AccountRef(recipient.clone()),
-          ],
-        })),
-        Box::new(PredicateTree::Id(Predicate {
-          // If proposed balance is not greater that current balance
-          // then require a signature to authorize spending
-          code: Code::AccountRef(
-            "/stdpred/v1".parse()?,
-            "require_ed25519_signature".into(),
-          ),
-          params: vec![Param::Inline(recipient_pubkey.to_bytes().to_vec())],
-        })),
-      ),
-    }),
-  };
-
-  Ok(Transaction::new(
-    vec![transfer_intent], //
-    [
-      (recipient.clone(), recipient_acc_change),
-      (sender.clone(), sender_acc_change),
-    ]
-    .into_iter()
-    .collect(),
-  ))
+  let builder = TransactionBuilder::new()
+    .intent(transfer_intent)
+    .replace_state(sender, to_vec(&new_sender_balance)?);
+
+  Ok(
+    match state.get(recipient) {
+      Some(_) => {
+        builder.replace_state(recipient, to_vec(&new_recipient_balance)?)
+      }
+      None => builder.create(recipient, Account {
+        state: to_vec(&new_recipient_balance)?,
+        predicates: wallet_predicates(recipient, recipient_pubkey),
+      }),
+    }
+    .build(),
+  )
 }
 
 pub fn transfer_unchecked(
@@ -244,72 +152,43 @@ pub fn transfer_unchecked(
   recipient_create_account: bool,
   recent_blockhash: Multihash,
 ) -> anyhow::Result<Transaction> {
-  let mut transfer_intent = Intent::new(
+  let transfer_intent = IntentBuilder::new(
+    super::CHAIN_ID,
     recent_blockhash,
-    PredicateTree::<Exact>::And(
-      Box::new(PredicateTree::Id(Predicate {
-        code: Code::AccountRef("/stdpred/v1".parse()?, "uint_equal".into()),
-        params: vec![
-          Param::ProposalRef(sender.clone()),
-          Param::Inline(to_vec(&sender_new_balance)?),
-        ],
-      })),
-      Box::new(PredicateTree::Id(Predicate {
-        code: Code::AccountRef("/stdpred/v1".parse()?, "uint_equal".into()),
-        params: vec![
-          Param::ProposalRef(recipient.clone()),
-          Param::Inline(to_vec(&recipient_new_balance)?),
-        ],
-      })),
-    ),
-  );
-
-  // sign intent by sender
-  transfer_intent.calldata.insert(
-    bs58::encode(sender_keypair.public.as_bytes()).into_string(),
-    sender_keypair
-      .sign(transfer_intent.signing_hash().to_bytes().as_slice())
-      .to_bytes()
-      .to_vec(),
-  );
-
-  let sender_acc_change =
-    AccountChange::ReplaceState(to_vec(&sender_new_balance)?);
-  let recipient_acc_change = match recipient_create_account {
-    true => AccountChange::CreateAccount(Account {
-      state: to_vec(&recipient_new_balance)?,
-      predicates: PredicateTree::Or(
-        Box::new(PredicateTree::Id(Predicate {
-          code: Code::AccountRef(
-            "/stdpred/v1".parse()?,
-            "uint_greater_than_equal".into(),
-          ),
-          params: vec![
-            Param::ProposalRef(recipient.clone()),
-            Param::AccountRef(recipient.clone()),
-          ],
-        })),
-        Box::new(PredicateTree::Id(Predicate {
-          // If proposed balance is not greater that current balance
-          // then require a signature to authorize spending
-          code: Code::AccountRef(
-            "/stdpred/v1".parse()?,
-            "require_ed25519_signature".into(),
-          ),
-          params: vec![Param::Inline(recipient_pubkey.to_bytes().to_vec())],
-        })),
-      ),
-    }),
-    false => AccountChange::ReplaceState(to_vec(&recipient_new_balance)?),
-  };
+    all![
+      stdpred::uint_equal(proposal(sender), inline(sender_new_balance)),
+      stdpred::uint_equal(proposal(recipient), inline(recipient_new_balance)),
+    ],
+  )
+  .signed_by(sender_keypair) // sign intent by sender
+  .build();
+
+  let builder = TransactionBuilder::new()
+    .intent(transfer_intent)
+    .replace_state(sender, to_vec(&sender_new_balance)?);
+
+  Ok(
+    match recipient_create_account {
+      true => builder.create(recipient, Account {
+        state: to_vec(&recipient_new_balance)?,
+        predicates: wallet_predicates(recipient, recipient_pubkey),
+      }),
+      false => {
+        builder.replace_state(recipient, to_vec(&recipient_new_balance)?)
+      }
+    }
+    .build(),
+  )
+}
 
-  Ok(Transaction::new(
-    vec![transfer_intent], //
-    [
-      (recipient.clone(), recipient_acc_change),
-      (sender.clone(), sender_acc_change),
-    ]
-    .into_iter()
-    .collect(),
-  ))
+/// Predicates of a newly created wallet account.
+///
+/// The wallet is happy to receive tokens without any authorization, but
+/// if the proposed balance is not greater than the current balance then
+/// a signature is required to authorize spending.
+fn wallet_predicates(wallet: &Address, owner: &PublicKey) -> PredicateTree {
+  any![
+    stdpred::uint_greater_than_equal(proposal(wallet), account(wallet)),
+    stdpred::require_ed25519_signature(owner),
+  ]
 }
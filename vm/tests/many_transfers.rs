@@ -1,6 +1,6 @@
 use {
   anoma_primitives::{Account, Address, Code, Param, Predicate, PredicateTree},
-  anoma_vm::{InMemoryStateStore, State, StateDiff},
+  anoma_vm::{InMemoryStateStore, Rules, State, StateDiff},
   common::{create_initial_blockchain_state, precache_predicates_bytecode},
   ed25519_dalek::Keypair,
   multihash::MultihashDigest,
@@ -12,6 +12,7 @@ mod common;
 
 #[test]
 fn mint_then_transfers() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
   let mint_keypair = Keypair::generate(&mut rand::thread_rng());
   let recent_blockhash = multihash::Code::Sha3_256.digest(b"test2");
 
@@ -83,7 +84,8 @@ fn mint_then_transfers() -> anyhow::Result<()> {
   }
 
   let started = Instant::now();
-  let results = anoma_vm::execute_many(&store, &cache, txs.into_iter());
+  let results =
+    anoma_vm::execute_many(1, &rules, &store, &cache, txs.into_iter());
   println!("elapsed: {:?}", started.elapsed());
 
   assert_eq!(results.len(), 1001);
@@ -101,6 +103,7 @@ fn mint_then_transfers() -> anyhow::Result<()> {
 
 #[test]
 fn many_independent_transfers() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
   // in this test we don't want to have any sequencial dependencies between txs
   // and all of them have to run in parallel
   let mint_keypair = Keypair::generate(&mut rand::thread_rng());
@@ -178,7 +181,8 @@ fn many_independent_transfers() -> anyhow::Result<()> {
   }
 
   let started = Instant::now();
-  let results = anoma_vm::execute_many(&store, &cache, txs.into_iter());
+  let results =
+    anoma_vm::execute_many(1, &rules, &store, &cache, txs.into_iter());
   println!("elapsed: {:?}", started.elapsed());
   assert_eq!(results.len(), 1000);
 
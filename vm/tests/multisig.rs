@@ -0,0 +1,102 @@
+mod common;
+use {
+  anoma_primitives::{
+    builder::{
+      inline,
+      proposal,
+      stdpred,
+      threshold,
+      IntentBuilder,
+      TransactionBuilder,
+    },
+    Account,
+  },
+  anoma_vm::{InMemoryStateStore, Rules, RuntimeError, State, StateDiff},
+  common::{create_initial_blockchain_state, precache_predicates_bytecode},
+  ed25519_dalek::Keypair,
+  multihash::MultihashDigest,
+  rmp_serde::{from_slice, to_vec},
+};
+
+#[test]
+fn two_of_three_multisig() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
+  let recent_blockhash = multihash::Code::Sha3_256.digest(b"test4");
+  let owners: Vec<_> = (0..3)
+    .map(|_| Keypair::generate(&mut rand::thread_rng()))
+    .collect();
+
+  let mut store = InMemoryStateStore::default();
+  store.apply(create_initial_blockchain_state(owners[0].public));
+
+  let mut cache = InMemoryStateStore::default();
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/stdpred/v1".parse().unwrap(),
+  ));
+
+  // any two of three owners can change the vault
+  let vault = "/vault.eth";
+  let mut diff = StateDiff::default();
+  diff.set(vault.parse()?, Account {
+    state: to_vec(&0u64)?,
+    predicates: threshold(
+      2,
+      owners
+        .iter()
+        .map(|owner| stdpred::require_ed25519_signature(&owner.public)),
+    ),
+  });
+  store.apply(diff);
+
+  let update = |value: u64, signers: &[&Keypair]| {
+    let intent = signers
+      .iter()
+      .fold(
+        IntentBuilder::new(
+          common::CHAIN_ID,
+          recent_blockhash,
+          stdpred::uint_equal(proposal(vault), inline(value)),
+        ),
+        |builder, signer| builder.signed_by(signer),
+      )
+      .build();
+    TransactionBuilder::new()
+      .intent(intent)
+      .replace_state(vault, to_vec(&value).unwrap())
+      .build()
+  };
+
+  // a single owner is not enough
+  assert!(matches!(
+    anoma_vm::execute(update(1, &[&owners[1]]), 1, &rules, &store, &cache),
+    Err(RuntimeError::Rejected(_))
+  ));
+
+  // any two owners are
+  store.apply(anoma_vm::execute(
+    update(2, &[&owners[0], &owners[2]]),
+    1,
+    &rules,
+    &store,
+    &cache,
+  )?);
+  assert_eq!(
+    from_slice::<u64>(&store.get(&vault.parse()?).unwrap().state)?,
+    2
+  );
+
+  store.apply(anoma_vm::execute(
+    update(3, &[&owners[0], &owners[1], &owners[2]]),
+    2,
+    &rules,
+    &store,
+    &cache,
+  )?);
+  assert_eq!(
+    from_slice::<u64>(&store.get(&vault.parse()?).unwrap().state)?,
+    3
+  );
+
+  Ok(())
+}
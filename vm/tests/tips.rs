@@ -0,0 +1,216 @@
+mod common;
+use {
+  anoma_primitives::{
+    all,
+    builder::{
+      account,
+      inline,
+      proposal,
+      stdpred,
+      IntentBuilder,
+      TransactionBuilder,
+    },
+    Address,
+  },
+  anoma_vm::{InMemoryStateStore, Rules, State},
+  common::{create_initial_blockchain_state, precache_predicates_bytecode},
+  ed25519_dalek::Keypair,
+  multihash::MultihashDigest,
+  rmp_serde::{from_slice, to_vec},
+};
+
+#[test]
+fn solver_claims_tips() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
+  let mint_keypair = Keypair::generate(&mut rand::thread_rng());
+  let recent_blockhash = multihash::Code::Sha3_256.digest(b"test6");
+
+  let mut store = InMemoryStateStore::default();
+  store.apply(create_initial_blockchain_state(mint_keypair.public));
+
+  let mut cache = InMemoryStateStore::default();
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/token".parse().unwrap(),
+  ));
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/stdpred/v1".parse().unwrap(),
+  ));
+
+  let alice_keypair = Keypair::generate(&mut rand::thread_rng());
+  let alice: Address = "/token/usdx/alice.eth".parse()?;
+  let bob: Address = "/token/usdx/bob.eth".parse()?;
+  let solver: Address = "/solver.eth".parse()?;
+  let solver_wallet: Address = "/token/usdx/solver.eth".parse()?;
+
+  // tips are paid to existing wallets
+  let solver_keypair = Keypair::generate(&mut rand::thread_rng());
+  for (wallet, keypair, amount) in [
+    (&alice, &alice_keypair, 1000),
+    (&bob, &Keypair::generate(&mut rand::thread_rng()), 0),
+    (&solver_wallet, &solver_keypair, 0),
+  ] {
+    store.apply(anoma_vm::execute(
+      common::token_ops::mint(
+        amount,
+        wallet,
+        &keypair.public,
+        &mint_keypair,
+        recent_blockhash,
+        &store,
+      )?,
+      1,
+      &rules,
+      &store,
+      &cache,
+    )?);
+  }
+
+  // alice sends 400 tokens to bob and pays 10 tokens to the solver
+  let intent = IntentBuilder::new(common::CHAIN_ID, recent_blockhash, all![
+    stdpred::uint_less_than_by(
+      proposal(&alice),
+      account(&alice),
+      inline(410u64)
+    ),
+    stdpred::uint_greater_than_equal(proposal(&bob), inline(400u64)),
+  ])
+  .tip("/token/usdx", 10)
+  .signed_by(&alice_keypair)
+  .build();
+
+  let transfer = |bob_balance: u64| {
+    TransactionBuilder::new()
+      .intent(intent.clone())
+      .replace_state(&alice, to_vec(&590u64).unwrap())
+      .replace_state(&bob, to_vec(&bob_balance).unwrap())
+  };
+
+  // the tip can't be claimed without naming the solver
+  let unnamed = transfer(400)
+    .replace_state(&solver_wallet, to_vec(&10u64)?)
+    .build();
+  assert!(anoma_vm::execute(unnamed, 2, &rules, &store, &cache).is_err());
+
+  // or by claiming less than the tip
+  let underpaid = transfer(405)
+    .replace_state(&solver_wallet, to_vec(&5u64)?)
+    .solver(&solver)
+    .build();
+  assert!(anoma_vm::execute(underpaid, 2, &rules, &store, &cache).is_err());
+
+  let claimed = transfer(400)
+    .claim_tips(&solver, |wallet| balance(&store, wallet))?
+    .build();
+  let diff = anoma_vm::execute(claimed, 2, &rules, &store, &cache)?;
+  store.apply(diff);
+
+  assert_eq!(balance(&store, &alice), 590);
+  assert_eq!(balance(&store, &bob), 400);
+  assert_eq!(balance(&store, &solver_wallet), 10);
+
+  Ok(())
+}
+
+#[test]
+fn every_tip_is_paid() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
+  let mint_keypair = Keypair::generate(&mut rand::thread_rng());
+  let recent_blockhash = multihash::Code::Sha3_256.digest(b"test7");
+
+  let mut store = InMemoryStateStore::default();
+  store.apply(create_initial_blockchain_state(mint_keypair.public));
+
+  let mut cache = InMemoryStateStore::default();
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/token".parse().unwrap(),
+  ));
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/stdpred/v1".parse().unwrap(),
+  ));
+
+  let alice_keypair = Keypair::generate(&mut rand::thread_rng());
+  let alice: Address = "/token/usdx/alice.eth".parse()?;
+  let bob: Address = "/token/usdx/bob.eth".parse()?;
+  let solver: Address = "/solver.eth".parse()?;
+  let solver_wallet: Address = "/token/usdx/solver.eth".parse()?;
+
+  for (wallet, keypair, amount) in [
+    (&alice, &alice_keypair, 1000),
+    (&bob, &Keypair::generate(&mut rand::thread_rng()), 0),
+    (
+      &solver_wallet,
+      &Keypair::generate(&mut rand::thread_rng()),
+      0,
+    ),
+  ] {
+    store.apply(anoma_vm::execute(
+      common::token_ops::mint(
+        amount,
+        wallet,
+        &keypair.public,
+        &mint_keypair,
+        recent_blockhash,
+        &store,
+      )?,
+      1,
+      &rules,
+      &store,
+      &cache,
+    )?);
+  }
+
+  // alice sends 400 tokens to bob and tips 10 tokens in each of two
+  // intents, both tips are paid to the same wallet of the solver.
+  let transfer = IntentBuilder::new(common::CHAIN_ID, recent_blockhash, all![
+    stdpred::uint_less_than_by(
+      proposal(&alice),
+      account(&alice),
+      inline(420u64)
+    ),
+    stdpred::uint_greater_than_equal(proposal(&bob), inline(400u64)),
+  ])
+  .tip("/token/usdx", 10)
+  .signed_by(&alice_keypair)
+  .build();
+  let receipt = IntentBuilder::new(
+    common::CHAIN_ID,
+    recent_blockhash,
+    stdpred::uint_greater_than_equal(proposal(&bob), inline(400u64)),
+  )
+  .tip("/token/usdx", 10)
+  .signed_by(&alice_keypair)
+  .build();
+
+  let transaction = |alice_balance: u64| {
+    TransactionBuilder::new()
+      .intent(transfer.clone())
+      .intent(receipt.clone())
+      .replace_state(&alice, to_vec(&alice_balance).unwrap())
+      .replace_state(&bob, to_vec(&400u64).unwrap())
+  };
+
+  // a single payment doesn't pay both tips
+  let single = transaction(590)
+    .replace_state(&solver_wallet, to_vec(&10u64)?)
+    .solver(&solver)
+    .build();
+  assert!(anoma_vm::execute(single, 2, &rules, &store, &cache).is_err());
+
+  let claimed = transaction(580)
+    .claim_tips(&solver, |wallet| balance(&store, wallet))?
+    .build();
+  store.apply(anoma_vm::execute(claimed, 2, &rules, &store, &cache)?);
+
+  assert_eq!(balance(&store, &alice), 580);
+  assert_eq!(balance(&store, &solver_wallet), 20);
+
+  Ok(())
+}
+
+fn balance(state: &impl State, wallet: &Address) -> u64 {
+  from_slice(&state.get(wallet).unwrap().state).unwrap()
+}
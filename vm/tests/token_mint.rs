@@ -1,7 +1,7 @@
 mod common;
 use {
   anoma_primitives::{Address, Code, Param, Predicate, PredicateTree},
-  anoma_vm::{InMemoryStateStore, State},
+  anoma_vm::{InMemoryStateStore, Rules, State},
   common::{create_initial_blockchain_state, precache_predicates_bytecode},
   ed25519_dalek::Keypair,
   multihash::MultihashDigest,
@@ -10,6 +10,7 @@ use {
 
 #[test]
 fn mint_tokens() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
   let mint_keypair = Keypair::generate(&mut rand::thread_rng());
   let recent_blockhash = multihash::Code::Sha3_256.digest(b"test1");
 
@@ -37,7 +38,7 @@ fn mint_tokens() -> anyhow::Result<()> {
   )?;
 
   // run transaction in the VM and get state diff
-  let outdiff = anoma_vm::execute(mint_tx, &store, &cache)?;
+  let outdiff = anoma_vm::execute(mint_tx, 1, &rules, &store, &cache)?;
 
   assert_eq!(outdiff.iter().count(), 2);
   assert!(outdiff.get(&"/token/usdx".parse()?).is_some());
@@ -66,8 +67,8 @@ fn mint_tokens() -> anyhow::Result<()> {
       .get(&"/token/usdx/wallet1.eth".parse()?)
       .unwrap()
       .predicates,
-    PredicateTree::Or(
-      Box::new(PredicateTree::Id(Predicate {
+    PredicateTree::Any(vec![
+      PredicateTree::Id(Predicate {
         code: Code::AccountRef(
           "/stdpred/v1".parse()?,
           "uint_greater_than_equal".into(),
@@ -76,15 +77,15 @@ fn mint_tokens() -> anyhow::Result<()> {
           Param::ProposalRef("/token/usdx/wallet1.eth".parse()?),
           Param::AccountRef("/token/usdx/wallet1.eth".parse()?),
         ],
-      })),
-      Box::new(PredicateTree::Id(Predicate {
+      }),
+      PredicateTree::Id(Predicate {
         code: Code::AccountRef(
           "/stdpred/v1".parse()?,
           "require_ed25519_signature".into(),
         ),
-        params: vec![Param::Inline(wallet1keypair.public.to_bytes().to_vec(),)],
-      })),
-    )
+        params: vec![Param::Inline(wallet1keypair.public.to_bytes().to_vec())],
+      }),
+    ])
   );
 
   // assert that the token account has its predicates
@@ -96,8 +97,8 @@ fn mint_tokens() -> anyhow::Result<()> {
       Box::new(PredicateTree::Id(Predicate {
         code: Code::AccountRef("/token".parse().unwrap(), "predicate".into()),
         params: vec![
-          Param::Inline(to_vec(&tokenaddr).unwrap()),
-          Param::Inline(to_vec(&mint_keypair.public).unwrap()),
+          Param::typed(&tokenaddr),
+          Param::typed(&mint_keypair.public),
           Param::AccountRef(tokenaddr.clone()),
         ],
       })),
@@ -106,7 +107,7 @@ fn mint_tokens() -> anyhow::Result<()> {
           "/stdpred/v1".parse().unwrap(),
           "immutable_predicates".into(),
         ),
-        params: vec![Param::Inline(to_vec(&tokenaddr).unwrap())],
+        params: vec![Param::typed(&tokenaddr)],
       })),
     )
   );
@@ -124,7 +125,7 @@ fn mint_tokens() -> anyhow::Result<()> {
   )?;
 
   // second mint tx
-  store.apply(anoma_vm::execute(second_mint, &store, &cache)?);
+  store.apply(anoma_vm::execute(second_mint, 2, &rules, &store, &cache)?);
 
   // prev mint 1000 + second mint 500
   assert_eq!(
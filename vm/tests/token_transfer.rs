@@ -1,5 +1,5 @@
 use {
-  anoma_vm::{InMemoryStateStore, State},
+  anoma_vm::{InMemoryStateStore, Rules, State},
   common::{create_initial_blockchain_state, precache_predicates_bytecode},
   ed25519_dalek::Keypair,
   multihash::MultihashDigest,
@@ -10,6 +10,7 @@ mod common;
 
 #[test]
 fn transfer_token() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
   let mint_keypair = Keypair::generate(&mut rand::thread_rng());
   let recent_blockhash = multihash::Code::Sha3_256.digest(b"test3");
 
@@ -41,6 +42,8 @@ fn transfer_token() -> anyhow::Result<()> {
       recent_blockhash,
       &store,
     )?,
+    1,
+    &rules,
     &store,
     &cache,
   )?);
@@ -62,6 +65,8 @@ fn transfer_token() -> anyhow::Result<()> {
       recent_blockhash,
       &store,
     )?,
+    2,
+    &rules,
     &store,
     &cache,
   )?);
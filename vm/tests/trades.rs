@@ -0,0 +1,97 @@
+mod common;
+use {
+  anoma_primitives::{
+    builder::{stdpred, IntentBuilder, TransactionBuilder},
+    Address,
+    Resource,
+    Trade,
+  },
+  anoma_vm::{InMemoryStateStore, Rules, State},
+  common::{create_initial_blockchain_state, precache_predicates_bytecode},
+  ed25519_dalek::Keypair,
+  multihash::MultihashDigest,
+  rmp_serde::to_vec,
+};
+
+#[test]
+fn trades_are_enforced() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
+  let mint_keypair = Keypair::generate(&mut rand::thread_rng());
+  let recent_blockhash = multihash::Code::Sha3_256.digest(b"test7");
+
+  let mut store = InMemoryStateStore::default();
+  store.apply(create_initial_blockchain_state(mint_keypair.public));
+
+  let mut cache = InMemoryStateStore::default();
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/token".parse().unwrap(),
+  ));
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/stdpred/v1".parse().unwrap(),
+  ));
+
+  let alice_keypair = Keypair::generate(&mut rand::thread_rng());
+  let alice: Address = "/token/usdx/alice.eth".parse()?;
+  let bob: Address = "/token/usdx/bob.eth".parse()?;
+  let carol: Address = "/token/usdx/carol.eth".parse()?;
+
+  for (wallet, amount) in [(&alice, 1000), (&bob, 0), (&carol, 0)] {
+    store.apply(anoma_vm::execute(
+      common::token_ops::mint(
+        amount,
+        wallet,
+        &alice_keypair.public,
+        &mint_keypair,
+        recent_blockhash,
+        &store,
+      )?,
+      1,
+      &rules,
+      &store,
+      &cache,
+    )?);
+  }
+
+  // alice offers up to 100 tokens to bob
+  let trade = Trade::new("/alice.eth".parse()?).offer(
+    Resource::new("/token/usdx".parse()?, 100)
+      .with_counterparty("/bob.eth".parse()?),
+  );
+  let intent = IntentBuilder::new(
+    common::CHAIN_ID,
+    recent_blockhash,
+    stdpred::require_ed25519_signature(&alice_keypair.public),
+  )
+  .trade(trade)
+  .signed_by(&alice_keypair)
+  .build();
+
+  let pay = |recipient: &Address, amount: u64| {
+    TransactionBuilder::new()
+      .intent(intent.clone())
+      .replace_state(&alice, to_vec(&(1000 - amount)).unwrap())
+      .replace_state(recipient, to_vec(&amount).unwrap())
+      .build()
+  };
+
+  // more than offered
+  assert!(anoma_vm::execute(pay(&bob, 101), 2, &rules, &store, &cache).is_err());
+
+  // not to the counterparty
+  assert!(
+    anoma_vm::execute(pay(&carol, 100), 2, &rules, &store, &cache).is_err()
+  );
+
+  store.apply(anoma_vm::execute(
+    pay(&bob, 100),
+    2,
+    &rules,
+    &store,
+    &cache,
+  )?);
+  assert_eq!(store.get(&bob).unwrap().state, to_vec(&100u64)?);
+
+  Ok(())
+}
@@ -0,0 +1,170 @@
+mod common;
+use {
+  anoma_primitives::{
+    builder::{stdpred, IntentBuilder, TransactionBuilder},
+    Account,
+    ValidationError,
+  },
+  anoma_vm::{InMemoryStateStore, Rules, RuntimeError, State, StateDiff},
+  common::{create_initial_blockchain_state, precache_predicates_bytecode},
+  ed25519_dalek::Keypair,
+  multihash::MultihashDigest,
+};
+
+#[test]
+fn intents_are_valid_within_window() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
+  let recent_blockhash = multihash::Code::Sha3_256.digest(b"test5");
+  let keypair = Keypair::generate(&mut rand::thread_rng());
+
+  let mut store = InMemoryStateStore::default();
+  store.apply(create_initial_blockchain_state(keypair.public));
+
+  let mut cache = InMemoryStateStore::default();
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/stdpred/v1".parse().unwrap(),
+  ));
+
+  let mut diff = StateDiff::default();
+  diff.set("/open.eth".parse()?, Account {
+    state: vec![],
+    predicates: stdpred::constant(true),
+  });
+  store.apply(diff);
+
+  // only within blocks 10 to 20
+  let tx = TransactionBuilder::new()
+    .intent(
+      IntentBuilder::new(
+        common::CHAIN_ID,
+        recent_blockhash,
+        stdpred::constant(true),
+      )
+      .valid_from(10)
+      .valid_until(20)
+      .signed_by(&keypair)
+      .build(),
+    )
+    .replace_state("/open.eth", vec![1])
+    .build();
+
+  assert!(matches!(
+    anoma_vm::execute(tx.clone(), 9, &rules, &store, &cache),
+    Err(RuntimeError::IntentNotValid(_, 9))
+  ));
+  assert!(anoma_vm::execute(tx.clone(), 10, &rules, &store, &cache).is_ok());
+  assert!(anoma_vm::execute(tx.clone(), 20, &rules, &store, &cache).is_ok());
+
+  let results =
+    anoma_vm::execute_many(21, &rules, &store, &cache, [tx].into_iter());
+  assert!(matches!(results.as_slice(), [Err(
+    RuntimeError::IntentNotValid(_, 21)
+  )]));
+
+  Ok(())
+}
+
+#[test]
+fn intents_of_other_chains_are_rejected() -> anyhow::Result<()> {
+  let keypair = Keypair::generate(&mut rand::thread_rng());
+  let mut store = InMemoryStateStore::default();
+  store.apply(create_initial_blockchain_state(keypair.public));
+
+  let mut cache = InMemoryStateStore::default();
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/stdpred/v1".parse().unwrap(),
+  ));
+
+  let mut diff = StateDiff::default();
+  diff.set("/open.eth".parse()?, Account {
+    state: vec![],
+    predicates: stdpred::constant(true),
+  });
+  store.apply(diff);
+
+  let tx = TransactionBuilder::new()
+    .intent(
+      IntentBuilder::new(
+        "othernet",
+        multihash::Code::Sha3_256.digest(b"test6"),
+        stdpred::constant(true),
+      )
+      .signed_by(&keypair)
+      .build(),
+    )
+    .replace_state("/open.eth", vec![1])
+    .build();
+
+  let rules = Rules::new(common::CHAIN_ID);
+  assert!(matches!(
+    anoma_vm::execute(tx.clone(), 1, &rules, &store, &cache),
+    Err(RuntimeError::WrongChain(_, chain)) if chain == "othernet"
+  ));
+  let results =
+    anoma_vm::execute_many(1, &rules, &store, &cache, [tx.clone()].into_iter());
+  assert!(matches!(results.as_slice(), [Err(
+    RuntimeError::WrongChain(..)
+  )]));
+
+  let rules = Rules::new("othernet");
+  assert!(anoma_vm::execute(tx, 1, &rules, &store, &cache).is_ok());
+
+  Ok(())
+}
+
+#[test]
+fn malformed_transactions_are_rejected() -> anyhow::Result<()> {
+  let rules = Rules::new(common::CHAIN_ID);
+  let recent_blockhash = multihash::Code::Sha3_256.digest(b"test8");
+  let keypair = Keypair::generate(&mut rand::thread_rng());
+
+  let mut store = InMemoryStateStore::default();
+  store.apply(create_initial_blockchain_state(keypair.public));
+
+  let mut cache = InMemoryStateStore::default();
+  cache.apply(precache_predicates_bytecode(
+    &store,
+    &"/stdpred/v1".parse().unwrap(),
+  ));
+
+  let mut diff = StateDiff::default();
+  diff.set("/open.eth".parse()?, Account {
+    state: vec![],
+    predicates: stdpred::constant(true),
+  });
+  store.apply(diff);
+
+  let tx = |state: Option<Vec<u8>>| {
+    let builder = TransactionBuilder::new().intent(
+      IntentBuilder::new(
+        common::CHAIN_ID,
+        recent_blockhash,
+        stdpred::constant(true),
+      )
+      .build(),
+    );
+    match state {
+      Some(state) => builder.replace_state("/open.eth", state),
+      None => builder,
+    }
+    .build()
+  };
+
+  let results = anoma_vm::execute_many(
+    1,
+    &rules,
+    &store,
+    &cache,
+    [tx(Some(vec![1])), tx(None), tx(Some(vec![2]))].into_iter(),
+  );
+
+  assert!(matches!(results.as_slice(), [
+    Ok(_),
+    Err(RuntimeError::Invalid(ValidationError::NoProposals)),
+    Ok(_)
+  ]));
+
+  Ok(())
+}
@@ -0,0 +1,67 @@
+use {
+  anoma_primitives::{
+    Account,
+    AccountChange,
+    Address,
+    PredicateTree,
+    Transaction,
+  },
+  anoma_vm::{InMemoryStateStore, Rules, RuntimeError, Witness, WitnessError},
+  std::collections::BTreeMap,
+};
+
+#[test]
+fn changes_of_descendants_are_rejected() -> anyhow::Result<()> {
+  let address: Address = "/pgqf/1".parse()?;
+  let cache = InMemoryStateStore::default();
+
+  // a witness can't prove that it carries all descendants of an account,
+  // deleting a single account depends on them only under hierarchy rules.
+  for (change, hierarchy) in [
+    (AccountChange::DeleteAccount, true),
+    (AccountChange::DeleteSubtree, false),
+    (AccountChange::MoveSubtree("/archive/1".parse()?), false),
+  ] {
+    let mut proposals = BTreeMap::new();
+    proposals.insert(address.clone(), change);
+    let tx = Transaction::new(vec![], proposals);
+    let rules = Rules {
+      hierarchy,
+      ..Rules::new("testnet")
+    };
+
+    assert!(matches!(
+      anoma_vm::execute_with_witness(tx, 1, &rules, &Witness::default(), &cache),
+      Err(RuntimeError::Witness(WitnessError::UnprovableChange(a)))
+        if a == address
+    ));
+  }
+
+  Ok(())
+}
+
+#[test]
+fn accounts_are_deleted_without_hierarchy_rules() -> anyhow::Result<()> {
+  let address: Address = "/pgqf/1".parse()?;
+  let cache = InMemoryStateStore::default();
+
+  let account = Account {
+    state: vec![],
+    predicates: PredicateTree::All(vec![]),
+  };
+  let mut witness = Witness::default();
+  witness
+    .accounts
+    .insert("/pgqf".parse()?, Some(account.clone()));
+  witness.accounts.insert(address.clone(), Some(account));
+
+  let mut proposals = BTreeMap::new();
+  proposals.insert(address.clone(), AccountChange::DeleteAccount);
+  let tx = Transaction::new(vec![], proposals);
+
+  let rules = Rules::new("testnet");
+  let diff = anoma_vm::execute_with_witness(tx, 1, &rules, &witness, &cache)?;
+  assert_eq!(diff.iter().collect::<Vec<_>>(), vec![(&address, None)]);
+
+  Ok(())
+}